@@ -0,0 +1,63 @@
+// Embeds build provenance into the benchmark.
+//
+// - `FLECHASDB_BENCHMARK_GIT_COMMIT`: git commit of the benchmark; suffixed
+//   with `-dirty` if there are uncommitted changes.
+// - `FLECHASDB_VERSION`: version and source of `flechasdb` in `Cargo.lock`.
+// - `FLECHASDB_BENCHMARK_PROFILE`: build profile.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!(
+        "cargo:rustc-env=FLECHASDB_BENCHMARK_GIT_COMMIT={}",
+        git_commit().unwrap_or_else(|| "unknown".to_string()),
+    );
+    println!(
+        "cargo:rustc-env=FLECHASDB_VERSION={}",
+        flechasdb_version().unwrap_or_else(|| "unknown".to_string()),
+    );
+    println!(
+        "cargo:rustc-env=FLECHASDB_BENCHMARK_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string()),
+    );
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .is_some_and(|output| !output.stdout.is_empty());
+    if dirty {
+        Some(format!("{}-dirty", commit))
+    } else {
+        Some(commit)
+    }
+}
+
+// Reads the version and source of `flechasdb` from `Cargo.lock`.
+fn flechasdb_version() -> Option<String> {
+    let lock = std::fs::read_to_string("Cargo.lock").ok()?;
+    let package = lock
+        .split("[[package]]")
+        .find(|package| package.lines().any(|line| line == "name = \"flechasdb\""))?;
+    let field = |key: &str| package
+        .lines()
+        .find_map(|line| line.strip_prefix(key))
+        .map(|value| value.trim_matches('"').to_string());
+    let version = field("version = ")?;
+    match field("source = ") {
+        Some(source) => Some(format!("{} ({})", version, source)),
+        None => Some(version),
+    }
+}
@@ -0,0 +1,455 @@
+//! Attributes assigned to vectors at build time.
+//!
+//! Attributes are loaded from a sidecar file keyed by vector index, or
+//! synthesized randomly.
+//!
+//! # Sidecar file formats
+//!
+//! The format is determined from the file extension.
+//!
+//! ## CSV (`*.csv`)
+//!
+//! The first row is a header.
+//! The first column is the vector index, and each of the other columns is an
+//! attribute whose header is `name:type`.
+//! `type` is either `string` or `u64`, and `string` if omitted.
+//! Fields are separated by commas and cannot be quoted.
+//! An empty field leaves the attribute unset.
+//!
+//! ```text
+//! index,title:string,year:u64
+//! 0,foo,2001
+//! 1,bar,2023
+//! ```
+//!
+//! ## JSON Lines (`*.jsonl`)
+//!
+//! Each line is an object that has the vector index in `index` field.
+//! Every other field is an attribute; strings are mapped to
+//! [`AttributeValue::String`] and unsigned integers to
+//! [`AttributeValue::Uint64`].
+//! `null` leaves the attribute unset.
+//!
+//! ```text
+//! {"index": 0, "title": "foo", "year": 2001}
+//! {"index": 1, "title": "bar", "year": 2023}
+//! ```
+
+use core::str::FromStr;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use flechasdb::db::AttributeValue;
+
+use crate::error::Error;
+
+/// Attributes of a single vector.
+#[derive(Clone, Debug)]
+pub struct AttributeRecord {
+    /// Index of the vector.
+    pub index: usize,
+    /// Pairs of attribute name and value.
+    pub values: Vec<(String, AttributeValue)>,
+}
+
+/// Reads a given attributes sidecar file.
+///
+/// Fails if the extension is neither `csv` nor `jsonl`.
+pub fn read_attributes_file(
+    path: impl AsRef<Path>,
+) -> Result<Vec<AttributeRecord>, Error> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let read = BufReader::new(File::open(path)?);
+    match extension {
+        Some("csv") => read_attributes_csv(read),
+        Some("jsonl") => read_attributes_jsonl(read),
+        _ => Err(Error::InvalidData(format!(
+            "unsupported attributes file: {:?}",
+            path,
+        ))),
+    }
+}
+
+/// Reads attributes in the CSV format.
+pub fn read_attributes_csv(
+    read: impl BufRead,
+) -> Result<Vec<AttributeRecord>, Error> {
+    let mut lines = read.lines();
+    let header = match lines.next() {
+        Some(line) => line?,
+        None => return Ok(Vec::new()),
+    };
+    let columns = header
+        .split(',')
+        .skip(1)
+        .map(parse_column)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut records: Vec<AttributeRecord> = Vec::new();
+    for (li, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split(',');
+        let index = parse_index(fields.next().unwrap(), li + 2)?;
+        let mut values: Vec<(String, AttributeValue)> =
+            Vec::with_capacity(columns.len());
+        for (ci, field) in fields.enumerate() {
+            let (name, column_type) = columns.get(ci).ok_or_else(|| {
+                Error::InvalidData(format!("too many fields at line {}", li + 2))
+            })?;
+            if field.is_empty() {
+                continue;
+            }
+            let value = match column_type {
+                ColumnType::String => AttributeValue::String(field.to_string()),
+                ColumnType::Uint64 => AttributeValue::Uint64(
+                    field.trim().parse().map_err(|_| Error::InvalidData(format!(
+                        "{} is not a u64 at line {}: {}",
+                        name,
+                        li + 2,
+                        field,
+                    )))?,
+                ),
+            };
+            values.push((name.clone(), value));
+        }
+        records.push(AttributeRecord { index, values });
+    }
+    Ok(records)
+}
+
+/// Reads attributes in the JSON Lines format.
+pub fn read_attributes_jsonl(
+    read: impl BufRead,
+) -> Result<Vec<AttributeRecord>, Error> {
+    let mut records: Vec<AttributeRecord> = Vec::new();
+    for (li, line) in read.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let object: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&line).map_err(|e| Error::InvalidData(
+                format!("invalid JSON at line {}: {}", li + 1, e),
+            ))?;
+        let index = object
+            .get("index")
+            .and_then(|index| index.as_u64())
+            .ok_or_else(|| Error::InvalidData(format!(
+                "missing index at line {}",
+                li + 1,
+            )))? as usize;
+        let mut values: Vec<(String, AttributeValue)> =
+            Vec::with_capacity(object.len() - 1);
+        for (name, value) in object.into_iter() {
+            if name == "index" {
+                continue;
+            }
+            let value = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(s) => AttributeValue::String(s),
+                serde_json::Value::Number(n) if n.is_u64() =>
+                    AttributeValue::Uint64(n.as_u64().unwrap()),
+                _ => return Err(Error::InvalidData(format!(
+                    "{} is neither a string nor u64 at line {}: {}",
+                    name,
+                    li + 1,
+                    value,
+                ))),
+            };
+            values.push((name, value));
+        }
+        records.push(AttributeRecord { index, values });
+    }
+    Ok(records)
+}
+
+/// Synthetic attribute.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyntheticAttribute {
+    /// Category uniformly chosen from `num_categories` categories.
+    ///
+    /// Values are strings `c0`, `c1`, ….
+    Category {
+        /// Number of categories.
+        num_categories: usize,
+    },
+    /// Timestamp in seconds since the UNIX epoch uniformly chosen from the
+    /// past year.
+    Timestamp,
+}
+
+impl SyntheticAttribute {
+    /// Returns the name of the attribute.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SyntheticAttribute::Category { .. } => "category",
+            SyntheticAttribute::Timestamp => "timestamp",
+        }
+    }
+
+    /// Generates a random value.
+    pub fn generate(&self, rng: &mut impl Rng) -> AttributeValue {
+        match self {
+            SyntheticAttribute::Category { num_categories } => {
+                AttributeValue::String(
+                    format!("c{}", rng.gen_range(0..*num_categories)),
+                )
+            },
+            SyntheticAttribute::Timestamp => {
+                const ORIGIN: u64 = 1_672_531_200; // 2023-01-01T00:00:00Z
+                const YEAR: u64 = 365 * 24 * 60 * 60;
+                AttributeValue::Uint64(ORIGIN + rng.gen_range(0..YEAR))
+            },
+        }
+    }
+}
+
+/// Generator of synthetic attributes of vectors in order of the vectors.
+///
+/// The same attributes and seed reproduce the same values, so that the
+/// values assigned by `build` can be reconstructed without the database.
+pub struct SyntheticAttributeGenerator {
+    attributes: Vec<SyntheticAttribute>,
+    rng: StdRng,
+}
+
+impl SyntheticAttributeGenerator {
+    /// Creates a generator of given attributes with a given seed.
+    pub fn new(attributes: &[SyntheticAttribute], seed: u64) -> Self {
+        Self {
+            attributes: attributes.to_vec(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Generates the values of every attribute for the next vector.
+    ///
+    /// Values are in the same order as the attributes.
+    pub fn generate(&mut self) -> Vec<(&'static str, AttributeValue)> {
+        self.attributes
+            .iter()
+            .map(|attribute| (attribute.name(), attribute.generate(&mut self.rng)))
+            .collect()
+    }
+}
+
+impl FromStr for SyntheticAttribute {
+    type Err = Error;
+
+    /// Parses `category:<NUM_CATEGORIES>` or `timestamp`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("category", n)) => {
+                let num_categories: usize = n.parse().map_err(|_| {
+                    Error::InvalidData(format!("invalid number of categories: {}", n))
+                })?;
+                if num_categories == 0 {
+                    return Err(Error::InvalidData(
+                        "number of categories must be positive".to_string(),
+                    ));
+                }
+                Ok(SyntheticAttribute::Category { num_categories })
+            },
+            None if s == "timestamp" => Ok(SyntheticAttribute::Timestamp),
+            _ => Err(Error::InvalidData(format!(
+                "unknown synthetic attribute: {}",
+                s,
+            ))),
+        }
+    }
+}
+
+// Type of a CSV column.
+enum ColumnType {
+    String,
+    Uint64,
+}
+
+// Parses a CSV column header `name:type`.
+fn parse_column(header: &str) -> Result<(String, ColumnType), Error> {
+    let header = header.trim();
+    match header.rsplit_once(':') {
+        Some((name, "string")) => Ok((name.to_string(), ColumnType::String)),
+        Some((name, "u64")) => Ok((name.to_string(), ColumnType::Uint64)),
+        Some((_, t)) => Err(Error::InvalidData(format!(
+            "unsupported column type: {}",
+            t,
+        ))),
+        None => Ok((header.to_string(), ColumnType::String)),
+    }
+}
+
+// Parses a vector index in a CSV line.
+fn parse_index(field: &str, line: usize) -> Result<usize, Error> {
+    field.trim().parse().map_err(|_| Error::InvalidData(format!(
+        "invalid index at line {}: {}",
+        line,
+        field,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns whether a value is a given string.
+    fn is_string(value: &AttributeValue, expected: &str) -> bool {
+        matches!(value, AttributeValue::String(s) if s == expected)
+    }
+
+    // Returns whether a value is a given u64.
+    fn is_uint64(value: &AttributeValue, expected: u64) -> bool {
+        matches!(value, AttributeValue::Uint64(n) if *n == expected)
+    }
+
+    #[test]
+    fn read_csv_typed_and_untyped_columns() {
+        let csv = "index,title:string,year:u64,note\n0,foo,2001,x\n1,bar,2023,y\n";
+        let records = read_attributes_csv(csv.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].index, 1);
+        let values = &records[1].values;
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0].0, "title");
+        assert!(is_string(&values[0].1, "bar"));
+        assert_eq!(values[1].0, "year");
+        assert!(is_uint64(&values[1].1, 2023));
+        assert_eq!(values[2].0, "note");
+        assert!(is_string(&values[2].1, "y"));
+    }
+
+    #[test]
+    fn read_csv_empty_fields() {
+        let csv = "index,title,year:u64\n0,,2001\n1,bar,\n";
+        let records = read_attributes_csv(csv.as_bytes()).unwrap();
+        assert_eq!(records[0].values.len(), 1);
+        assert_eq!(records[0].values[0].0, "year");
+        assert_eq!(records[1].values.len(), 1);
+        assert_eq!(records[1].values[0].0, "title");
+    }
+
+    #[test]
+    fn read_csv_too_many_fields() {
+        let csv = "index,title\n0,foo,bar\n";
+        assert!(matches!(
+            read_attributes_csv(csv.as_bytes()),
+            Err(Error::InvalidData(_)),
+        ));
+    }
+
+    #[test]
+    fn read_csv_non_u64() {
+        for field in ["-1", "1.5", "abc"] {
+            let csv = format!("index,year:u64\n0,{}\n", field);
+            assert!(matches!(
+                read_attributes_csv(csv.as_bytes()),
+                Err(Error::InvalidData(_)),
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_column_headers() {
+        assert!(matches!(
+            parse_column("year:u64"),
+            Ok((name, ColumnType::Uint64)) if name == "year",
+        ));
+        assert!(matches!(
+            parse_column(" title:string "),
+            Ok((name, ColumnType::String)) if name == "title",
+        ));
+        assert!(matches!(
+            parse_column("title"),
+            Ok((name, ColumnType::String)) if name == "title",
+        ));
+        assert!(matches!(
+            parse_column("score:f32"),
+            Err(Error::InvalidData(_)),
+        ));
+    }
+
+    #[test]
+    fn read_jsonl_null_and_types() {
+        let jsonl = "{\"index\": 0, \"title\": \"foo\", \"year\": 2001}\n\
+                     \n\
+                     {\"index\": 1, \"title\": null, \"year\": 2023}\n";
+        let records = read_attributes_jsonl(jsonl.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        let values = &records[0].values;
+        assert_eq!(values.len(), 2);
+        assert!(values
+            .iter()
+            .any(|(name, value)| name == "title" && is_string(value, "foo")));
+        assert!(values
+            .iter()
+            .any(|(name, value)| name == "year" && is_uint64(value, 2001)));
+        assert_eq!(records[1].index, 1);
+        assert_eq!(records[1].values.len(), 1);
+        assert_eq!(records[1].values[0].0, "year");
+    }
+
+    #[test]
+    fn read_jsonl_missing_index() {
+        let jsonl = "{\"title\": \"foo\"}\n";
+        assert!(matches!(
+            read_attributes_jsonl(jsonl.as_bytes()),
+            Err(Error::InvalidData(_)),
+        ));
+    }
+
+    #[test]
+    fn read_jsonl_non_u64() {
+        for value in ["-1", "1.5", "true", "[1]"] {
+            let jsonl = format!("{{\"index\": 0, \"year\": {}}}\n", value);
+            assert!(matches!(
+                read_attributes_jsonl(jsonl.as_bytes()),
+                Err(Error::InvalidData(_)),
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_synthetic_attributes() {
+        assert_eq!(
+            "category:4".parse::<SyntheticAttribute>().unwrap(),
+            SyntheticAttribute::Category { num_categories: 4 },
+        );
+        assert_eq!(
+            "timestamp".parse::<SyntheticAttribute>().unwrap(),
+            SyntheticAttribute::Timestamp,
+        );
+        for s in ["category:0", "category:x", "category", "timestamp:1", "rating"] {
+            assert!(
+                matches!(s.parse::<SyntheticAttribute>(), Err(Error::InvalidData(_))),
+                "{} is accepted",
+                s,
+            );
+        }
+    }
+
+    #[test]
+    fn synthetic_attributes_are_reproducible() {
+        let attributes = [
+            SyntheticAttribute::Category { num_categories: 3 },
+            SyntheticAttribute::Timestamp,
+        ];
+        let generate = |seed| {
+            let mut generator = SyntheticAttributeGenerator::new(&attributes, seed);
+            (0..16)
+                .flat_map(|_| generator.generate())
+                .map(|(name, value)| format!("{}={:?}", name, value))
+                .collect::<Vec<_>>()
+        };
+        let values = generate(42);
+        assert_eq!(values, generate(42));
+        assert_ne!(values, generate(43));
+        assert!(values[0].starts_with("category="));
+        assert!(values[1].starts_with("timestamp="));
+    }
+}
@@ -0,0 +1,36 @@
+//! Report on building a database.
+
+use serde::{Deserialize, Serialize};
+
+use crate::provenance::Provenance;
+use crate::resources::ResourceUsage;
+
+/// Report on building a database.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BuildReport {
+    /// Size of each vector.
+    pub vector_size: usize,
+    /// Number of vectors.
+    pub num_vectors: usize,
+    /// Number of partitions.
+    pub num_partitions: usize,
+    /// Number of subvector divisions.
+    pub num_divisions: usize,
+    /// Number of clusters (codes).
+    pub num_codes: usize,
+    /// Time to build the database in seconds.
+    pub build_seconds: f64,
+    /// Time to assign attributes in seconds.
+    ///
+    /// Includes the vector indices (`datum_id`) but not loading the
+    /// attributes file.
+    pub attribute_seconds: f64,
+    /// Time to save the database in seconds.
+    pub save_seconds: f64,
+    /// Disk usage of the saved database in bytes.
+    pub disk_bytes: u64,
+    /// Resource usage during the build.
+    pub resources: ResourceUsage,
+    /// Provenance of the report.
+    pub provenance: Provenance,
+}
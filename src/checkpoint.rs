@@ -0,0 +1,314 @@
+//! Checkpoints of batch queries.
+//!
+//! A checkpoint file is a JSON Lines file whose first line is a
+//! [`CheckpointHeader`] followed by a [`CheckpointEntry`] per query.
+//! Entries hold every measurement that goes into the statistics, so that
+//! replaying them reproduces the statistics of an uninterrupted run.
+//!
+//! Entries are buffered in memory and appended to the file by
+//! [`CheckpointWriter::sync`].
+//! An incomplete last line left by an interruption during a sync is
+//! discarded on resuming.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::io::IoSnapshot;
+
+/// Header of a checkpoint file.
+///
+/// A checkpoint can be resumed only by a batch with the same header.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct CheckpointHeader {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Whether asynchronously executed.
+    pub r#async: bool,
+    /// Number of queries in the batch.
+    pub num_queries: usize,
+    /// Whether datum IDs of results are skipped.
+    pub skip_attributes: bool,
+    /// Attributes looked up in addition to `datum_id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lookup_attributes: Vec<String>,
+    /// Whether query phases are recorded.
+    pub events: bool,
+    /// Whether I/O operations are counted.
+    pub io_stats: bool,
+    /// Whether first-touch queries are separated.
+    pub first_touch: bool,
+    /// Workload that generated the queries if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workload: Option<String>,
+    /// Whether the database is reloaded before every query.
+    #[serde(default)]
+    pub cold: bool,
+    /// Number of warmup queries.
+    ///
+    /// Measured queries follow the warmup queries.
+    #[serde(default)]
+    pub warmup: usize,
+    /// Simulated remote storage if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    /// Build parameters of the database built in memory if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_memory: Option<String>,
+    /// SHA-256 hashes of the input files keyed by role; e.g., `queries`.
+    ///
+    /// The hash of the database header file identifies the entire database.
+    #[serde(default)]
+    pub inputs: Vec<(String, String)>,
+}
+
+/// Entry of a single query in a checkpoint file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CheckpointEntry {
+    /// Index of the query vector in the query vector set.
+    pub query_index: usize,
+    /// Measurement of the query.
+    ///
+    /// `None` if the query failed.
+    pub measurement: Option<QueryMeasurement>,
+}
+
+/// Measurement of a single query that goes into the statistics.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryMeasurement {
+    /// k-NN search time in seconds.
+    pub search_seconds: f64,
+    /// Attribute (`datum_id`) resolution time in seconds.
+    ///
+    /// `None` if attributes are not resolved.
+    pub attribute_seconds: Option<f64>,
+    /// Flat query time in seconds.
+    ///
+    /// `None` if attributes are not resolved.
+    pub flat_seconds: Option<f64>,
+    /// Recall.
+    ///
+    /// `None` if attributes are not resolved.
+    pub recall: Option<f32>,
+    /// Durations of query phases if query events are recorded.
+    pub phases: Option<Vec<(String, f64)>>,
+    /// I/O operations if counted.
+    pub io: Option<IoSnapshot>,
+    /// Kind of the query if generated from a workload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workload: Option<String>,
+    /// Lookup time of each attribute in seconds if any attribute is looked
+    /// up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute_lookups: Option<Vec<(String, f64)>>,
+}
+
+/// Checkpoint read from a file.
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// Header.
+    pub header: CheckpointHeader,
+    /// Entries of completed queries in order.
+    pub entries: Vec<CheckpointEntry>,
+    // length of the complete lines in bytes.
+    valid_len: u64,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint file.
+    ///
+    /// An incomplete last line is ignored.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)?;
+        // only lines terminated with a newline are complete
+        let complete_len = contents
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let mut lines = contents[..complete_len]
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty());
+        let header: CheckpointHeader = lines
+            .next()
+            .ok_or_else(|| Error::InvalidData(format!(
+                "empty checkpoint: {:?}",
+                path,
+            )))
+            .and_then(|line| parse_line(path, line))?;
+        let entries = lines
+            .map(|line| parse_line(path, line))
+            .collect::<Result<Vec<CheckpointEntry>, Error>>()?;
+        // measured queries start after the warmup queries
+        if let Some((i, entry)) = entries
+            .iter()
+            .enumerate()
+            .find(|(i, entry)| entry.query_index != header.warmup + i)
+        {
+            return Err(Error::InvalidData(format!(
+                "checkpoint entries out of order: expected query {} but got {}",
+                header.warmup + i,
+                entry.query_index,
+            )));
+        }
+        Ok(Self {
+            header,
+            entries,
+            valid_len: complete_len as u64,
+        })
+    }
+
+    /// Returns the index of the query vector of the first query not in the
+    /// checkpoint.
+    ///
+    /// Measured queries start after the warmup queries.
+    pub fn next_query_index(&self) -> usize {
+        self.header.warmup + self.entries.len()
+    }
+}
+
+// Parses a line of a checkpoint file.
+fn parse_line<T>(path: &Path, line: &[u8]) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    serde_json::from_slice(line)
+        .map_err(|e| Error::InvalidData(format!(
+            "failed to parse checkpoint {:?}: {}",
+            path,
+            e,
+        )))
+}
+
+/// Writer of a checkpoint file.
+pub struct CheckpointWriter {
+    file: File,
+    pending: Vec<u8>,
+}
+
+impl CheckpointWriter {
+    /// Creates a checkpoint file and writes the header.
+    pub fn create(
+        path: impl AsRef<Path>,
+        header: &CheckpointHeader,
+    ) -> Result<Self, Error> {
+        let mut writer = Self {
+            file: File::create(path)?,
+            pending: Vec::new(),
+        };
+        writer.push_line(header)?;
+        writer.sync()?;
+        Ok(writer)
+    }
+
+    /// Opens a checkpoint file to append entries after a given checkpoint.
+    ///
+    /// An incomplete last line is truncated.
+    pub fn resume(
+        path: impl AsRef<Path>,
+        checkpoint: &Checkpoint,
+    ) -> Result<Self, Error> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        file.set_len(checkpoint.valid_len)?;
+        file.sync_data()?;
+        Ok(Self {
+            file,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Buffers an entry.
+    pub fn write(&mut self, entry: &CheckpointEntry) -> Result<(), Error> {
+        self.push_line(entry)
+    }
+
+    /// Appends the buffered entries to the file and syncs it to the disk.
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.file.write_all(&self.pending)?;
+        self.file.sync_data()?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    // Buffers a value as a line.
+    fn push_line(&mut self, value: &impl Serialize) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.pending, value)
+            .map_err(|e| Error::InvalidData(format!(
+                "failed to serialize checkpoint: {}",
+                e,
+            )))?;
+        self.pending.push(b'\n');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns a path in the temporary directory unique to the process.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "flechasdb-benchmark-{}-{}.jsonl",
+            name,
+            std::process::id(),
+        ))
+    }
+
+    fn header(warmup: usize) -> CheckpointHeader {
+        CheckpointHeader {
+            k: 10,
+            nprobe: 2,
+            r#async: false,
+            num_queries: 4,
+            skip_attributes: true,
+            lookup_attributes: Vec::new(),
+            events: false,
+            io_stats: false,
+            first_touch: false,
+            workload: None,
+            cold: false,
+            warmup,
+            remote: None,
+            in_memory: None,
+            inputs: Vec::new(),
+        }
+    }
+
+    // Writes a checkpoint with entries of given query indices.
+    fn write_checkpoint(path: &Path, warmup: usize, query_indices: &[usize]) {
+        let mut writer = CheckpointWriter::create(path, &header(warmup))
+            .unwrap();
+        for &query_index in query_indices {
+            writer.write(&CheckpointEntry {
+                query_index,
+                measurement: None,
+            }).unwrap();
+        }
+        writer.sync().unwrap();
+    }
+
+    #[test]
+    fn resume_after_warmup() {
+        let path = temp_path("checkpoint-warmup");
+        write_checkpoint(&path, 3, &[3, 4]);
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.header, header(3));
+        assert_eq!(checkpoint.entries.len(), 2);
+        assert_eq!(checkpoint.next_query_index(), 5);
+    }
+
+    #[test]
+    fn reject_entries_ignoring_warmup() {
+        let path = temp_path("checkpoint-no-warmup");
+        write_checkpoint(&path, 3, &[0, 1]);
+        let result = Checkpoint::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+    }
+}
@@ -0,0 +1,300 @@
+//! Comparison of query statistics between two runs.
+//!
+//! Detects regressions of query time and recalls between a baseline and a
+//! candidate; e.g., before and after upgrading `flechasdb`.
+
+use crate::error::Error;
+use crate::stats::{QueryStats, Stats};
+
+/// Thresholds of regressions.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// Maximum allowed increase of query time in percent.
+    pub max_time_increase: f64,
+    /// Maximum allowed decrease of recalls in percentage points.
+    pub max_recall_decrease: f64,
+    /// Significance level of the Mann–Whitney U test on query time.
+    pub significance: f64,
+}
+
+/// Change of a metric.
+#[derive(Clone, Debug)]
+pub struct MetricDelta {
+    /// Name of the metric including the unit.
+    pub name: &'static str,
+    /// Value in the baseline.
+    pub baseline: f64,
+    /// Value in the candidate.
+    pub candidate: f64,
+    /// Whether the change exceeds the threshold.
+    pub regressed: bool,
+}
+
+impl MetricDelta {
+    /// Returns the difference from the baseline to the candidate.
+    pub fn delta(&self) -> f64 {
+        self.candidate - self.baseline
+    }
+
+    /// Returns the change relative to the baseline in percent.
+    pub fn percent_change(&self) -> f64 {
+        self.delta() / self.baseline * 100.0
+    }
+}
+
+/// Comparison between a baseline and a candidate.
+#[derive(Debug)]
+pub struct Comparison {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Whether queries are asynchronously executed.
+    pub r#async: bool,
+    /// Changes of metrics.
+    pub metrics: Vec<MetricDelta>,
+    /// Mann–Whitney U test on the raw query time.
+    ///
+    /// `None` unless both statistics retain raw samples.
+    pub time_test: Option<MannWhitney>,
+}
+
+impl Comparison {
+    /// Compares a candidate with a baseline.
+    ///
+    /// A query time metric regresses if it increases more than
+    /// [`Thresholds::max_time_increase`] and the Mann–Whitney U test on the
+    /// raw query time is significant at [`Thresholds::significance`].
+    /// The test is omitted if either statistics lacks raw samples.
+    /// A recall metric regresses if it decreases more than
+    /// [`Thresholds::max_recall_decrease`].
+    ///
+    /// Fails if `k`, `nprobe`, or the execution mode does not match.
+    pub fn new(
+        baseline: &QueryStats,
+        candidate: &QueryStats,
+        thresholds: &Thresholds,
+    ) -> Result<Self, Error> {
+        if baseline.k != candidate.k
+            || baseline.nprobe != candidate.nprobe
+            || baseline.r#async != candidate.r#async
+        {
+            return Err(Error::InvalidData(format!(
+                "statistics do not match: (k={}, nprobe={}, async={}) ≠ \
+                 (k={}, nprobe={}, async={})",
+                baseline.k,
+                baseline.nprobe,
+                baseline.r#async,
+                candidate.k,
+                candidate.nprobe,
+                candidate.r#async,
+            )));
+        }
+        let time_test = baseline.samples
+            .as_ref()
+            .zip(candidate.samples.as_ref())
+            .and_then(|(b, c)| mann_whitney_u(&b.seconds, &c.seconds));
+        let significant = time_test
+            .is_none_or(|test| test.p_value < thresholds.significance);
+        let time_metric = |name, f: fn(&Stats<f64>) -> f64| {
+            let baseline = f(&baseline.seconds) * 1_000.0;
+            let candidate = f(&candidate.seconds) * 1_000.0;
+            let increase = (candidate - baseline) / baseline * 100.0;
+            MetricDelta {
+                name,
+                baseline,
+                candidate,
+                regressed: significant
+                    && increase > thresholds.max_time_increase,
+            }
+        };
+        let mut metrics = vec![
+            time_metric("time mean (ms)", |s| s.mean),
+            time_metric("time median (ms)", |s| s.median),
+            time_metric("time q3 (ms)", |s| s.q3),
+        ];
+        if let Some((b, c)) = baseline.recalls
+            .as_ref()
+            .zip(candidate.recalls.as_ref())
+        {
+            let recall_metric = |name, f: fn(&Stats<f32>) -> f32| {
+                let baseline = f(b) as f64 * 100.0;
+                let candidate = f(c) as f64 * 100.0;
+                MetricDelta {
+                    name,
+                    baseline,
+                    candidate,
+                    regressed:
+                        baseline - candidate > thresholds.max_recall_decrease,
+                }
+            };
+            metrics.push(recall_metric("recall mean (%)", |s| s.mean));
+            metrics.push(recall_metric("recall median (%)", |s| s.median));
+        }
+        Ok(Comparison {
+            k: baseline.k,
+            nprobe: baseline.nprobe,
+            r#async: baseline.r#async,
+            metrics,
+            time_test,
+        })
+    }
+
+    /// Returns whether any metric regressed.
+    pub fn has_regression(&self) -> bool {
+        self.metrics.iter().any(|metric| metric.regressed)
+    }
+}
+
+/// Result of the Mann–Whitney U test.
+#[derive(Clone, Copy, Debug)]
+pub struct MannWhitney {
+    /// U statistic of the first sample.
+    pub u: f64,
+    /// Standard score of `u` with continuity correction.
+    pub z: f64,
+    /// Two-sided p-value.
+    pub p_value: f64,
+}
+
+/// Performs the two-sided Mann–Whitney U test.
+///
+/// The p-value is approximated with the normal distribution corrected for
+/// ties, which is accurate enough for the number of queries in a benchmark.
+///
+/// Returns `None` if either sample has fewer than two values.
+pub fn mann_whitney_u(xs: &[f64], ys: &[f64]) -> Option<MannWhitney> {
+    if xs.len() < 2 || ys.len() < 2 {
+        return None;
+    }
+    let n1 = xs.len() as f64;
+    let n2 = ys.len() as f64;
+    let n = n1 + n2;
+    // (value, whether from xs)
+    let mut values: Vec<(f64, bool)> = xs
+        .iter()
+        .map(|&x| (x, true))
+        .chain(ys.iter().map(|&y| (y, false)))
+        .collect();
+    values.sort_by(|l, r| l.0.partial_cmp(&r.0).unwrap());
+    // assigns average ranks to ties
+    let mut rank_sum = 0.0f64;
+    let mut tie_sum = 0.0f64;
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i + 1;
+        while j < values.len() && values[j].0 == values[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 * 0.5;
+        rank_sum += rank * values[i..j].iter().filter(|v| v.1).count() as f64;
+        let t = (j - i) as f64;
+        tie_sum += t * t * t - t;
+        i = j;
+    }
+    let u = rank_sum - n1 * (n1 + 1.0) * 0.5;
+    let mean = n1 * n2 * 0.5;
+    let var = n1 * n2 / 12.0 * ((n + 1.0) - tie_sum / (n * (n - 1.0)));
+    if var <= 0.0 {
+        return Some(MannWhitney { u, z: 0.0, p_value: 1.0 });
+    }
+    let diff = u - mean;
+    let z = (diff.abs() - 0.5).max(0.0) * diff.signum() / var.sqrt();
+    Some(MannWhitney {
+        u,
+        z,
+        p_value: erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0),
+    })
+}
+
+// Complementary error function.
+//
+// Chebyshev approximation from Numerical Recipes whose fractional error is
+// less than 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223 + t * (1.00002368 + t * (0.37409196
+        + t * (0.09678418 + t * (-0.18628806 + t * (0.27886807
+        + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223
+        + t * 0.17087277))))))))).exp();
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Asserts that `actual` is within a relative `tolerance` of `expected`.
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance * expected.abs(),
+            "{} is not close to {}",
+            actual,
+            expected,
+        );
+    }
+
+    #[test]
+    fn mann_whitney_u_reference_values() {
+        // example in the SciPy documentation of `mannwhitneyu`
+        let test = mann_whitney_u(
+            &[19.0, 22.0, 16.0, 29.0, 24.0],
+            &[20.0, 11.0, 17.0, 12.0],
+        ).unwrap();
+        assert_eq!(test.u, 17.0);
+        assert_close(test.p_value, 0.11134688653314041, 1e-6);
+        // completely separated samples
+        let test = mann_whitney_u(
+            &[1.0, 2.0, 3.0, 4.0, 5.0],
+            &[6.0, 7.0, 8.0, 9.0, 10.0],
+        ).unwrap();
+        assert_eq!(test.u, 0.0);
+        assert!(test.z < 0.0);
+        assert_close(test.p_value, 0.012185780355344818, 1e-6);
+    }
+
+    #[test]
+    fn mann_whitney_u_with_ties() {
+        let test = mann_whitney_u(&[1.0, 2.0, 2.0, 3.0], &[2.0, 3.0, 4.0, 5.0])
+            .unwrap();
+        assert_eq!(test.u, 2.5);
+        assert_close(test.p_value, 0.13665824773814753, 1e-6);
+    }
+
+    #[test]
+    fn mann_whitney_u_all_ties() {
+        let test = mann_whitney_u(&[1.0, 1.0, 1.0], &[1.0, 1.0]).unwrap();
+        assert_eq!(test.u, 3.0);
+        assert_eq!(test.z, 0.0);
+        assert_eq!(test.p_value, 1.0);
+    }
+
+    #[test]
+    fn mann_whitney_u_identical_samples() {
+        let xs = [0.1, 0.2, 0.3, 0.4];
+        let test = mann_whitney_u(&xs, &xs).unwrap();
+        assert_eq!(test.u, 8.0);
+        assert_eq!(test.z, 0.0);
+        assert_eq!(test.p_value, 1.0);
+    }
+
+    #[test]
+    fn mann_whitney_u_too_few_values() {
+        assert!(mann_whitney_u(&[], &[1.0, 2.0]).is_none());
+        assert!(mann_whitney_u(&[1.0, 2.0], &[]).is_none());
+        assert!(mann_whitney_u(&[1.0], &[1.0, 2.0]).is_none());
+        assert!(mann_whitney_u(&[1.0, 2.0], &[3.0]).is_none());
+    }
+
+    #[test]
+    fn erfc_reference_values() {
+        // the approximation has a fractional error less than 1.2e-7
+        assert_close(erfc(0.0), 1.0, 1.2e-7);
+        assert_close(erfc(1.0), 0.15729920705028513, 1.2e-7);
+        assert_close(erfc(-1.0), 1.842700792949715, 1.2e-7);
+        assert_close(erfc(5.0), 1.5374597944280351e-12, 1.2e-7);
+        assert_close(erfc(10.0), 2.088487583762545e-45, 1.2e-7);
+        assert_close(erfc(-10.0), 2.0, 1.2e-7);
+    }
+}
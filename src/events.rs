@@ -0,0 +1,123 @@
+//! Timelines of query events.
+//!
+//! Durations of query phases are derived from events notified by
+//! `query_with_events`.
+//! A phase starts at an event and ends at the next event.
+//! A phase is named after the event that starts it without arguments; e.g.,
+//! `StartingPartitionQuery(3)` starts the `StartingPartitionQuery` phase.
+//! Events whose names start with `Finished` only end the preceding phase.
+
+use core::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::error::Error;
+use crate::stats::Stats;
+
+/// Timeline of events notified during a single query.
+#[derive(Default)]
+pub struct EventTimeline {
+    // debug representation of each event and when it was notified.
+    events: Vec<(String, Instant)>,
+}
+
+impl EventTimeline {
+    /// Creates an empty timeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a given event at the current time.
+    pub fn record(&mut self, event: &impl Debug) {
+        let time = Instant::now();
+        self.events.push((format!("{:?}", event), time));
+    }
+
+    /// Returns the debug representation of each event and when it was
+    /// notified in order.
+    pub fn events(&self) -> &[(String, Instant)] {
+        &self.events
+    }
+
+    /// Returns the total duration in seconds of each phase.
+    ///
+    /// `end` ends the last phase unless it is ended by a `Finished` event.
+    /// Durations of the phases of the same name are summed.
+    /// Phases are in order of their first appearance.
+    pub fn phase_durations(&self, end: Instant) -> Vec<(String, f64)> {
+        let mut durations: Vec<(String, f64)> = Vec::new();
+        for (i, (event, start)) in self.events.iter().enumerate() {
+            let name = phase_name(event);
+            if name.starts_with("Finished") {
+                continue;
+            }
+            let next = self.events.get(i + 1).map_or(end, |(_, time)| *time);
+            let seconds = next.duration_since(*start).as_secs_f64();
+            match durations.iter_mut().find(|(n, _)| n == name) {
+                Some((_, total)) => *total += seconds,
+                None => durations.push((name.to_string(), seconds)),
+            }
+        }
+        durations
+    }
+}
+
+/// Recorder of statistics on query phases.
+#[derive(Default)]
+pub struct PhaseStatsRecorder {
+    phases: Vec<(String, Vec<f64>)>,
+}
+
+impl PhaseStatsRecorder {
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds phase durations of a single query.
+    pub fn add_durations(&mut self, durations: Vec<(String, f64)>) {
+        for (name, seconds) in durations.into_iter() {
+            match self.phases.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, records)) => records.push(seconds),
+                None => self.phases.push((name, vec![seconds])),
+            }
+        }
+    }
+
+    /// Finishes recording and computes the statistics.
+    ///
+    /// Fails if any phase has no duration.
+    pub fn finish(self) -> Result<Vec<PhaseStats>, Error> {
+        self.phases
+            .into_iter()
+            .map(|(name, records)| {
+                let num_queries = records.len();
+                let seconds = Stats::compute_if_any(records)
+                    .ok_or_else(|| Error::InvalidData(format!(
+                        "no duration of phase {}",
+                        name,
+                    )))?;
+                Ok(PhaseStats { name, num_queries, seconds })
+            })
+            .collect()
+    }
+}
+
+/// Statistics on a query phase.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PhaseStats {
+    /// Name of the phase.
+    pub name: String,
+    /// Number of queries that went through the phase.
+    pub num_queries: usize,
+    /// Total duration of the phase per query in seconds.
+    pub seconds: Stats<f64>,
+}
+
+// Returns the name of an event without arguments.
+fn phase_name(event: &str) -> &str {
+    match event.find(['(', '{', ' ']) {
+        Some(i) => &event[..i],
+        None => event,
+    }
+}
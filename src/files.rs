@@ -0,0 +1,42 @@
+//! Utilities for database files.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Extension of the database header file.
+pub const HEADER_EXTENSION: &str = "binpb";
+
+/// Calculates the total size of files in a given folder in bytes.
+///
+/// Subfolders are recursively visited.
+pub fn disk_usage(path: impl AsRef<Path>) -> Result<u64, Error> {
+    let mut total: u64 = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            total += disk_usage(entry.path())?;
+        } else {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Lists database header files in a given folder.
+///
+/// Subfolders are not visited.
+pub fn find_header_files(path: impl AsRef<Path>) -> Result<Vec<PathBuf>, Error> {
+    let mut headers: Vec<PathBuf> = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path.extension().is_some_and(|ext| ext == HEADER_EXTENSION)
+        {
+            headers.push(path);
+        }
+    }
+    headers.sort();
+    Ok(headers)
+}
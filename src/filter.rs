@@ -0,0 +1,154 @@
+//! Filtered k-NN search.
+//!
+//! Filters select vectors by the synthetic `category` attribute.
+//! Since categories are uniformly assigned, a filter that matches `m` of `n`
+//! categories selects about `m / n` of the vectors; the selectivity.
+//!
+//! Queries are post-filtered: `k × over_fetch` candidates are fetched, and the
+//! `k` nearest ones that match the filter are kept.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+use flechasdb::db::AttributeValue;
+
+use crate::error::Error;
+use crate::provenance::Provenance;
+use crate::stats::QueryStats;
+
+/// Filter that matches the first categories; `c0`, `c1`, ….
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CategoryFilter {
+    /// Number of categories.
+    pub num_categories: usize,
+    /// Number of matching categories.
+    pub num_matched: usize,
+}
+
+impl CategoryFilter {
+    /// Creates a filter closest to a given selectivity.
+    ///
+    /// Matches at least one category.
+    pub fn from_selectivity(
+        num_categories: usize,
+        selectivity: f64,
+    ) -> Result<Self, Error> {
+        if !(selectivity > 0.0 && selectivity <= 1.0) {
+            return Err(Error::InvalidData(format!(
+                "selectivity must be in (0, 1]: {}",
+                selectivity,
+            )));
+        }
+        let num_matched = ((num_categories as f64 * selectivity).round() as usize)
+            .clamp(1, num_categories);
+        Ok(Self {
+            num_categories,
+            num_matched,
+        })
+    }
+
+    /// Returns the expected fraction of vectors that match.
+    pub fn selectivity(&self) -> f64 {
+        self.num_matched as f64 / self.num_categories as f64
+    }
+
+    /// Whether a given category index matches.
+    pub fn matches(&self, category: usize) -> bool {
+        category < self.num_matched
+    }
+}
+
+/// Parses a category value `c<INDEX>` into the index.
+pub fn parse_category(value: &AttributeValue) -> Result<usize, Error> {
+    match value {
+        AttributeValue::String(s) => s
+            .strip_prefix('c')
+            .and_then(|index| index.parse().ok())
+            .ok_or_else(|| Error::InvalidData(format!(
+                "invalid category: {}",
+                s,
+            ))),
+        _ => Err(Error::InvalidData(format!(
+            "category is not a string but {:?}",
+            value,
+        ))),
+    }
+}
+
+/// Result of a single selectivity level.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SelectivityResult {
+    /// Requested selectivity.
+    pub selectivity: f64,
+    /// Number of matching categories.
+    pub num_matched_categories: usize,
+    /// Number of vectors that match the filter.
+    pub num_matched_vectors: usize,
+    /// Actual fraction of vectors that match the filter.
+    pub actual_selectivity: f64,
+    /// Number of queries that returned fewer than k results after
+    /// filtering.
+    pub num_underfilled: usize,
+    /// Statistics on queries.
+    ///
+    /// Query time includes resolving attributes and filtering.
+    /// Recalls are evaluated against the exact k-NN within the matching
+    /// vectors.
+    pub stats: QueryStats,
+}
+
+/// Report of filtered k-NN search.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FilterReport {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Multiplier of k for candidates fetched before filtering.
+    pub over_fetch: usize,
+    /// Number of categories.
+    pub num_categories: usize,
+    /// Number of queries per selectivity.
+    pub num_queries: usize,
+    /// Results of the selectivity levels.
+    pub levels: Vec<SelectivityResult>,
+    /// Provenance of the results.
+    ///
+    /// Set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+impl FilterReport {
+    /// Renders the report as a Markdown table.
+    pub fn to_markdown(&self) -> String {
+        let time_unit: f64 = 1_000.0; // s → ms
+        let mut md = String::new();
+        writeln!(
+            md,
+            "### Filtered queries (k={}, nprobe={}, over-fetch={}, {} queries)",
+            self.k,
+            self.nprobe,
+            self.over_fetch,
+            self.num_queries,
+        ).unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| selectivity (%) | matched vectors | time (ms) μ ± σ | recall (%) μ ± σ | underfilled |").unwrap();
+        writeln!(md, "| --------------- | --------------- | --------------- | ---------------- | ----------- |").unwrap();
+        for level in self.levels.iter() {
+            let stats = &level.stats;
+            writeln!(
+                md,
+                "| {:.1} | {} | {:.3} ± {:.3} | {:.1} ± {:.1} | {} |",
+                level.actual_selectivity * 100.0,
+                level.num_matched_vectors,
+                stats.seconds.mean * time_unit,
+                stats.seconds.std * time_unit,
+                stats.recalls.as_ref().map_or(f32::NAN, |r| r.mean) * 100.0,
+                stats.recalls.as_ref().map_or(f32::NAN, |r| r.std) * 100.0,
+                level.num_underfilled,
+            ).unwrap();
+        }
+        md
+    }
+}
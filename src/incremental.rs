@@ -0,0 +1,150 @@
+//! Incremental insertion.
+//!
+//! A database is built over an initial fraction of the dataset, and the
+//! remaining vectors are added in batches in order of the dataset.
+//!
+//! `flechasdb` 0.1.0 provides no way to add vectors to a built database, nor
+//! to encode vectors with existing partitions and codebooks.
+//! Every step thus rebuilds the database over all the vectors added so far,
+//! and the report measures the cost of a rebuild, not of an insertion.
+//!
+//! Codebook drift (how stale partitions and codebooks degrade as vectors are
+//! appended without retraining) is not measurable either, because
+//! `flechasdb` 0.1.0 exposes no codebooks to encode new vectors with.
+//! Recalls of the rebuilt databases only tell how the recall changes with the
+//! size of a freshly trained database.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+use crate::error::Error;
+use crate::provenance::Provenance;
+use crate::stats::QueryStats;
+use crate::tune::BuildParameters;
+
+/// Plans the number of vectors at each step.
+///
+/// Step 0 has `initial_fraction` of `num_vectors`, and each of the following
+/// `num_batches` steps adds an equal share of the rest.
+pub fn plan_steps(
+    num_vectors: usize,
+    initial_fraction: f64,
+    num_batches: usize,
+) -> Result<Vec<usize>, Error> {
+    if !(initial_fraction > 0.0 && initial_fraction <= 1.0) {
+        return Err(Error::InvalidData(format!(
+            "initial fraction must be in (0, 1]: {}",
+            initial_fraction,
+        )));
+    }
+    if num_batches == 0 {
+        return Err(Error::InvalidData(
+            "number of batches must be positive".to_string(),
+        ));
+    }
+    let num_initial = ((num_vectors as f64 * initial_fraction).round() as usize)
+        .clamp(1, num_vectors);
+    let num_rest = num_vectors - num_initial;
+    Ok((0..=num_batches)
+        .map(|i| num_initial + num_rest * i / num_batches)
+        .collect())
+}
+
+/// Result of a single step.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InsertionStep {
+    /// Step number; 0 is the initial build.
+    pub step: usize,
+    /// Number of vectors in the database after the step.
+    pub num_vectors: usize,
+    /// Number of vectors added by the step.
+    pub num_added: usize,
+    /// Time to rebuild the database over all the vectors in seconds.
+    ///
+    /// Includes serializing the database into memory, but not copying the
+    /// vectors to build.
+    pub rebuild_seconds: f64,
+    /// Rebuild time per vector in the database in seconds.
+    ///
+    /// Not an insertion cost; every vector is rebuilt, not only added ones.
+    pub rebuild_seconds_per_vector: f64,
+    /// Statistics on queries against the rebuilt database.
+    ///
+    /// Recalls are evaluated against the exact k-NN over the vectors inserted
+    /// so far.
+    pub rebuilt: QueryStats,
+}
+
+/// Report of incremental insertion.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IncrementalReport {
+    /// Build parameters.
+    pub parameters: BuildParameters,
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Number of queries per step.
+    pub num_queries: usize,
+    /// Fraction of the dataset in the initial database.
+    pub initial_fraction: f64,
+    /// Results of the steps.
+    pub steps: Vec<InsertionStep>,
+    /// Provenance of the results.
+    ///
+    /// Set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+impl IncrementalReport {
+    /// Renders the report as a Markdown table.
+    ///
+    /// Δ recall is the change of the mean recall from step 0.
+    pub fn to_markdown(&self) -> String {
+        let time_unit: f64 = 1_000.0; // s → ms
+        let mean_recall = |stats: &QueryStats| stats.recalls
+            .as_ref()
+            .map_or(f32::NAN, |r| r.mean) * 100.0;
+        let base_recall = self.steps
+            .first()
+            .map_or(f32::NAN, |step| mean_recall(&step.rebuilt));
+        let mut md = String::new();
+        writeln!(
+            md,
+            "### Incremental insertion by rebuilding (partitions={}, divisions={}, codes={}, k={}, nprobe={}, {} queries)",
+            self.parameters.num_partitions,
+            self.parameters.num_divisions,
+            self.parameters.num_codes,
+            self.k,
+            self.nprobe,
+            self.num_queries,
+        ).unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| step | vectors | added | rebuild (s) | rebuild (μs/vector) | time (ms) μ ± σ | recall (%) | Δ recall (pp) |").unwrap();
+        writeln!(md, "| ---- | ------- | ----- | ----------- | ------------------- | --------------- | ---------- | ------------- |").unwrap();
+        for step in self.steps.iter() {
+            let recall = mean_recall(&step.rebuilt);
+            writeln!(
+                md,
+                "| {} | {} | {} | {:.1} | {:.1} | {:.3} ± {:.3} | {:.1} | {:+.1} |",
+                step.step,
+                step.num_vectors,
+                step.num_added,
+                step.rebuild_seconds,
+                step.rebuild_seconds_per_vector * 1_000_000.0,
+                step.rebuilt.seconds.mean * time_unit,
+                step.rebuilt.seconds.std * time_unit,
+                recall,
+                recall - base_recall,
+            ).unwrap();
+        }
+        writeln!(md).unwrap();
+        writeln!(
+            md,
+            "Every step rebuilds the database, so rebuild costs are not insertion costs. \
+             Codebook drift is not measurable because `flechasdb` exposes no codebooks.",
+        ).unwrap();
+        md
+    }
+}
@@ -0,0 +1,37 @@
+//! Graceful interruption by Ctrl-C (SIGINT).
+//!
+//! The first SIGINT after [`install_handler`] only raises a flag that long
+//! loops poll with [`is_interrupted`] to stop early and save what they have
+//! done so far.
+//! The handler is reset by the first SIGINT, so a second Ctrl-C terminates
+//! the process immediately.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::Error;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the SIGINT handler.
+pub fn install_handler() -> Result<(), Error> {
+    let handler: extern "C" fn(libc::c_int) = handle_sigint;
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESETHAND | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut()) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// Whether SIGINT has been received.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+extern "C" fn handle_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
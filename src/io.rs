@@ -0,0 +1,293 @@
+//! Instrumented file systems.
+//!
+//! [`CountingFileSystem`] wraps a file system of `flechasdb` and counts files
+//! opened and bytes read through it.
+//! It implements both [`flechasdb::io::FileSystem`] and
+//! [`flechasdb::asyncdb::io::FileSystem`] so that it can serve both
+//! synchronous and asynchronous databases.
+
+use async_trait::async_trait;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use flechasdb::asyncdb::io::{
+    FileSystem as AsyncFileSystem,
+    HashedFileIn as AsyncHashedFileIn,
+};
+use flechasdb::error::Error;
+use flechasdb::io::{FileSystem, HashedFileIn};
+
+use crate::stats::Stats;
+
+/// Counters of I/O operations.
+///
+/// Shared among a [`CountingFileSystem`] and files opened through it.
+#[derive(Debug, Default)]
+pub struct IoCounters {
+    num_opens: AtomicU64,
+    num_reads: AtomicU64,
+    bytes_read: AtomicU64,
+    open_nanos: AtomicU64,
+    read_nanos: AtomicU64,
+}
+
+impl IoCounters {
+    /// Creates zero counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a snapshot of the counters.
+    pub fn snapshot(&self) -> IoSnapshot {
+        IoSnapshot {
+            num_opens: self.num_opens.load(Ordering::Relaxed),
+            num_reads: self.num_reads.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            open_nanos: self.open_nanos.load(Ordering::Relaxed),
+            read_nanos: self.read_nanos.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add_open(&self, time: Instant) {
+        self.num_opens.fetch_add(1, Ordering::Relaxed);
+        self.open_nanos.fetch_add(elapsed_nanos(time), Ordering::Relaxed);
+    }
+
+    fn add_read(&self, bytes: usize, time: Instant) {
+        self.num_reads.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.read_nanos.fetch_add(elapsed_nanos(time), Ordering::Relaxed);
+    }
+}
+
+/// Snapshot of [`IoCounters`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct IoSnapshot {
+    /// Number of opened files.
+    pub num_opens: u64,
+    /// Number of read operations.
+    pub num_reads: u64,
+    /// Number of bytes read.
+    pub bytes_read: u64,
+    /// Total time spent opening files in nanoseconds.
+    pub open_nanos: u64,
+    /// Total time spent reading files in nanoseconds.
+    pub read_nanos: u64,
+}
+
+impl IoSnapshot {
+    /// Returns the difference from an earlier snapshot.
+    pub fn since(&self, earlier: &IoSnapshot) -> IoSnapshot {
+        IoSnapshot {
+            num_opens: self.num_opens - earlier.num_opens,
+            num_reads: self.num_reads - earlier.num_reads,
+            bytes_read: self.bytes_read - earlier.bytes_read,
+            open_nanos: self.open_nanos - earlier.open_nanos,
+            read_nanos: self.read_nanos - earlier.read_nanos,
+        }
+    }
+}
+
+/// File system that counts I/O operations through another file system.
+pub struct CountingFileSystem<FS> {
+    fs: FS,
+    counters: Arc<IoCounters>,
+}
+
+impl<FS> CountingFileSystem<FS> {
+    /// Wraps a given file system.
+    pub fn new(fs: FS, counters: Arc<IoCounters>) -> Self {
+        Self { fs, counters }
+    }
+}
+
+impl<FS> FileSystem for CountingFileSystem<FS>
+where
+    FS: FileSystem,
+{
+    type HashedFileOut = FS::HashedFileOut;
+    type HashedFileIn = CountingFileIn<FS::HashedFileIn>;
+
+    fn create_hashed_file(&self) -> Result<Self::HashedFileOut, Error> {
+        self.fs.create_hashed_file()
+    }
+
+    fn create_hashed_file_in(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<Self::HashedFileOut, Error> {
+        self.fs.create_hashed_file_in(path)
+    }
+
+    fn open_hashed_file(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<Self::HashedFileIn, Error> {
+        let time = Instant::now();
+        let file = self.fs.open_hashed_file(path)?;
+        self.counters.add_open(time);
+        Ok(CountingFileIn::new(file, self.counters.clone()))
+    }
+}
+
+#[async_trait]
+impl<FS> AsyncFileSystem for CountingFileSystem<FS>
+where
+    FS: AsyncFileSystem,
+{
+    type HashedFileIn = CountingFileIn<FS::HashedFileIn>;
+
+    async fn open_hashed_file(
+        &self,
+        path: impl Into<String> + Send,
+    ) -> Result<Self::HashedFileIn, Error> {
+        let time = Instant::now();
+        let file = self.fs.open_hashed_file(path).await?;
+        self.counters.add_open(time);
+        Ok(CountingFileIn::new(file, self.counters.clone()))
+    }
+}
+
+/// File that counts read operations.
+pub struct CountingFileIn<F> {
+    file: F,
+    counters: Arc<IoCounters>,
+    // when the pending asynchronous read started.
+    pending_since: Option<Instant>,
+}
+
+impl<F> CountingFileIn<F> {
+    fn new(file: F, counters: Arc<IoCounters>) -> Self {
+        Self {
+            file,
+            counters,
+            pending_since: None,
+        }
+    }
+}
+
+impl<F> Read for CountingFileIn<F>
+where
+    F: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let time = Instant::now();
+        let n = self.file.read(buf)?;
+        self.counters.add_read(n, time);
+        Ok(n)
+    }
+}
+
+impl<F> HashedFileIn for CountingFileIn<F>
+where
+    F: HashedFileIn,
+{
+    fn verify(self) -> Result<(), Error> {
+        self.file.verify()
+    }
+}
+
+impl<F> AsyncRead for CountingFileIn<F>
+where
+    F: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        // a read that returns pending counts from the first poll
+        let time = self.pending_since.take().unwrap_or_else(Instant::now);
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.file).poll_read(cx, buf) {
+            Poll::Pending => {
+                self.pending_since = Some(time);
+                Poll::Pending
+            },
+            Poll::Ready(Ok(())) => {
+                self.counters.add_read(buf.filled().len() - filled, time);
+                Poll::Ready(Ok(()))
+            },
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+#[async_trait]
+impl<F> AsyncHashedFileIn for CountingFileIn<F>
+where
+    F: AsyncHashedFileIn,
+{
+    async fn verify(self) -> Result<(), Error> {
+        self.file.verify().await
+    }
+}
+
+/// Recorder of statistics on I/O operations per query.
+#[derive(Default)]
+pub struct IoStatsRecorder {
+    num_opens: Vec<f64>,
+    num_reads: Vec<f64>,
+    bytes_read: Vec<f64>,
+    open_seconds: Vec<f64>,
+    read_seconds: Vec<f64>,
+}
+
+impl IoStatsRecorder {
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds I/O operations of a single query.
+    pub fn add_record(&mut self, io: IoSnapshot) {
+        self.num_opens.push(io.num_opens as f64);
+        self.num_reads.push(io.num_reads as f64);
+        self.bytes_read.push(io.bytes_read as f64);
+        self.open_seconds.push(io.open_nanos as f64 * 1.0e-9);
+        self.read_seconds.push(io.read_nanos as f64 * 1.0e-9);
+    }
+
+    /// Finishes recording and computes the statistics.
+    ///
+    /// Fails if no query is recorded.
+    pub fn finish(self) -> Result<IoStats, crate::error::Error> {
+        if self.num_opens.is_empty() {
+            return Err(crate::error::Error::InvalidData(
+                "no I/O operations recorded".to_string(),
+            ));
+        }
+        Ok(IoStats {
+            num_opens: Stats::compute(self.num_opens),
+            num_reads: Stats::compute(self.num_reads),
+            bytes_read: Stats::compute(self.bytes_read),
+            open_seconds: Stats::compute(self.open_seconds),
+            read_seconds: Stats::compute(self.read_seconds),
+        })
+    }
+}
+
+/// Statistics on I/O operations per query.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IoStats {
+    /// Number of opened files.
+    pub num_opens: Stats<f64>,
+    /// Number of read operations.
+    pub num_reads: Stats<f64>,
+    /// Number of bytes read.
+    pub bytes_read: Stats<f64>,
+    /// Time spent opening files in seconds.
+    pub open_seconds: Stats<f64>,
+    /// Time spent reading files in seconds.
+    pub read_seconds: Stats<f64>,
+}
+
+fn elapsed_nanos(time: Instant) -> u64 {
+    time.elapsed().as_nanos() as u64
+}
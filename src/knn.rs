@@ -0,0 +1,71 @@
+//! Flat k-NN search and recall evaluation.
+
+use flechasdb::linalg::{dot, subtract};
+use flechasdb::nbest::NBestByKey;
+use flechasdb::vector::BlockVectorSet;
+
+/// Queries in a given flat table.
+///
+/// Returns the indices of the k-nearest neighbors sorted by distance.
+pub fn flat_query(vs: &BlockVectorSet<f32>, qv: &[f32], k: usize) -> Vec<usize> {
+    flat_query_filtered(vs, qv, k, |_| true)
+}
+
+/// Queries in a given flat table restricted to vectors that satisfy a given
+/// predicate on the vector index.
+///
+/// Returns the indices of the k-nearest neighbors sorted by distance.
+/// Fewer than `k` indices are returned if fewer vectors satisfy the
+/// predicate.
+pub fn flat_query_filtered(
+    vs: &BlockVectorSet<f32>,
+    qv: &[f32],
+    k: usize,
+    predicate: impl Fn(usize) -> bool,
+) -> Vec<usize> {
+    let mut distances: NBestByKey<(usize, f32), f32, _> =
+        NBestByKey::new(k, |t: &(usize, f32)| t.1);
+    let mut buf: Vec<f32> = Vec::with_capacity(vs.vector_size());
+    unsafe { buf.set_len(vs.vector_size()); }
+    for i in (0..vs.len()).filter(|&i| predicate(i)) {
+        subtract(vs.get(i), qv, &mut buf);
+        distances.push((i, dot(&buf, &buf)));
+    }
+    distances.sort_by(|l, r| l.1.partial_cmp(&r.1).unwrap());
+    distances
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Calculates the recall.
+pub fn calculate_recall<T>(reference_results: &Vec<T>, results: &Vec<T>) -> f32
+where
+    T: PartialEq<T>,
+{
+    assert_eq!(reference_results.len(), results.len());
+    let recall: f32 = results
+        .iter()
+        .map(|i| if reference_results.contains(i) { 1.0f32 } else { 0.0f32 })
+        .sum();
+    recall / results.len() as f32
+}
+
+/// Calculates the recall of results that may be fewer than the reference
+/// results; e.g., those left after filtering.
+///
+/// The recall is the fraction of the reference results found in the results.
+/// Returns 1 if there are no reference results.
+pub fn calculate_filtered_recall<T>(reference_results: &[T], results: &[T]) -> f32
+where
+    T: PartialEq<T>,
+{
+    if reference_results.is_empty() {
+        return 1.0;
+    }
+    let recall: f32 = reference_results
+        .iter()
+        .map(|i| if results.contains(i) { 1.0f32 } else { 0.0f32 })
+        .sum();
+    recall / reference_results.len() as f32
+}
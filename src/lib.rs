@@ -2,5 +2,32 @@
 
 //! Bechmark for [`flechasdb`](https://github.com/codemonger-io/flechasdb).
 
+pub mod attributes;
+pub mod build;
+pub mod checkpoint;
+pub mod compare;
 pub mod error;
+pub mod events;
+pub mod files;
+pub mod filter;
+pub mod incremental;
+pub mod interrupt;
+pub mod io;
+pub mod knn;
+pub mod load;
+pub mod logging;
+pub mod memory;
+pub mod openloop;
+pub mod plot;
+pub mod progress;
+pub mod provenance;
+pub mod records;
+pub mod remote;
+pub mod report;
+pub mod resources;
+pub mod sample;
+pub mod scenario;
 pub mod sift;
+pub mod stats;
+pub mod tune;
+pub mod workload;
@@ -0,0 +1,35 @@
+//! Statistics on loading a database.
+
+use serde::Serialize;
+
+use crate::provenance::Provenance;
+use crate::resources::MemoryUsage;
+use crate::stats::Stats;
+
+/// Statistics on loading a database.
+#[derive(Debug, Serialize)]
+pub struct LoadStats {
+    /// Whether the asynchronous database is loaded.
+    pub r#async: bool,
+    /// Number of loads.
+    pub num_loads: usize,
+    /// Time to load the database header in seconds.
+    pub seconds: Stats<f64>,
+    /// Number of warmup queries.
+    pub num_warmup_queries: usize,
+    /// Number of partitions searched in by each warmup query.
+    pub nprobe: usize,
+    /// Memory usage before loading the database.
+    pub memory_before_load: MemoryUsage,
+    /// Memory usage after loading the database.
+    pub memory_after_load: MemoryUsage,
+    /// Memory usage after the warmup queries.
+    pub memory_after_warmup: MemoryUsage,
+    /// Memory usage after touching all the partitions.
+    pub memory_after_touch: MemoryUsage,
+    /// Provenance of the results.
+    ///
+    /// Set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
@@ -0,0 +1,220 @@
+//! Structured logging.
+//!
+//! Every log line is an event with a name, fields, and a human-readable
+//! message.
+//! In the text format, only messages are printed as they used to be.
+//! In the JSON format, each event is printed as a line of JSON:
+//!
+//! ```json
+//! {"event":"database_saved","fields":{"seconds":12.3},"level":"info","message":"saved database in 12.3 s","timestamp":"2023-10-01T12:34:56.789Z"}
+//! ```
+//!
+//! Use the [`error!`](crate::error!), [`warn!`](crate::warn!),
+//! [`info!`](crate::info!), and [`debug!`](crate::debug!) macros to log
+//! events.
+
+use core::fmt::{Arguments, Display, Formatter};
+use core::str::FromStr;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::OnceLock;
+
+use crate::error::Error;
+use crate::provenance::now_rfc3339;
+
+static CONFIG: OnceLock<(Level, LogFormat)> = OnceLock::new();
+
+/// Log level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Errors.
+    Error,
+    /// Warnings.
+    Warn,
+    /// Progress and results.
+    Info,
+    /// Details; e.g., every query event.
+    Debug,
+}
+
+impl Level {
+    /// Returns the name of the level.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Level {
+    type Err = Error;
+
+    /// Parses `error`, `warn`, `info`, or `debug`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(Error::InvalidData(format!("unknown log level: {}", s))),
+        }
+    }
+}
+
+/// Format of log lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable messages.
+    Text,
+    /// JSON lines.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    /// Parses `text` or `json`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::InvalidData(format!("unknown log format: {}", s))),
+        }
+    }
+}
+
+/// Configures logging.
+///
+/// Only the first call takes effect.
+/// Defaults to [`Level::Info`] and [`LogFormat::Text`] if never called.
+pub fn init(level: Level, format: LogFormat) {
+    let _ = CONFIG.set((level, format));
+}
+
+/// Whether events at a given level are logged.
+pub fn is_enabled(level: Level) -> bool {
+    level <= config().0
+}
+
+/// Returns the format of log lines.
+pub fn format() -> LogFormat {
+    config().1
+}
+
+fn config() -> (Level, LogFormat) {
+    *CONFIG.get_or_init(|| (Level::Info, LogFormat::Text))
+}
+
+/// Converts a field value into JSON.
+///
+/// Values that fail to serialize become `null`.
+pub fn to_value<T>(value: &T) -> serde_json::Value
+where
+    T: Serialize + ?Sized,
+{
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+/// Logs an event.
+///
+/// Prefer the macros.
+pub fn emit(
+    level: Level,
+    event: &str,
+    fields: Vec<(&str, serde_json::Value)>,
+    message: Arguments<'_>,
+) {
+    if !is_enabled(level) {
+        return;
+    }
+    match format() {
+        LogFormat::Text => match level {
+            Level::Error => eprintln!("error: {}", message),
+            Level::Warn => eprintln!("warning: {}", message),
+            Level::Info | Level::Debug => println!("{}", message),
+        },
+        LogFormat::Json => {
+            let line = serde_json::json!({
+                "timestamp": now_rfc3339(),
+                "level": level.name(),
+                "event": event,
+                "message": message.to_string(),
+                "fields": fields
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect::<serde_json::Map<_, _>>(),
+            });
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", line).ok();
+        },
+    }
+}
+
+/// Logs an event at a given level.
+///
+/// ```ignore
+/// log!(Level::Info, "dataset_loaded", { seconds: seconds }, "loaded dataset in {} s", seconds);
+/// log!(Level::Info, "building_database", "building database");
+/// ```
+#[macro_export]
+macro_rules! log {
+    (
+        $level:expr,
+        $event:expr,
+        { $($key:ident : $value:expr),* $(,)? },
+        $($arg:tt)+
+    ) => {
+        if $crate::logging::is_enabled($level) {
+            $crate::logging::emit(
+                $level,
+                $event,
+                vec![$((stringify!($key), $crate::logging::to_value(&$value))),*],
+                format_args!($($arg)+),
+            );
+        }
+    };
+    ($level:expr, $event:expr, $($arg:tt)+) => {
+        $crate::log!($level, $event, {}, $($arg)+)
+    };
+}
+
+/// Logs an event at [`Level::Error`](crate::logging::Level::Error).
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::logging::Level::Error, $($arg)+)
+    };
+}
+
+/// Logs an event at [`Level::Warn`](crate::logging::Level::Warn).
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::logging::Level::Warn, $($arg)+)
+    };
+}
+
+/// Logs an event at [`Level::Info`](crate::logging::Level::Info).
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::logging::Level::Info, $($arg)+)
+    };
+}
+
+/// Logs an event at [`Level::Debug`](crate::logging::Level::Debug).
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::logging::Level::Debug, $($arg)+)
+    };
+}
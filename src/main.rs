@@ -1,17 +1,25 @@
 use anyhow::{Context as _, Error, bail};
-use clap::{Parser, Subcommand};
-use core::ops::{AddAssign, Div, Mul, Sub};
+use clap::{Args, Parser, Subcommand};
+use core::fmt::Write as _;
 use futures::future::try_join_all;
-use rand::Rng;
-use serde::Serialize;
+use futures::stream::{FuturesUnordered, StreamExt as _};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::runtime::Runtime;
 
 use flechasdb::asyncdb::{
-    io::{LocalFileSystem as AsyncLocalFileSystem},
-    stored::{Database as AsyncDatabase, LoadDatabase as _},
+    io::{FileSystem as AsyncFileSystem, LocalFileSystem as AsyncLocalFileSystem},
+    stored::{
+        Database as AsyncDatabase,
+        LoadDatabase as _,
+        QueryResult as AsyncQueryResult,
+    },
 };
 use flechasdb::db::AttributeValue;
 use flechasdb::db::build::{
@@ -19,19 +27,121 @@ use flechasdb::db::build::{
     proto::serialize_database,
 };
 use flechasdb::db::stored::{self, LoadDatabase as _};
-use flechasdb::io::LocalFileSystem;
-use flechasdb::linalg::{dot, subtract, sum};
-use flechasdb::nbest::NBestByKey;
-use flechasdb::numbers::{FromAs, Sqrt, Zero};
+use flechasdb::io::{FileSystem, LocalFileSystem};
 use flechasdb::vector::BlockVectorSet;
 
+use flechasdb_benchmark::build::BuildReport;
+use flechasdb_benchmark::checkpoint::{
+    Checkpoint,
+    CheckpointEntry,
+    CheckpointHeader,
+    CheckpointWriter,
+    QueryMeasurement,
+};
+use flechasdb_benchmark::compare::{Comparison, Thresholds};
+use flechasdb_benchmark::attributes::{
+    SyntheticAttribute,
+    SyntheticAttributeGenerator,
+    read_attributes_file,
+};
+use flechasdb_benchmark::events::EventTimeline;
+use flechasdb_benchmark::files::{disk_usage, find_header_files};
+use flechasdb_benchmark::filter::{
+    CategoryFilter,
+    FilterReport,
+    SelectivityResult,
+    parse_category,
+};
+use flechasdb_benchmark::incremental::{
+    IncrementalReport,
+    InsertionStep,
+    plan_steps,
+};
+use flechasdb_benchmark::interrupt::{install_handler, is_interrupted};
+use flechasdb_benchmark::io::{CountingFileSystem, IoCounters, IoSnapshot};
+use flechasdb_benchmark::knn::{
+    calculate_filtered_recall,
+    calculate_recall,
+    flat_query,
+    flat_query_filtered,
+};
+use flechasdb_benchmark::load::LoadStats;
+use flechasdb_benchmark::logging::{self, Level, LogFormat};
+use flechasdb_benchmark::memory::MemoryFileSystem;
+use flechasdb_benchmark::openloop::{
+    ArrivalProcess,
+    OpenLoopReport,
+    RateRecorder,
+    RateResult,
+    SaturationCriteria,
+};
+use flechasdb_benchmark::plot::{
+    Axis,
+    BoxItem,
+    Scale,
+    Series,
+    box_plot,
+    histogram,
+    line_chart,
+    read_sweeps,
+};
+use flechasdb_benchmark::progress::{BuildProgress, QueryProgress};
+use flechasdb_benchmark::provenance::Provenance;
+use flechasdb_benchmark::records::{QueryRecord, QueryRecordWriter};
+use flechasdb_benchmark::remote::{
+    LatencyModel,
+    RemoteProfile,
+    RemoteSimulator,
+    SimulatedRemoteFileSystem,
+};
+use flechasdb_benchmark::report::{Report, read_stats_file};
+use flechasdb_benchmark::resources::{
+    MemoryUsage,
+    ResourceSampler,
+    ResourceUsage,
+};
+use flechasdb_benchmark::sample::{copy_vectors, extract_vectors, sample_indices};
+use flechasdb_benchmark::scenario::{
+    BatchStep,
+    QueryMode,
+    QuerySpec,
+    ResultsBundle,
+    Scenario,
+};
 use flechasdb_benchmark::sift::read_fvecs_file;
+use flechasdb_benchmark::stats::{
+    Partial,
+    QueryRange,
+    QueryStats,
+    QueryStatsRecorder,
+    Stats,
+};
+use flechasdb_benchmark::tune::{
+    BuildParameters,
+    ConfigurationResult,
+    TuneReport,
+};
+use flechasdb_benchmark::workload::{
+    Workload,
+    WorkloadComponent,
+    WorkloadKind,
+    WorkloadParameters,
+};
+use flechasdb_benchmark::{debug, error, info, warn};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    /// Log level: `error`, `warn`, `info`, or `debug`.
+    /// Query events of `batch` are logged at `debug`.
+    #[arg(long, global = true, default_value = "info", value_parser = parse_log_level)]
+    log_level: Level,
+    /// Format of log lines: `text` or `json`.
+    /// `json` prints an event object per line.
+    #[arg(long, global = true, default_value = "text", value_parser = parse_log_format)]
+    log_format: LogFormat,
 }
 
 #[derive(Subcommand)]
@@ -51,6 +161,22 @@ enum Command {
         /// Number of clusters (codes).
         #[arg(short = 'c', long, default_value_t = 256)]
         num_codes: usize,
+        /// Path to the attributes sidecar file (*.csv or *.jsonl file).
+        #[arg(short, long)]
+        attributes_path: Option<String>,
+        /// Synthetic attributes to assign (comma separated).
+        /// `category:<NUM_CATEGORIES>` or `timestamp`.
+        #[arg(long = "synthetic", value_delimiter = ',', value_parser = parse_synthetic_attribute)]
+        synthetic_attributes: Vec<SyntheticAttribute>,
+        /// Seed for synthetic attributes.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Interval in ms to sample resource usage.
+        #[arg(long, default_value_t = 1000)]
+        sampling_interval: u64,
+        /// Output path of the build report (JSON).
+        #[arg(short, long)]
+        report_path: Option<String>,
     },
     /// Queries the database with a single query vector.
     Query {
@@ -70,48 +196,584 @@ enum Command {
         /// Number of partitions to search in.
         #[arg(short = 'p', long, default_value_t = 10)]
         nprobe: usize,
+        /// Interval in ms to sample resource usage.
+        #[arg(long, default_value_t = 1000)]
+        sampling_interval: u64,
     },
     /// Queries the database with every query vector.
     Batch {
         /// Path to the dataset (*.fvecs file).
         dataset_path: String,
+        /// Path to the database file.
+        /// Ignored if `--in-memory` is given.
+        database_path: String,
+        /// Path to the query vectorset (*.fvecs file).
+        queries_path: String,
+        #[command(flatten)]
+        options: BatchOptions,
+        /// Output path of the statistics.
+        #[arg(short, long)]
+        stats_path: Option<String>,
+        /// Whether asynchronously executed.
+        #[arg(short, long)]
+        r#async: bool,
+    },
+    /// Queries the database with filters on the synthetic category
+    /// attribute.
+    Filter {
+        /// Path to the dataset (*.fvecs file).
+        dataset_path: String,
+        /// Path to the database file.
+        database_path: String,
+        /// Path to the query vectorset (*.fvecs file).
+        queries_path: String,
+        /// Synthetic attributes given to `build` (comma separated).
+        /// Must be the same as `build` and include `category:<NUM_CATEGORIES>`.
+        #[arg(long = "synthetic", required = true, value_delimiter = ',', value_parser = parse_synthetic_attribute)]
+        synthetic_attributes: Vec<SyntheticAttribute>,
+        /// Seed for synthetic attributes given to `build`.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Fractions of vectors that match the filter (comma separated).
+        /// Rounded to multiples of 1/NUM_CATEGORIES.
+        #[arg(long = "selectivity", value_delimiter = ',', default_values_t = [0.01, 0.1, 0.5])]
+        selectivities: Vec<f64>,
+        /// Number of best matches (k-nearest neighbors) to return.
+        #[arg(short, long, default_value_t = 100)]
+        k: usize,
+        /// Number of partitions to search in.
+        #[arg(short = 'p', long, default_value_t = 10)]
+        nprobe: usize,
+        /// Multiplier of k for the number of candidates fetched before
+        /// filtering.
+        #[arg(short, long, default_value_t = 10)]
+        over_fetch: usize,
+        /// Limits the number of queries per selectivity.
+        #[arg(short, long)]
+        limit: Option<usize>,
+        /// Output path of the report (JSON).
+        #[arg(short, long)]
+        report_path: Option<String>,
+        /// Output path of the report (Markdown).
+        #[arg(short, long)]
+        markdown_path: Option<String>,
+    },
+    /// Issues queries at target arrival rates regardless of completions
+    /// (open loop) and sweeps the rates to find the saturation point.
+    /// Queries share a single task, so CPU-bound query work is serialized.
+    OpenLoop {
         /// Path to the database file.
         database_path: String,
         /// Path to the query vectorset (*.fvecs file).
         queries_path: String,
+        /// Target arrival rates in queries per second (comma separated).
+        /// Swept in the given order.
+        #[arg(long = "rate", value_delimiter = ',', default_values_t = [10.0, 20.0, 50.0, 100.0, 200.0, 500.0])]
+        rates: Vec<f64>,
+        /// Arrival process: `poisson` or `constant`.
+        #[arg(long, default_value = "poisson", value_parser = parse_arrival_process)]
+        arrival: ArrivalProcess,
+        /// Duration in seconds to issue queries at each rate.
+        #[arg(short, long, default_value_t = 10.0)]
+        duration: f64,
         /// Number of best matches (k-nearest neighbors) to return.
         #[arg(short, long, default_value_t = 100)]
         k: usize,
         /// Number of partitions to search in.
         #[arg(short = 'p', long, default_value_t = 10)]
         nprobe: usize,
+        /// Number of warmup queries run one by one before the sweep.
+        #[arg(short, long, default_value_t = 100)]
+        warmup: usize,
+        /// Maximum number of queries in flight.
+        /// Queries that arrive while this many queries are in flight are
+        /// dropped.
+        #[arg(long, default_value_t = 1_000)]
+        max_in_flight: usize,
+        /// A rate is saturated if the achieved throughput falls below this
+        /// fraction of the offered rate.
+        #[arg(long, default_value_t = 0.95)]
+        min_throughput_ratio: f64,
+        /// A rate is saturated if the 99th percentile latency exceeds this in
+        /// ms.
+        #[arg(long)]
+        max_p99: Option<f64>,
+        /// Sweeps the rates beyond the first saturated rate.
+        #[arg(long)]
+        sweep_all: bool,
+        /// Skips resolving the datum IDs of the results.
+        #[arg(long)]
+        skip_attributes: bool,
+        /// Seed for the arrivals.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[command(flatten)]
+        remote: RemoteOptions,
+        /// Output path of the report (JSON).
+        #[arg(short, long)]
+        report_path: Option<String>,
+        /// Output path of the report (Markdown).
+        #[arg(short, long)]
+        markdown_path: Option<String>,
+    },
+    /// Measures the time and memory to load the database.
+    Load {
+        /// Path to the database file.
+        database_path: String,
+        /// Path to the query vectorset (*.fvecs file).
+        queries_path: String,
+        /// Number of times to load the database.
+        #[arg(short, long, default_value_t = 100)]
+        num_loads: usize,
+        /// Number of warmup queries.
+        #[arg(short, long, default_value_t = 100)]
+        warmup: usize,
+        /// Number of partitions to search in by each warmup query.
+        #[arg(short = 'p', long, default_value_t = 10)]
+        nprobe: usize,
         /// Output path of the statistics.
         #[arg(short, long)]
         stats_path: Option<String>,
+        /// Whether the asynchronous database is loaded.
+        #[arg(short, long)]
+        r#async: bool,
+    },
+    /// Searches the best build parameters over parameter grids.
+    Tune {
+        /// Path to the dataset (*.fvecs file).
+        dataset_path: String,
+        /// Path to the query vectorset (*.fvecs file).
+        queries_path: String,
+        /// Path to the folder where to save candidate databases.
+        output_path: String,
+        /// Numbers of partitions to try (comma separated).
+        #[arg(short = 'p', long, value_delimiter = ',', default_values_t = [256, 1024, 2048])]
+        num_partitions: Vec<usize>,
+        /// Numbers of subvector divisions to try (comma separated).
+        #[arg(short = 'd', long, value_delimiter = ',', default_values_t = [8])]
+        num_divisions: Vec<usize>,
+        /// Numbers of clusters (codes) to try (comma separated).
+        #[arg(short = 'c', long, value_delimiter = ',', default_values_t = [256])]
+        num_codes: Vec<usize>,
+        /// Numbers of partitions to search in (comma separated).
+        #[arg(long = "nprobe", value_delimiter = ',', default_values_t = [1, 10, 20, 50])]
+        nprobes: Vec<usize>,
+        /// Number of vectors randomly sampled from the dataset to build each
+        /// database.
+        #[arg(short, long, default_value_t = 100_000)]
+        train_size: usize,
+        /// Number of best matches (k-nearest neighbors) to return.
+        #[arg(short, long, default_value_t = 100)]
+        k: usize,
         /// Limits the number of queries.
+        #[arg(short, long, default_value_t = 1_000)]
+        limit: usize,
+        /// Seed for sampling the training subset.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Output path of the report (JSON).
         #[arg(short, long)]
-        limit: Option<usize>,
-        /// Whether asynchronously executed.
+        report_path: Option<String>,
+        /// Output path of the report (Markdown).
         #[arg(short, long)]
-        r#async: bool,
+        markdown_path: Option<String>,
+    },
+    /// Builds a database over an initial fraction of the dataset and adds
+    /// the remaining vectors in batches by rebuilding.
+    /// Measures the rebuild cost, not an insertion cost, and no codebook
+    /// drift.
+    Incremental {
+        /// Path to the dataset (*.fvecs file).
+        dataset_path: String,
+        /// Path to the query vectorset (*.fvecs file).
+        queries_path: String,
+        /// Fraction of the dataset in the initial database.
+        #[arg(short, long, default_value_t = 0.5)]
+        initial_fraction: f64,
+        /// Number of batches to add the remaining vectors in.
+        #[arg(short = 'b', long, default_value_t = 5)]
+        num_batches: usize,
+        /// Number of partitions.
+        #[arg(short = 'p', long, default_value_t = 2048)]
+        num_partitions: usize,
+        /// Number of subvector divisions.
+        #[arg(short = 'd', long, default_value_t = 8)]
+        num_divisions: usize,
+        /// Number of clusters (codes).
+        #[arg(short = 'c', long, default_value_t = 256)]
+        num_codes: usize,
+        /// Number of best matches (k-nearest neighbors) to return.
+        #[arg(short, long, default_value_t = 100)]
+        k: usize,
+        /// Number of partitions to search in.
+        #[arg(long, default_value_t = 10)]
+        nprobe: usize,
+        /// Limits the number of queries per step.
+        #[arg(short, long, default_value_t = 1_000)]
+        limit: usize,
+        /// Output path of the report (JSON).
+        #[arg(short, long)]
+        report_path: Option<String>,
+        /// Output path of the report (Markdown).
+        #[arg(short, long)]
+        markdown_path: Option<String>,
+    },
+    /// Renders statistics files into the query time and recall tables.
+    Report {
+        /// Paths to the statistics files (JSON) saved by `batch`.
+        #[arg(required = true)]
+        stats_paths: Vec<String>,
+        /// Output path of the tables (Markdown).
+        /// Printed to the standard output if omitted.
+        #[arg(short, long)]
+        output_path: Option<String>,
+        /// Output path of the query time table (CSV).
+        #[arg(long)]
+        time_csv_path: Option<String>,
+        /// Output path of the recall table (CSV).
+        #[arg(long)]
+        recall_csv_path: Option<String>,
+    },
+    /// Compares statistics files and fails if regressions are detected.
+    Compare {
+        /// Path to the baseline statistics file (JSON).
+        baseline_path: String,
+        /// Path to the candidate statistics file (JSON).
+        candidate_path: String,
+        /// Maximum allowed increase of query time in percent.
+        #[arg(long, default_value_t = 10.0)]
+        max_time_increase: f64,
+        /// Maximum allowed decrease of recalls in percentage points.
+        #[arg(long, default_value_t = 1.0)]
+        max_recall_decrease: f64,
+        /// Significance level of the Mann–Whitney U test on query time.
+        #[arg(long, default_value_t = 0.05)]
+        significance: f64,
+    },
+    /// Plots statistics files as SVG charts.
+    Plot {
+        /// Paths to the statistics files (JSON) saved by `batch`, or reports
+        /// (JSON) saved by `tune`.
+        #[arg(required = true)]
+        stats_paths: Vec<String>,
+        /// Path to the folder to save the charts.
+        #[arg(short, long, default_value = "plots")]
+        output_path: String,
+        /// Number of bins of the query time histograms.
+        #[arg(long, default_value_t = 30)]
+        num_bins: usize,
     },
+    /// Runs a benchmark scenario.
+    Run {
+        /// Path to the scenario file (TOML).
+        scenario_path: String,
+        /// Runs every step even if its output is up to date.
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+// Options for batch queries.
+#[derive(Args)]
+struct BatchOptions {
+    /// Number of best matches (k-nearest neighbors) to return.
+    #[arg(short, long, default_value_t = 100)]
+    k: usize,
+    /// Number of partitions to search in.
+    #[arg(short = 'p', long, default_value_t = 10)]
+    nprobe: usize,
+    /// Limits the number of queries.
+    #[arg(short, long)]
+    limit: Option<usize>,
+    /// Skips resolving datum IDs of results.
+    /// Measures only k-NN search time, and does not evaluate recalls.
+    #[arg(long)]
+    skip_attributes: bool,
+    /// Attributes to look up for every result after `datum_id` (comma
+    /// separated).
+    /// The lookup time of each attribute is reported separately and not
+    /// included in the query time.
+    #[arg(long = "lookup-attribute", value_delimiter = ',', conflicts_with = "skip_attributes")]
+    lookup_attributes: Vec<String>,
+    /// Records the duration of each query phase from query events.
+    #[arg(short, long)]
+    events: bool,
+    /// Counts files opened and bytes read by each query.
+    #[arg(short, long)]
+    io_stats: bool,
+    /// Number of warmup queries excluded from the statistics.
+    /// Warmup queries are taken from the beginning of the query vectorset,
+    /// and measured queries follow them.
+    #[arg(short, long, default_value_t = 0)]
+    warmup: usize,
+    /// Reloads the database before every query.
+    #[arg(short, long)]
+    cold: bool,
+    /// Separates statistics on queries that open files (first touch) from
+    /// those that do not (steady state).
+    #[arg(short, long)]
+    first_touch: bool,
+    /// Interval in ms to sample resource usage.
+    #[arg(long, default_value_t = 1000)]
+    sampling_interval: u64,
+    /// Keeps the query time and recall of every query in the statistics.
+    /// Needed to test the significance of changes with `compare`.
+    #[arg(long)]
+    keep_samples: bool,
+    /// Output path of per-query records (JSON Lines).
+    #[arg(long)]
+    records_path: Option<String>,
+    /// Output path of the checkpoint (JSON Lines).
+    /// Measurements of completed queries are periodically saved so that an
+    /// interrupted batch can be resumed with `--resume`.
+    #[arg(long)]
+    checkpoint_path: Option<String>,
+    /// Number of queries between checkpoints.
+    #[arg(long, default_value_t = 100)]
+    checkpoint_interval: usize,
+    /// Resumes the batch from the checkpoint at `--checkpoint-path`.
+    /// Per-query records at `--records-path` are appended.
+    #[arg(long, requires = "checkpoint_path")]
+    resume: bool,
+    #[command(flatten)]
+    remote: RemoteOptions,
+    #[command(flatten)]
+    memory: InMemoryOptions,
+    #[command(flatten)]
+    workload: WorkloadOptions,
+    // SHA-256 hashes of the input files keyed by role.
+    //
+    // Set from the provenance of the batch to identify the inputs in the
+    // checkpoint.
+    #[arg(skip)]
+    inputs: Vec<(String, String)>,
+}
+
+impl BatchOptions {
+    // Fails if options conflict.
+    fn validate(&self) -> Result<(), Error> {
+        if self.cold && self.warmup > 0 {
+            bail!("warmup queries are meaningless in the cold mode");
+        }
+        if self.checkpoint_interval == 0 {
+            bail!("checkpoint interval must be positive");
+        }
+        if self.limit == Some(0) {
+            bail!("limit must be positive");
+        }
+        Ok(())
+    }
+
+    // Whether I/O operations are counted.
+    fn counts_io(&self) -> bool {
+        self.io_stats || self.first_touch
+    }
+
+    // Makes options for a batch step of a scenario.
+    fn from_scenario(
+        queries: &QuerySpec,
+        batch: &BatchStep,
+        sampling_interval: u64,
+    ) -> Self {
+        Self {
+            k: batch.k,
+            nprobe: batch.nprobe,
+            limit: queries.limit,
+            skip_attributes: queries.skip_attributes,
+            lookup_attributes: Vec::new(),
+            events: queries.events,
+            io_stats: queries.io_stats,
+            warmup: queries.warmup,
+            cold: false,
+            first_touch: false,
+            sampling_interval,
+            keep_samples: queries.keep_samples,
+            records_path: None,
+            checkpoint_path: None,
+            checkpoint_interval: 100,
+            resume: false,
+            remote: RemoteOptions::local(),
+            memory: InMemoryOptions {
+                in_memory: false,
+                num_partitions: 2048,
+                num_divisions: 8,
+                num_codes: 256,
+            },
+            workload: WorkloadOptions {
+                workload: Vec::new(),
+                zipf_exponent: 1.0,
+                noise_scale: 0.1,
+                workload_seed: 0,
+            },
+            inputs: Vec::new(),
+        }
+    }
+}
+
+// Options for the simulated remote storage.
+#[derive(Args)]
+struct RemoteOptions {
+    /// Simulates a remote storage with a given request latency in ms.
+    /// `fixed:<MS>`, `normal:<MEAN_MS>,<STD_MS>`, or
+    /// `pareto:<MIN_MS>,<SHAPE>`.
+    #[arg(long, value_parser = parse_latency_model)]
+    remote_latency: Option<LatencyModel>,
+    /// Simulates a remote storage with a given bandwidth in MB/s.
+    #[arg(long)]
+    remote_bandwidth: Option<f64>,
+    /// Simulates a remote storage whose requests fail at a given rate.
+    /// Failed queries are counted and excluded from the statistics.
+    #[arg(long, default_value_t = 0.0)]
+    remote_error_rate: f64,
+    /// Seed for the simulated remote storage.
+    #[arg(long, default_value_t = 0)]
+    remote_seed: u64,
+}
+
+impl RemoteOptions {
+    // Makes options that simulate no remote storage.
+    fn local() -> Self {
+        Self {
+            remote_latency: None,
+            remote_bandwidth: None,
+            remote_error_rate: 0.0,
+            remote_seed: 0,
+        }
+    }
+
+    // Returns the profile of the remote storage if simulated.
+    fn profile(&self) -> Option<RemoteProfile> {
+        if self.remote_latency.is_none()
+            && self.remote_bandwidth.is_none()
+            && self.remote_error_rate == 0.0
+        {
+            return None;
+        }
+        Some(RemoteProfile {
+            latency: self.remote_latency,
+            bandwidth: self.remote_bandwidth.map(|mbps| mbps * 1_000_000.0),
+            error_rate: self.remote_error_rate,
+            seed: self.remote_seed,
+        })
+    }
+
+    // Returns the simulator of the remote storage if any.
+    fn simulator(&self) -> Option<Arc<RemoteSimulator>> {
+        self.profile()
+            .map(|profile| Arc::new(RemoteSimulator::new(profile)))
+    }
+
+    // Describes the simulated remote storage if any.
+    fn description(&self) -> Option<String> {
+        self.profile().map(|profile| format!("{:?}", profile))
+    }
+
+    // Whether failed queries are tolerated.
+    fn tolerates_errors(&self) -> bool {
+        self.remote_error_rate > 0.0
+    }
+}
+
+// Options for the database built in memory.
+#[derive(Args)]
+struct InMemoryOptions {
+    /// Builds the database from the dataset in memory and queries it instead
+    /// of loading the database file.
+    #[arg(long)]
+    in_memory: bool,
+    /// Number of partitions of the database built in memory.
+    #[arg(long, default_value_t = 2048)]
+    num_partitions: usize,
+    /// Number of subvector divisions of the database built in memory.
+    #[arg(long, default_value_t = 8)]
+    num_divisions: usize,
+    /// Number of clusters (codes) of the database built in memory.
+    #[arg(long, default_value_t = 256)]
+    num_codes: usize,
+}
+
+// Options for query workloads.
+#[derive(Args)]
+struct WorkloadOptions {
+    /// Generates queries from a workload instead of walking the query
+    /// vectorset once (comma separated).
+    /// `sequential`, `zipf`, `noise`, `base`, or `ood`, optionally weighted as
+    /// `<KIND>:<WEIGHT>`.
+    /// `--limit` gives the number of queries, which may exceed the number of
+    /// query vectors.
+    #[arg(long, value_delimiter = ',', value_parser = parse_workload_component)]
+    workload: Vec<WorkloadComponent>,
+    /// Exponent of the Zipfian distribution of `zipf` queries.
+    #[arg(long, default_value_t = 1.0)]
+    zipf_exponent: f64,
+    /// Standard deviation of the noise added to `noise` queries relative to
+    /// that of the vector components in the dataset.
+    #[arg(long, default_value_t = 0.1)]
+    noise_scale: f64,
+    /// Seed for the workload.
+    #[arg(long, default_value_t = 0)]
+    workload_seed: u64,
+}
+
+impl WorkloadOptions {
+    // Returns the parameters of the workload.
+    fn parameters(&self) -> WorkloadParameters {
+        WorkloadParameters {
+            zipf_exponent: self.zipf_exponent,
+            noise_scale: self.noise_scale,
+            seed: self.workload_seed,
+        }
+    }
+
+    // Describes the workload to identify it in checkpoints.
+    //
+    // `None` if no workload is given.
+    fn description(&self) -> Option<String> {
+        if self.workload.is_empty() {
+            None
+        } else {
+            Some(format!("{:?} {:?}", self.workload, self.parameters()))
+        }
+    }
+}
+
+impl InMemoryOptions {
+    // Returns the build parameters.
+    fn parameters(&self) -> BuildParameters {
+        BuildParameters {
+            num_partitions: self.num_partitions,
+            num_divisions: self.num_divisions,
+            num_codes: self.num_codes,
+        }
+    }
 }
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
-    match cli.command {
+    logging::init(cli.log_level, cli.log_format);
+    let result = match cli.command {
         Command::Build {
             dataset_path,
             output_path,
             num_partitions,
             num_divisions,
             num_codes,
+            attributes_path,
+            synthetic_attributes,
+            seed,
+            sampling_interval,
+            report_path,
         } => do_build(
             dataset_path,
             output_path,
             num_partitions,
             num_divisions,
             num_codes,
+            attributes_path,
+            synthetic_attributes,
+            seed,
+            Duration::from_millis(sampling_interval),
+            report_path,
         ),
         Command::Query {
             dataset_path,
@@ -120,6 +782,7 @@ fn main() {
             query_index,
             k,
             nprobe,
+            sampling_interval,
         } => do_query(
             dataset_path,
             database_path,
@@ -127,15 +790,14 @@ fn main() {
             query_index,
             k,
             nprobe,
+            Duration::from_millis(sampling_interval),
         ),
         Command::Batch {
             dataset_path,
             database_path,
             queries_path,
-            k,
-            nprobe,
+            options,
             stats_path,
-            limit,
             r#async,
         } => {
             if r#async {
@@ -143,9 +805,7 @@ fn main() {
                     dataset_path,
                     database_path,
                     queries_path,
-                    k,
-                    nprobe,
-                    limit,
+                    options,
                     stats_path,
                 )
             } else {
@@ -153,82 +813,451 @@ fn main() {
                     dataset_path,
                     database_path,
                     queries_path,
-                    k,
+                    options,
+                    stats_path,
+                )
+            }
+        },
+        Command::Filter {
+            dataset_path,
+            database_path,
+            queries_path,
+            synthetic_attributes,
+            seed,
+            selectivities,
+            k,
+            nprobe,
+            over_fetch,
+            limit,
+            report_path,
+            markdown_path,
+        } => do_filter(
+            dataset_path,
+            database_path,
+            queries_path,
+            synthetic_attributes,
+            seed,
+            selectivities,
+            k,
+            nprobe,
+            over_fetch,
+            limit,
+            report_path,
+            markdown_path,
+        ),
+        Command::OpenLoop {
+            database_path,
+            queries_path,
+            rates,
+            arrival,
+            duration,
+            k,
+            nprobe,
+            warmup,
+            max_in_flight,
+            min_throughput_ratio,
+            max_p99,
+            sweep_all,
+            skip_attributes,
+            seed,
+            remote,
+            report_path,
+            markdown_path,
+        } => do_open_loop(
+            database_path,
+            queries_path,
+            OpenLoopOptions {
+                rates,
+                arrival,
+                duration,
+                k,
+                nprobe,
+                warmup,
+                max_in_flight,
+                criteria: SaturationCriteria {
+                    min_throughput_ratio,
+                    max_p99_seconds: max_p99.map(|ms| ms / 1_000.0),
+                },
+                sweep_all,
+                skip_attributes,
+                seed,
+                remote,
+            },
+            report_path,
+            markdown_path,
+        ),
+        Command::Load {
+            database_path,
+            queries_path,
+            num_loads,
+            warmup,
+            nprobe,
+            stats_path,
+            r#async,
+        } => {
+            if r#async {
+                do_load_async(
+                    database_path,
+                    queries_path,
+                    num_loads,
+                    warmup,
+                    nprobe,
+                    stats_path,
+                )
+            } else {
+                do_load(
+                    database_path,
+                    queries_path,
+                    num_loads,
+                    warmup,
                     nprobe,
-                    limit,
                     stats_path,
                 )
             }
         },
-    }.unwrap();
+        Command::Tune {
+            dataset_path,
+            queries_path,
+            output_path,
+            num_partitions,
+            num_divisions,
+            num_codes,
+            nprobes,
+            train_size,
+            k,
+            limit,
+            seed,
+            report_path,
+            markdown_path,
+        } => do_tune(
+            dataset_path,
+            queries_path,
+            output_path,
+            BuildParameters::grid(&num_partitions, &num_divisions, &num_codes),
+            nprobes,
+            train_size,
+            k,
+            limit,
+            seed,
+            report_path,
+            markdown_path,
+        ),
+        Command::Incremental {
+            dataset_path,
+            queries_path,
+            initial_fraction,
+            num_batches,
+            num_partitions,
+            num_divisions,
+            num_codes,
+            k,
+            nprobe,
+            limit,
+            report_path,
+            markdown_path,
+        } => do_incremental(
+            dataset_path,
+            queries_path,
+            BuildParameters {
+                num_partitions,
+                num_divisions,
+                num_codes,
+            },
+            initial_fraction,
+            num_batches,
+            k,
+            nprobe,
+            limit,
+            report_path,
+            markdown_path,
+        ),
+        Command::Report {
+            stats_paths,
+            output_path,
+            time_csv_path,
+            recall_csv_path,
+        } => do_report(
+            stats_paths,
+            output_path,
+            time_csv_path,
+            recall_csv_path,
+        ),
+        Command::Compare {
+            baseline_path,
+            candidate_path,
+            max_time_increase,
+            max_recall_decrease,
+            significance,
+        } => do_compare(
+            baseline_path,
+            candidate_path,
+            Thresholds {
+                max_time_increase,
+                max_recall_decrease,
+                significance,
+            },
+        ),
+        Command::Plot {
+            stats_paths,
+            output_path,
+            num_bins,
+        } => do_plot(stats_paths, output_path, num_bins),
+        Command::Run { scenario_path, force } => do_run(scenario_path, force),
+    };
+    match result {
+        // 128 + SIGINT by convention
+        Ok(()) if is_interrupted() => ExitCode::from(130),
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match logging::format() {
+                LogFormat::Text => eprintln!("Error: {:?}", e),
+                LogFormat::Json => error!(
+                    "failed",
+                    {
+                        causes: e.chain()
+                            .map(|cause| cause.to_string())
+                            .collect::<Vec<_>>(),
+                    },
+                    "{:#}",
+                    e,
+                ),
+            }
+            ExitCode::FAILURE
+        },
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn do_build(
     dataset_path: String,
     output_path: String,
     num_partitions: usize,
     num_divisions: usize,
     num_codes: usize,
+    attributes_path: Option<String>,
+    synthetic_attributes: Vec<SyntheticAttribute>,
+    seed: u64,
+    sampling_interval: Duration,
+    report_path: Option<String>,
 ) -> Result<(), Error> {
-    println!("loading dataset: {}", dataset_path);
+    let sampler = ResourceSampler::start(sampling_interval)?;
+    let mut provenance = Provenance::start();
+    provenance.add_file("dataset", &dataset_path)?;
+    if let Some(attributes_path) = attributes_path.as_ref() {
+        provenance.add_file("attributes", attributes_path)?;
+    }
+    if !synthetic_attributes.is_empty() {
+        provenance.seed = Some(seed);
+    }
+    info!(
+        "loading_dataset",
+        { path: dataset_path },
+        "loading dataset: {}",
+        dataset_path,
+    );
     let vs = read_fvecs_file(&dataset_path)
         .context(format!("failed to load dataset: {}", dataset_path))?;
-    println!("vector size: {}", vs.vector_size());
-    println!("number of vectors: {}", vs.len());
-    println!("number of partitions: {}", num_partitions);
-    println!("number of divisions: {}", num_divisions);
-    println!("number of codes: {}", num_codes);
+    let vector_size = vs.vector_size();
+    let num_vectors = vs.len();
+    info!(
+        "building_database",
+        {
+            vector_size: vector_size,
+            num_vectors: num_vectors,
+            num_partitions: num_partitions,
+            num_divisions: num_divisions,
+            num_codes: num_codes,
+        },
+        "vector size: {}\nnumber of vectors: {}\nnumber of partitions: {}\nnumber of divisions: {}\nnumber of codes: {}",
+        vector_size,
+        num_vectors,
+        num_partitions,
+        num_divisions,
+        num_codes,
+    );
     let time = std::time::Instant::now();
-    let event_time = std::time::Instant::now();
+    let mut progress = BuildProgress::start(num_divisions);
     let mut db = DatabaseBuilder::new(vs)
         .with_partitions(num_partitions.try_into()?)
         .with_divisions(num_divisions.try_into()?)
         .with_clusters(num_codes.try_into()?)
-        .build_with_events(move |event| println!(
-            "{:?} at {} s",
-            event,
-            event_time.elapsed().as_secs_f32(),
-        ))
+        .build_with_events(move |event| progress.on_event(event))
         .context("failed to build database")?;
-    println!("built database in {} s", time.elapsed().as_secs_f32());
-    println!("assigning vector indices (datum_id)");
+    let build_seconds = time.elapsed().as_secs_f64();
+    info!(
+        "database_built",
+        { seconds: build_seconds },
+        "built database in {} s",
+        build_seconds,
+    );
+    info!("assigning_datum_ids", "assigning vector indices (datum_id)");
+    // sum of the assignment phases; excludes loading the attributes file
+    let mut attribute_seconds = 0.0f64;
     let time = std::time::Instant::now();
     for i in 0..db.num_vectors() {
         db.set_attribute_at(i, ("datum_id", i as u64))?;
     }
-    println!("assigned vector indices in {} s", time.elapsed().as_secs_f32());
-    println!("saving database: {}", output_path);
+    let seconds = time.elapsed().as_secs_f64();
+    attribute_seconds += seconds;
+    info!(
+        "datum_ids_assigned",
+        { seconds: seconds },
+        "assigned vector indices in {} s",
+        seconds,
+    );
+    if let Some(attributes_path) = attributes_path.as_ref() {
+        info!(
+            "loading_attributes",
+            { path: attributes_path },
+            "loading attributes: {}",
+            attributes_path,
+        );
+        let records = read_attributes_file(attributes_path)
+            .context(format!("failed to load attributes: {}", attributes_path))?;
+        info!("assigning_attributes", "assigning attributes");
+        let time = std::time::Instant::now();
+        let mut num_attributes = 0usize;
+        for record in records.into_iter() {
+            if record.index >= db.num_vectors() {
+                bail!(
+                    "attribute index out of bounds: {} ≥ {}",
+                    record.index,
+                    db.num_vectors(),
+                );
+            }
+            for value in record.values.into_iter() {
+                db.set_attribute_at(record.index, value)?;
+                num_attributes += 1;
+            }
+        }
+        let seconds = time.elapsed().as_secs_f64();
+        attribute_seconds += seconds;
+        info!(
+            "attributes_assigned",
+            { num_attributes: num_attributes, seconds: seconds },
+            "assigned {} attributes in {} s",
+            num_attributes,
+            seconds,
+        );
+    }
+    if !synthetic_attributes.is_empty() {
+        info!(
+            "assigning_synthetic_attributes",
+            {
+                attributes: synthetic_attributes
+                    .iter()
+                    .map(|attribute| format!("{:?}", attribute))
+                    .collect::<Vec<_>>(),
+            },
+            "assigning synthetic attributes: {:?}",
+            synthetic_attributes,
+        );
+        let time = std::time::Instant::now();
+        let mut generator =
+            SyntheticAttributeGenerator::new(&synthetic_attributes, seed);
+        for i in 0..db.num_vectors() {
+            for value in generator.generate().into_iter() {
+                db.set_attribute_at(i, value)?;
+            }
+        }
+        let seconds = time.elapsed().as_secs_f64();
+        attribute_seconds += seconds;
+        info!(
+            "synthetic_attributes_assigned",
+            { seconds: seconds },
+            "assigned synthetic attributes in {} s",
+            seconds,
+        );
+    }
+    info!(
+        "saving_database",
+        { path: output_path },
+        "saving database: {}",
+        output_path,
+    );
     let time = std::time::Instant::now();
     serialize_database(&db, &mut LocalFileSystem::new(&output_path))
         .context(format!("failed to save database: {}", output_path))?;
-    println!("saved database in {} s", time.elapsed().as_secs_f32());
-    Ok(())
-}
-
-fn do_query(
-    dataset_path: String,
-    database_path: String,
+    let save_seconds = time.elapsed().as_secs_f64();
+    info!(
+        "database_saved",
+        { seconds: save_seconds },
+        "saved database in {} s",
+        save_seconds,
+    );
+    let disk_bytes = disk_usage(&output_path)?;
+    info!(
+        "disk_usage",
+        { bytes: disk_bytes },
+        "disk usage: {} bytes",
+        disk_bytes,
+    );
+    let header_paths = find_header_files(&output_path)?;
+    if let [header_path] = &header_paths[..] {
+        provenance.add_file("database", header_path)?;
+    }
+    provenance.finish();
+    let resources = sampler.stop()?;
+    print_resource_usage(&resources);
+    if let Some(report_path) = report_path.as_ref() {
+        info!(
+            "saving_report",
+            { path: report_path },
+            "saving report: {}",
+            report_path,
+        );
+        let report = BuildReport {
+            vector_size,
+            num_vectors,
+            num_partitions,
+            num_divisions,
+            num_codes,
+            build_seconds,
+            attribute_seconds,
+            save_seconds,
+            disk_bytes,
+            resources,
+            provenance,
+        };
+        let file = File::create(report_path)
+            .context(format!("failed to create report file: {}", report_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report)
+            .context(format!("failed to write report to file: {}", report_path))?;
+    }
+    Ok(())
+}
+
+fn do_query(
+    dataset_path: String,
+    database_path: String,
     queries_path: String,
     query_index: Option<usize>,
     k: usize,
     nprobe: usize,
+    sampling_interval: Duration,
 ) -> Result<(), Error> {
-    println!("loading dataset: {}", dataset_path);
-    let time = std::time::Instant::now();
-    let vs = read_fvecs_file(&dataset_path)
-        .context(format!("failed to load dataset: {}", dataset_path))?;
-    println!("loaded dataset in {} s", time.elapsed().as_secs_f32());
-    println!("loading database: {}", database_path);
+    let sampler = ResourceSampler::start(sampling_interval)?;
+    let vs = load_dataset(&dataset_path)?;
+    info!(
+        "loading_database",
+        { path: database_path },
+        "loading database: {}",
+        database_path,
+    );
     let time = std::time::Instant::now();
     let database_path = Path::new(&database_path);
     let db = stored::Database::<f32, _>::load_database(
         LocalFileSystem::new(database_path.parent().unwrap()),
         database_path.file_name().unwrap().to_str().unwrap(),
     ).context(format!("failed to load database: {:?}", database_path))?;
-    println!("loaded database in {} s", time.elapsed().as_secs_f32());
-    println!("loading query vectors: {}", queries_path);
-    let qvs = read_fvecs_file(&queries_path)
-        .context(format!("failed to read query vectors: {}", queries_path))?;
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "database_loaded",
+        { seconds: seconds },
+        "loaded database in {} s",
+        seconds,
+    );
+    let qvs = load_query_vectors(&queries_path)?;
     let query_index = match query_index {
         Some(i) => i,
         None => {
@@ -236,59 +1265,67 @@ fn do_query(
             rng.gen_range(0..qvs.len())
         },
     };
-    println!("query vector index: {}", query_index);
     if query_index >= qvs.len() {
         bail!("query index out of bounds: {} ≥ {}", query_index, qvs.len());
     }
     let qv = qvs.get(query_index);
-    println!("k: {}", k);
-    println!("nprobe: {}", nprobe);
+    info!(
+        "querying",
+        { query_index: query_index, k: k, nprobe: nprobe },
+        "query vector index: {}\nk: {}\nnprobe: {}",
+        query_index,
+        k,
+        nprobe,
+    );
     let time = std::time::Instant::now();
     let event_time = std::time::Instant::now();
     let results = db.query_with_events(
         qv,
         k.try_into()?,
         nprobe.try_into()?,
-        move |event| println!(
-            "{:?} at {} s",
-            event,
-            event_time.elapsed().as_secs_f32(),
-        ),
+        move |event| {
+            let seconds = event_time.elapsed().as_secs_f32();
+            info!(
+                "query_event",
+                { event: format!("{:?}", event), seconds: seconds },
+                "{:?} at {} s",
+                event,
+                seconds,
+            );
+        },
     )?;
-    let results = results
-        .into_iter()
-        .map(|result| {
-            result.get_attribute("datum_id")
-                .and_then(|value| value.ok_or(
-                    flechasdb::error::Error::InvalidData(
-                        format!("missing datum_id"),
-                    ),
-                ))
-                .and_then(|v| match *v {
-                    AttributeValue::Uint64(v) => Ok(v as usize),
-                    _ => Err(flechasdb::error::Error::InvalidData(format!(
-                        "datum_id is not a u64 but {:?}",
-                        v,
-                    ))),
-                })
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    println!("queried k-NN in {} s", time.elapsed().as_secs_f32());
-    println!("selected datum IDs: {:?}", results);
+    let results = resolve_datum_ids(&results)?;
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "queried",
+        { seconds: seconds, datum_ids: results },
+        "queried k-NN in {} s\nselected datum IDs: {:?}",
+        seconds,
+        results,
+    );
     let time = std::time::Instant::now();
     let flat_results = flat_query(&vs, qv, k);
-    println!("flat-queried k-NN in {} s", time.elapsed().as_secs_f32());
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "flat_queried",
+        { seconds: seconds },
+        "flat-queried k-NN in {} s",
+        seconds,
+    );
     // evaluates recalls
     let recall = results
         .iter()
         .map(|i| if flat_results.contains(i) { 1 } else { 0 })
         .sum::<usize>();
-    println!(
+    info!(
+        "recall",
+        { num_hits: recall, k: k },
         "recall: {}/{} ({:.0}%)",
         recall,
         k,
         recall as f32 / k as f32 * 100.0f32,
     );
+    print_resource_usage(&sampler.stop()?);
     Ok(())
 }
 
@@ -296,103 +1333,62 @@ fn do_batch(
     dataset_path: String,
     database_path: String,
     queries_path: String,
-    k: usize,
-    nprobe: usize,
-    limit: Option<usize>,
+    mut options: BatchOptions,
     stats_path: Option<String>,
 ) -> Result<(), Error> {
-    println!("loading dataset: {}", dataset_path);
-    let time = std::time::Instant::now();
-    let vs = read_fvecs_file(&dataset_path)
-        .context(format!("failed to load dataset: {}", dataset_path))?;
-    println!("loaded dataset in {} s", time.elapsed().as_secs_f32());
-    println!("loading database: {}", database_path);
-    let time = std::time::Instant::now();
-    let database_path = Path::new(&database_path);
-    let db = stored::Database::<f32, _>::load_database(
-        LocalFileSystem::new(database_path.parent().unwrap()),
-        database_path.file_name().unwrap().to_str().unwrap(),
-    ).context(format!("failed to load database: {:?}", database_path))?;
-    println!("loaded database in {} s", time.elapsed().as_secs_f32());
-    println!("loading query vectors: {}", queries_path);
-    let qvs = read_fvecs_file(&queries_path)
-        .context(format!("failed to read query vectors: {}", queries_path))?;
-    let mut stats = QueryStatsRecorder::new(k, nprobe);
-    let num_queries = limit
-        .map(|n| std::cmp::min(n, qvs.len()))
-        .unwrap_or(qvs.len());
-    for qi in 0..num_queries {
-        if qi % 100 == 0 {
-            println!("processing query vector:\t{}/{}", qi, num_queries);
-        }
-        let qv = qvs.get(qi);
-        // indexed query
-        let time = std::time::Instant::now();
-        let results = db.query(qv, k.try_into()?, nprobe.try_into()?)?;
-        let results = results
-            .into_iter()
-            .map(|result| {
-                result.get_attribute("datum_id")
-                    .and_then(|value| value.ok_or(
-                        flechasdb::error::Error::InvalidData(
-                            format!("missing datum_id"),
-                        ),
-                    ))
-                    .and_then(|v| match *v {
-                        AttributeValue::Uint64(v) => Ok(v as usize),
-                        _ => Err(flechasdb::error::Error::InvalidData(format!(
-                            "datum_id is not a u64 but {:?}",
-                            v,
-                        ))),
-                    })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        let query_time = time.elapsed().as_secs_f64();
-        // flat query
-        let time = std::time::Instant::now();
-        let flat_results = flat_query(&vs, qv, k);
-        let flat_query_time = time.elapsed().as_secs_f64();
-        // records stats
-        let recall = calculate_recall(&flat_results, &results);
-        stats.add_record(query_time, flat_query_time, recall);
-    }
-    println!("Statistics");
-    println!("k: {}", k);
-    println!("nprobe: {}", nprobe);
-    let stats = stats.finish();
-    let time_unit: f64 = 1_000.0; // s → ms
-    println!(
-        "indexed time (ms): {:.3}±{:.3}, median={:.3}, q1={:.3}, q3={:.3}, min={:.3}, max={:.3}",
-        stats.seconds.mean * time_unit,
-        stats.seconds.std * time_unit,
-        stats.seconds.median * time_unit,
-        stats.seconds.q1 * time_unit,
-        stats.seconds.q3 * time_unit,
-        stats.seconds.min * time_unit,
-        stats.seconds.max * time_unit,
-    );
-    println!(
-        "flat time (ms): {:.3}±{:.3}, median={:.3}, q1={:.3}, q3={:.3}, min={:.3}, max={:.3}",
-        stats.flat_seconds.mean * time_unit,
-        stats.flat_seconds.std * time_unit,
-        stats.flat_seconds.median * time_unit,
-        stats.flat_seconds.q1 * time_unit,
-        stats.flat_seconds.q3 * time_unit,
-        stats.flat_seconds.min * time_unit,
-        stats.flat_seconds.max * time_unit,
-    );
-    println!(
-        "recall (%): {:.1}±{:.1}, median={:.1}, q1={:.1}, q3={:.1}, min={:.1}, max={:.1}",
-        stats.recalls.mean * 100.0,
-        stats.recalls.std * 100.0,
-        stats.recalls.median * 100.0,
-        stats.recalls.q1 * 100.0,
-        stats.recalls.q3 * 100.0,
-        stats.recalls.min * 100.0,
-        stats.recalls.max * 100.0,
-    );
+    options.validate()?;
+    install_handler()?;
+    let sampler = ResourceSampler::start(
+        Duration::from_millis(options.sampling_interval),
+    )?;
+    let mut provenance = start_batch_provenance(
+        &dataset_path,
+        &database_path,
+        &queries_path,
+        &options,
+    )?;
+    options.inputs = provenance.file_hashes();
+    let vs = load_dataset(&dataset_path)?;
+    let qvs = load_query_vectors(&queries_path)?;
+    let (qvs, kinds) = generate_workload(&options, qvs, &vs)?;
+    let stats = if options.memory.in_memory {
+        let (fs, header_path) =
+            build_in_memory(copy_vectors(&vs)?, options.memory.parameters())?;
+        _do_batch(
+            || fs.clone(),
+            Path::new(&header_path),
+            &vs,
+            &qvs,
+            kinds.as_deref(),
+            &options,
+        )?
+    } else {
+        let database_path = Path::new(&database_path);
+        let base_path = database_path.parent().unwrap();
+        _do_batch(
+            || LocalFileSystem::new(base_path),
+            database_path,
+            &vs,
+            &qvs,
+            kinds.as_deref(),
+            &options,
+        )?
+    };
+    let Some(mut stats) = stats else {
+        warn_no_stats();
+        return Ok(());
+    };
+    provenance.finish();
+    stats.resources = Some(sampler.stop()?);
+    stats.provenance = Some(provenance);
+    print_query_stats(&stats);
     if let Some(stats_path) = stats_path.as_ref() {
-        println!("saving stats: {}", stats_path);
+        info!(
+            "saving_stats",
+            { path: stats_path },
+            "saving stats: {}",
+            stats_path,
+        );
         let file = File::create(stats_path)
             .context(format!("failed to create stats file: {}", stats_path))?;
         serde_json::to_writer_pretty(BufWriter::new(file), &stats)
@@ -401,68 +1397,499 @@ fn do_batch(
     Ok(())
 }
 
+// Loads a dataset.
+fn load_dataset(dataset_path: &str) -> Result<BlockVectorSet<f32>, Error> {
+    info!(
+        "loading_dataset",
+        { path: dataset_path },
+        "loading dataset: {}",
+        dataset_path,
+    );
+    let time = std::time::Instant::now();
+    let vs = read_fvecs_file(dataset_path)
+        .context(format!("failed to load dataset: {}", dataset_path))?;
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "dataset_loaded",
+        { seconds: seconds, num_vectors: vs.len() },
+        "loaded dataset in {} s",
+        seconds,
+    );
+    Ok(vs)
+}
+
+// Loads query vectors.
+fn load_query_vectors(queries_path: &str) -> Result<BlockVectorSet<f32>, Error> {
+    info!(
+        "loading_query_vectors",
+        { path: queries_path },
+        "loading query vectors: {}",
+        queries_path,
+    );
+    let qvs = read_fvecs_file(queries_path)
+        .context(format!("failed to read query vectors: {}", queries_path))?;
+    Ok(qvs)
+}
+
+// Generates queries from the workload if given.
+//
+// Returns the query vectors and the kind of each query.
+// Returns the query vectorset as it is and `None` if no workload is given.
+fn generate_workload(
+    options: &BatchOptions,
+    qvs: BlockVectorSet<f32>,
+    vs: &BlockVectorSet<f32>,
+) -> Result<(BlockVectorSet<f32>, Option<Vec<WorkloadKind>>), Error> {
+    let components = &options.workload.workload;
+    if components.is_empty() {
+        return Ok((qvs, None));
+    }
+    // warmup queries are also generated from the workload
+    let num_queries = options.limit
+        .map(|n| options.warmup + n)
+        .unwrap_or(qvs.len());
+    info!(
+        "generating_workload",
+        {
+            workload: format!("{:?}", components),
+            num_queries: num_queries,
+            seed: options.workload.workload_seed,
+        },
+        "generating workload: {:?} ({} queries)",
+        components,
+        num_queries,
+    );
+    let workload = Workload::generate(
+        components,
+        &options.workload.parameters(),
+        &qvs,
+        vs,
+        num_queries,
+    ).context("failed to generate workload")?;
+    Ok((workload.queries, Some(workload.kinds)))
+}
+
+// Starts recording provenance of a batch with input files.
+fn start_batch_provenance(
+    dataset_path: &str,
+    database_path: &str,
+    queries_path: &str,
+    options: &BatchOptions,
+) -> Result<Provenance, Error> {
+    let mut provenance = Provenance::start();
+    provenance.add_file("dataset", dataset_path)?;
+    if !options.memory.in_memory {
+        provenance.add_file("database", database_path)?;
+    }
+    provenance.add_file("queries", queries_path)?;
+    if options.remote.simulator().is_some() {
+        provenance.seed = Some(options.remote.remote_seed);
+    } else if !options.workload.workload.is_empty() {
+        provenance.seed = Some(options.workload.workload_seed);
+    }
+    Ok(provenance)
+}
+
+// Builds a database and serializes it into an in-memory file system.
+//
+// Takes the vectors so that callers can copy them outside of timed regions.
+// Returns the file system and the path of the database header file.
+fn build_in_memory(
+    vs: BlockVectorSet<f32>,
+    parameters: BuildParameters,
+) -> Result<(MemoryFileSystem, String), Error> {
+    info!(
+        "building_database_in_memory",
+        { parameters: parameters },
+        "building database in memory: {:?}",
+        parameters,
+    );
+    let time = std::time::Instant::now();
+    let mut db = DatabaseBuilder::new(vs)
+        .with_partitions(parameters.num_partitions.try_into()?)
+        .with_divisions(parameters.num_divisions.try_into()?)
+        .with_clusters(parameters.num_codes.try_into()?)
+        .build()
+        .context(format!("failed to build database: {:?}", parameters))?;
+    for i in 0..db.num_vectors() {
+        db.set_attribute_at(i, ("datum_id", i as u64))?;
+    }
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "database_built",
+        { seconds: seconds },
+        "built database in {} s",
+        seconds,
+    );
+    let mut fs = MemoryFileSystem::new();
+    let time = std::time::Instant::now();
+    serialize_database(&db, &mut fs)
+        .context("failed to serialize database in memory")?;
+    let seconds = time.elapsed().as_secs_f32();
+    let bytes = fs.total_bytes();
+    info!(
+        "database_serialized",
+        { seconds: seconds, bytes: bytes },
+        "serialized database in {} s\nin-memory files: {} bytes",
+        seconds,
+        bytes,
+    );
+    let mut header_paths = fs.find_header_files();
+    if header_paths.len() != 1 {
+        bail!(
+            "expected one database header file in memory but found {}",
+            header_paths.len(),
+        );
+    }
+    let header_path = header_paths.pop().unwrap();
+    Ok((fs, header_path))
+}
+
+// Queries a database loaded from a given base file system.
+//
+// Wraps the base file system to simulate a remote storage and to count I/O
+// operations as specified in `options`.
+fn _do_batch<FS>(
+    make_base_fs: impl Fn() -> FS,
+    database_path: &Path,
+    vs: &BlockVectorSet<f32>,
+    qvs: &BlockVectorSet<f32>,
+    kinds: Option<&[WorkloadKind]>,
+    options: &BatchOptions,
+) -> Result<Option<QueryStats>, Error>
+where
+    FS: FileSystem,
+{
+    let counters = Arc::new(IoCounters::new());
+    match (options.remote.simulator(), options.counts_io()) {
+        (None, false) => run_batch(
+            &make_base_fs,
+            database_path,
+            vs,
+            qvs,
+            kinds,
+            options,
+            None,
+        ),
+        (None, true) => run_batch(
+            || CountingFileSystem::new(
+                make_base_fs(),
+                counters.clone(),
+            ),
+            database_path,
+            vs,
+            qvs,
+            kinds,
+            options,
+            Some(&counters),
+        ),
+        (Some(simulator), false) => run_batch(
+            || SimulatedRemoteFileSystem::new(
+                make_base_fs(),
+                simulator.clone(),
+            ),
+            database_path,
+            vs,
+            qvs,
+            kinds,
+            options,
+            None,
+        ),
+        (Some(simulator), true) => run_batch(
+            || CountingFileSystem::new(
+                SimulatedRemoteFileSystem::new(
+                    make_base_fs(),
+                    simulator.clone(),
+                ),
+                counters.clone(),
+            ),
+            database_path,
+            vs,
+            qvs,
+            kinds,
+            options,
+            Some(&counters),
+        ),
+    }
+}
+
+// Queries a database with every query vector.
+//
+// `make_fs` makes a file system to load the database from.
+// It is called before every query in the cold mode.
+// I/O operations of each query are counted if `counters` is given.
+// Statistics are grouped by `kinds` of queries if given.
+fn run_batch<FS>(
+    make_fs: impl Fn() -> FS,
+    database_path: &Path,
+    vs: &BlockVectorSet<f32>,
+    qvs: &BlockVectorSet<f32>,
+    kinds: Option<&[WorkloadKind]>,
+    options: &BatchOptions,
+    counters: Option<&IoCounters>,
+) -> Result<Option<QueryStats>, Error>
+where
+    FS: FileSystem,
+{
+    info!(
+        "loading_database",
+        { path: database_path },
+        "loading database: {:?}",
+        database_path,
+    );
+    let time = std::time::Instant::now();
+    let mut db = load_database(make_fs(), database_path)?;
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "database_loaded",
+        { seconds: seconds },
+        "loaded database in {} s",
+        seconds,
+    );
+    for wi in 0..options.warmup {
+        if is_interrupted() {
+            warn_interrupted_during_warmup(wi, options.warmup);
+            return Ok(None);
+        }
+        if wi % 100 == 0 {
+            info!(
+                "warming_up",
+                { num_completed: wi, num_planned: options.warmup },
+                "warming up:\t{}/{}",
+                wi,
+                options.warmup,
+            );
+        }
+        if let Err(e) = query_once(&db, qvs.get(wi), options) {
+            if !options.remote.tolerates_errors() {
+                return Err(e);
+            }
+        }
+    }
+    let num_queries = count_measured_queries(options, qvs.len())?;
+    let (mut recorder, first_query) =
+        BatchRecorder::start(options, num_queries, false, kinds)?;
+    for qi in first_query..num_queries {
+        if is_interrupted() {
+            recorder.suspend_progress();
+            warn!(
+                "interrupted",
+                { num_completed: qi, num_planned: num_queries },
+                "interrupted: stopping after {}/{} queries",
+                qi,
+                num_queries,
+            );
+            break;
+        }
+        if options.cold {
+            // a failed reload counts as a failed query
+            match load_database(make_fs(), database_path) {
+                Ok(reloaded) => db = reloaded,
+                Err(e) if options.remote.tolerates_errors() => {
+                    recorder.add_failure(qi, &e)?;
+                    continue;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        let qv = qvs.get(options.warmup + qi);
+        let io_before = counters.map(|c| c.snapshot());
+        let outcome = match query_once(&db, qv, options) {
+            Ok(outcome) => outcome,
+            Err(e) if options.remote.tolerates_errors() => {
+                recorder.add_failure(qi, &e)?;
+                continue;
+            },
+            Err(e) => return Err(e),
+        };
+        let io = counters
+            .zip(io_before)
+            .map(|(c, before)| c.snapshot().since(&before));
+        recorder.add_outcome(qi, outcome, io, vs, qv, options)?;
+    }
+    recorder.finish(num_queries)
+}
+
+// Counts the queries to measure after warmup queries.
+//
+// Measured queries take the query vectors following the warmup queries.
+fn count_measured_queries(
+    options: &BatchOptions,
+    num_query_vectors: usize,
+) -> Result<usize, Error> {
+    if options.warmup >= num_query_vectors {
+        bail!(
+            "no query vector left after {} warmup queries: {} query vectors",
+            options.warmup,
+            num_query_vectors,
+        );
+    }
+    let num_left = num_query_vectors - options.warmup;
+    Ok(options.limit
+        .map(|n| std::cmp::min(n, num_left))
+        .unwrap_or(num_left))
+}
+
+// Logs an interruption during warmup queries.
+fn warn_interrupted_during_warmup(num_completed: usize, num_planned: usize) {
+    warn!(
+        "interrupted",
+        { num_completed: num_completed, num_planned: num_planned },
+        "interrupted: stopping after {}/{} warmup queries",
+        num_completed,
+        num_planned,
+    );
+}
+
+// Logs that no statistics are saved because no query completed.
+fn warn_no_stats() {
+    warn!(
+        "no_stats",
+        "interrupted before any query completed: saving no stats",
+    );
+}
+
+// Loads a database from a given file system.
+fn load_database<FS>(
+    fs: FS,
+    database_path: &Path,
+) -> Result<stored::Database<f32, FS>, Error>
+where
+    FS: FileSystem,
+{
+    let db = stored::Database::<f32, _>::load_database(
+        fs,
+        database_path.file_name().unwrap().to_str().unwrap(),
+    ).context(format!("failed to load database: {:?}", database_path))?;
+    Ok(db)
+}
+
+// Runs a single query.
+fn query_once<FS>(
+    db: &stored::Database<f32, FS>,
+    qv: &[f32],
+    options: &BatchOptions,
+) -> Result<QueryOutcome, Error>
+where
+    FS: FileSystem,
+{
+    // indexed query
+    let mut timeline = EventTimeline::new();
+    let logs_events = logging::is_enabled(Level::Debug);
+    let time = std::time::Instant::now();
+    let results = if options.events || logs_events {
+        db.query_with_events(
+            qv,
+            options.k.try_into()?,
+            options.nprobe.try_into()?,
+            |event| timeline.record(&event),
+        )?
+    } else {
+        db.query(qv, options.k.try_into()?, options.nprobe.try_into()?)?
+    };
+    let end_time = std::time::Instant::now();
+    let search_time = end_time.duration_since(time).as_secs_f64();
+    if logs_events {
+        log_query_events(&timeline, time);
+    }
+    let squared_distances = results
+        .iter()
+        .map(|result| result.squared_distance)
+        .collect();
+    let phases = if options.events {
+        Some(timeline.phase_durations(end_time))
+    } else {
+        None
+    };
+    if options.skip_attributes {
+        return Ok(QueryOutcome {
+            search_time,
+            squared_distances,
+            phases,
+            resolved: None,
+            lookups: None,
+        });
+    }
+    // resolves datum IDs
+    let time = std::time::Instant::now();
+    let datum_ids = resolve_datum_ids(&results)?;
+    let attribute_time = time.elapsed().as_secs_f64();
+    let lookups = if options.lookup_attributes.is_empty() {
+        None
+    } else {
+        Some(look_up_attributes(&results, &options.lookup_attributes)?)
+    };
+    Ok(QueryOutcome {
+        search_time,
+        squared_distances,
+        phases,
+        resolved: Some((datum_ids, attribute_time)),
+        lookups,
+    })
+}
+
 fn do_batch_async(
     dataset_path: String,
     database_path: String,
     queries_path: String,
-    k: usize,
-    nprobe: usize,
-    limit: Option<usize>,
+    mut options: BatchOptions,
     stats_path: Option<String>,
 ) -> Result<(), Error> {
-    println!("loading dataset: {}", dataset_path);
-    let time = std::time::Instant::now();
-    let vs = read_fvecs_file(&dataset_path)
-        .context(format!("failed to load dataset: {}", dataset_path))?;
-    println!("loaded dataset in {} s", time.elapsed().as_secs_f32());
-    println!("loading query vectors: {}", queries_path);
-    let qvs = read_fvecs_file(&queries_path)
-        .context(format!("failed to read query vectors: {}", queries_path))?;
+    options.validate()?;
+    install_handler()?;
+    let sampler = ResourceSampler::start(
+        Duration::from_millis(options.sampling_interval),
+    )?;
+    let mut provenance = start_batch_provenance(
+        &dataset_path,
+        &database_path,
+        &queries_path,
+        &options,
+    )?;
+    options.inputs = provenance.file_hashes();
+    let vs = load_dataset(&dataset_path)?;
+    let qvs = load_query_vectors(&queries_path)?;
+    let (qvs, kinds) = generate_workload(&options, qvs, &vs)?;
     let rt = Runtime::new()?;
-    let stats = rt.block_on(_do_batch_async(
-        database_path,
-        k,
-        nprobe,
-        limit,
-        vs,
-        qvs,
-    ))?;
-    println!("Statistics");
-    println!("k: {}", k);
-    println!("nprobe: {}", nprobe);
-    let time_unit: f64 = 1_000.0; // s → ms
-    println!(
-        "indexed time (ms): {:.3}±{:.3}, median={:.3}, q1={:.3}, q3={:.3}, min={:.3}, max={:.3}",
-        stats.seconds.mean * time_unit,
-        stats.seconds.std * time_unit,
-        stats.seconds.median * time_unit,
-        stats.seconds.q1 * time_unit,
-        stats.seconds.q3 * time_unit,
-        stats.seconds.min * time_unit,
-        stats.seconds.max * time_unit,
-    );
-    println!(
-        "flat time (ms): {:.3}±{:.3}, median={:.3}, q1={:.3}, q3={:.3}, min={:.3}, max={:.3}",
-        stats.flat_seconds.mean * time_unit,
-        stats.flat_seconds.std * time_unit,
-        stats.flat_seconds.median * time_unit,
-        stats.flat_seconds.q1 * time_unit,
-        stats.flat_seconds.q3 * time_unit,
-        stats.flat_seconds.min * time_unit,
-        stats.flat_seconds.max * time_unit,
-    );
-    println!(
-        "recall (%): {:.1}±{:.1}, median={:.1}, q1={:.1}, q3={:.1}, min={:.1}, max={:.1}",
-        stats.recalls.mean * 100.0,
-        stats.recalls.std * 100.0,
-        stats.recalls.median * 100.0,
-        stats.recalls.q1 * 100.0,
-        stats.recalls.q3 * 100.0,
-        stats.recalls.min * 100.0,
-        stats.recalls.max * 100.0,
-    );
+    let stats = if options.memory.in_memory {
+        let (fs, header_path) =
+            build_in_memory(copy_vectors(&vs)?, options.memory.parameters())?;
+        rt.block_on(_do_batch_async(
+            || fs.clone(),
+            Path::new(&header_path),
+            &options,
+            vs,
+            qvs,
+            kinds,
+        ))?
+    } else {
+        let database_path = Path::new(&database_path);
+        let base_path = database_path.parent().unwrap();
+        rt.block_on(_do_batch_async(
+            || AsyncLocalFileSystem::new(base_path),
+            database_path,
+            &options,
+            vs,
+            qvs,
+            kinds,
+        ))?
+    };
+    let Some(mut stats) = stats else {
+        warn_no_stats();
+        return Ok(());
+    };
+    stats.r#async = true;
+    provenance.finish();
+    stats.resources = Some(sampler.stop()?);
+    stats.provenance = Some(provenance);
+    print_query_stats(&stats);
     if let Some(stats_path) = stats_path.as_ref() {
-        println!("saving stats: {}", stats_path);
+        info!(
+            "saving_stats",
+            { path: stats_path },
+            "saving stats: {}",
+            stats_path,
+        );
         let file = File::create(stats_path)
             .context(format!("failed to create stats file: {}", stats_path))?;
         serde_json::to_writer_pretty(BufWriter::new(file), &stats)
@@ -471,183 +1898,2558 @@ fn do_batch_async(
     Ok(())
 }
 
-async fn _do_batch_async(
-    database_path: String,
-    k: usize,
-    nprobe: usize,
-    limit: Option<usize>,
+// Asynchronously queries a database loaded from a given base file system.
+//
+// Wraps the base file system to simulate a remote storage and to count I/O
+// operations as specified in `options`.
+async fn _do_batch_async<FS>(
+    make_base_fs: impl Fn() -> FS,
+    database_path: &Path,
+    options: &BatchOptions,
     vs: BlockVectorSet<f32>,
     qvs: BlockVectorSet<f32>,
-) -> Result<QueryStats, Error> {
-    println!("loading database: {}", database_path);
+    kinds: Option<Vec<WorkloadKind>>,
+) -> Result<Option<QueryStats>, Error>
+where
+    FS: AsyncFileSystem + 'static,
+{
+    let counters = Arc::new(IoCounters::new());
+    match (options.remote.simulator(), options.counts_io()) {
+        (None, false) => run_batch_async(
+            &make_base_fs,
+            database_path,
+            &vs,
+            &qvs,
+            kinds.as_deref(),
+            options,
+            None,
+        ).await,
+        (None, true) => run_batch_async(
+            || CountingFileSystem::new(
+                make_base_fs(),
+                counters.clone(),
+            ),
+            database_path,
+            &vs,
+            &qvs,
+            kinds.as_deref(),
+            options,
+            Some(&counters),
+        ).await,
+        (Some(simulator), false) => run_batch_async(
+            || SimulatedRemoteFileSystem::new(
+                make_base_fs(),
+                simulator.clone(),
+            ),
+            database_path,
+            &vs,
+            &qvs,
+            kinds.as_deref(),
+            options,
+            None,
+        ).await,
+        (Some(simulator), true) => run_batch_async(
+            || CountingFileSystem::new(
+                SimulatedRemoteFileSystem::new(
+                    make_base_fs(),
+                    simulator.clone(),
+                ),
+                counters.clone(),
+            ),
+            database_path,
+            &vs,
+            &qvs,
+            kinds.as_deref(),
+            options,
+            Some(&counters),
+        ).await,
+    }
+}
+
+// Asynchronously queries a database with every query vector.
+//
+// `make_fs` makes a file system to load the database from.
+// It is called before every query in the cold mode.
+// I/O operations of each query are counted if `counters` is given.
+// Statistics are grouped by `kinds` of queries if given.
+async fn run_batch_async<FS>(
+    make_fs: impl Fn() -> FS,
+    database_path: &Path,
+    vs: &BlockVectorSet<f32>,
+    qvs: &BlockVectorSet<f32>,
+    kinds: Option<&[WorkloadKind]>,
+    options: &BatchOptions,
+    counters: Option<&IoCounters>,
+) -> Result<Option<QueryStats>, Error>
+where
+    FS: AsyncFileSystem + 'static,
+{
+    info!(
+        "loading_database",
+        { path: database_path },
+        "loading database: {:?}",
+        database_path,
+    );
     let time = std::time::Instant::now();
-    let database_path = Path::new(&database_path);
+    let mut db = load_database_async(make_fs(), database_path).await?;
+    let seconds = time.elapsed().as_secs_f32();
+    info!(
+        "database_loaded",
+        { seconds: seconds },
+        "loaded database in {} s",
+        seconds,
+    );
+    for wi in 0..options.warmup {
+        if is_interrupted() {
+            warn_interrupted_during_warmup(wi, options.warmup);
+            return Ok(None);
+        }
+        if wi % 100 == 0 {
+            info!(
+                "warming_up",
+                { num_completed: wi, num_planned: options.warmup },
+                "warming up:\t{}/{}",
+                wi,
+                options.warmup,
+            );
+        }
+        let qv = qvs.get(wi);
+        if let Err(e) = query_once_async(&db, qv, options).await {
+            if !options.remote.tolerates_errors() {
+                return Err(e);
+            }
+        }
+    }
+    let num_queries = count_measured_queries(options, qvs.len())?;
+    let (mut recorder, first_query) =
+        BatchRecorder::start(options, num_queries, true, kinds)?;
+    for qi in first_query..num_queries {
+        if is_interrupted() {
+            recorder.suspend_progress();
+            warn!(
+                "interrupted",
+                { num_completed: qi, num_planned: num_queries },
+                "interrupted: stopping after {}/{} queries",
+                qi,
+                num_queries,
+            );
+            break;
+        }
+        if options.cold {
+            // a failed reload counts as a failed query
+            match load_database_async(make_fs(), database_path).await {
+                Ok(reloaded) => db = reloaded,
+                Err(e) if options.remote.tolerates_errors() => {
+                    recorder.add_failure(qi, &e)?;
+                    continue;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        let qv = qvs.get(options.warmup + qi);
+        let io_before = counters.map(|c| c.snapshot());
+        let outcome = match query_once_async(&db, qv, options).await {
+            Ok(outcome) => outcome,
+            Err(e) if options.remote.tolerates_errors() => {
+                recorder.add_failure(qi, &e)?;
+                continue;
+            },
+            Err(e) => return Err(e),
+        };
+        let io = counters
+            .zip(io_before)
+            .map(|(c, before)| c.snapshot().since(&before));
+        recorder.add_outcome(qi, outcome, io, vs, qv, options)?;
+    }
+    recorder.finish(num_queries)
+}
+
+// Asynchronously loads a database from a given file system.
+async fn load_database_async<FS>(
+    fs: FS,
+    database_path: &Path,
+) -> Result<AsyncDatabase<f32, FS>, Error>
+where
+    FS: AsyncFileSystem + 'static,
+{
     let db = AsyncDatabase::<f32, _>::load_database(
-        AsyncLocalFileSystem::new(database_path.parent().unwrap()),
+        fs,
         database_path.file_name().unwrap().to_str().unwrap(),
     )
         .await
         .context(format!("failed to load database: {:?}", database_path))?;
-    println!("loaded database in {} s", time.elapsed().as_secs_f32());
-    let mut stats = QueryStatsRecorder::new(k, nprobe);
-    let num_queries = limit
-        .map(|n| std::cmp::min(n, qvs.len()))
-        .unwrap_or(qvs.len());
-    for qi in 0..num_queries {
-        if qi % 100 == 0 {
-            println!("processing query vector:\t{}/{}", qi, num_queries);
+    Ok(db)
+}
+
+// Logs the events of a query at the debug level.
+//
+// Called after the query finished so that logging is not timed.
+// `start_time` is when the query started.
+fn log_query_events(timeline: &EventTimeline, start_time: std::time::Instant) {
+    for (event, time) in timeline.events() {
+        let seconds = time.duration_since(start_time).as_secs_f64();
+        debug!(
+            "query_event",
+            { event: event, seconds: seconds },
+            "{} at {} s",
+            event,
+            seconds,
+        );
+    }
+}
+
+// Asynchronously runs a single query.
+async fn query_once_async<FS>(
+    db: &AsyncDatabase<f32, FS>,
+    qv: &[f32],
+    options: &BatchOptions,
+) -> Result<QueryOutcome, Error>
+where
+    FS: AsyncFileSystem,
+{
+    // indexed query
+    let timeline = Arc::new(Mutex::new(EventTimeline::new()));
+    let logs_events = logging::is_enabled(Level::Debug);
+    let time = std::time::Instant::now();
+    let results = if options.events || logs_events {
+        let timeline = timeline.clone();
+        db.query_with_events(
+            qv,
+            options.k.try_into()?,
+            options.nprobe.try_into()?,
+            move |event| timeline.lock().unwrap().record(&event),
+        ).await?
+    } else {
+        db.query(
+            qv,
+            options.k.try_into()?,
+            options.nprobe.try_into()?,
+        ).await?
+    };
+    let end_time = std::time::Instant::now();
+    let search_time = end_time.duration_since(time).as_secs_f64();
+    let phases = {
+        let timeline = timeline.lock().unwrap();
+        if logs_events {
+            log_query_events(&timeline, time);
         }
-        let qv = qvs.get(qi);
-        // indexed query
-        let time = std::time::Instant::now();
-        let results = db.query(qv, k.try_into()?, nprobe.try_into()?).await?;
-        let results = try_join_all(results
-            .into_iter()
-            .map(|result| async move {
-                result.get_attribute("datum_id").await
-                    .and_then(|value| value.ok_or(
-                        flechasdb::error::Error::InvalidData(
-                            format!("missing datum_id"),
-                        ),
-                    ))
-                    .and_then(|v| match v {
-                        AttributeValue::Uint64(v) => Ok(v as usize),
-                        _ => Err(flechasdb::error::Error::InvalidData(format!(
-                            "datum_id is not a u64 but {:?}",
-                            v,
-                        ))),
-                    })
-            }),
-        ).await?;
-        let query_time = time.elapsed().as_secs_f64();
-        // flat query
-        let time = std::time::Instant::now();
-        let flat_results = flat_query(&vs, qv, k);
-        let flat_query_time = time.elapsed().as_secs_f64();
-        // records stats
-        let recall = calculate_recall(&flat_results, &results);
-        stats.add_record(query_time, flat_query_time, recall);
-    }
-    Ok(stats.finish())
-}
-
-// Quries in a given flat table.
-fn flat_query(vs: &BlockVectorSet<f32>, qv: &[f32], k: usize) -> Vec<usize> {
-    let mut distances: NBestByKey<(usize, f32), f32, _> =
-        NBestByKey::new(k, |t: &(usize, f32)| t.1);
-    let mut buf: Vec<f32> = Vec::with_capacity(vs.vector_size());
-    unsafe { buf.set_len(vs.vector_size()); }
-    for i in 0..vs.len() {
-        subtract(vs.get(i), qv, &mut buf);
-        distances.push((i, dot(&buf, &buf)));
-    }
-    distances.sort_by(|l, r| l.1.partial_cmp(&r.1).unwrap());
-    distances
-        .into_iter()
-        .map(|(i, _)| i)
-        .collect()
+        if options.events {
+            Some(timeline.phase_durations(end_time))
+        } else {
+            None
+        }
+    };
+    let squared_distances = results
+        .iter()
+        .map(|result| result.squared_distance)
+        .collect();
+    if options.skip_attributes {
+        return Ok(QueryOutcome {
+            search_time,
+            squared_distances,
+            phases,
+            resolved: None,
+            lookups: None,
+        });
+    }
+    // resolves datum IDs
+    let time = std::time::Instant::now();
+    let datum_ids = resolve_datum_ids_async(&results).await?;
+    let attribute_time = time.elapsed().as_secs_f64();
+    let lookups = if options.lookup_attributes.is_empty() {
+        None
+    } else {
+        Some(look_up_attributes_async(&results, &options.lookup_attributes).await?)
+    };
+    Ok(QueryOutcome {
+        search_time,
+        squared_distances,
+        phases,
+        resolved: Some((datum_ids, attribute_time)),
+        lookups,
+    })
 }
 
-// Calculates the recall.
-fn calculate_recall<T>(reference_results: &Vec<T>, results: &Vec<T>) -> f32
+// Asynchronously resolves the datum IDs of given query results.
+async fn resolve_datum_ids_async<FS>(
+    results: &[AsyncQueryResult<'_, f32, FS>],
+) -> Result<Vec<usize>, Error>
 where
-    T: PartialEq<T>,
+    FS: AsyncFileSystem,
 {
-    assert_eq!(reference_results.len(), results.len());
-    let recall: f32 = results
+    let results = try_join_all(results
         .iter()
-        .map(|i| if reference_results.contains(i) { 1.0f32 } else { 0.0f32 })
-        .sum();
-    recall / results.len() as f32
+        .map(|result| async move {
+            result.get_attribute("datum_id").await
+                .and_then(|value| parse_datum_id(value.as_ref()))
+        }),
+    ).await?;
+    Ok(results)
 }
 
-// Recorder of statistics on queries.
-struct QueryStatsRecorder {
-    k: usize,
-    nprobe: usize,
-    seconds: Vec<f64>,
-    flat_seconds: Vec<f64>,
-    recalls: Vec<f32>,
+// Asynchronously looks up given attributes of every result.
+//
+// Returns the time to look up each attribute of all the results in seconds.
+async fn look_up_attributes_async<FS>(
+    results: &[AsyncQueryResult<'_, f32, FS>],
+    names: &[String],
+) -> Result<Vec<(String, f64)>, Error>
+where
+    FS: AsyncFileSystem,
+{
+    let mut lookups: Vec<(String, f64)> = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let time = std::time::Instant::now();
+        try_join_all(results.iter().map(|result| result.get_attribute(name)))
+            .await?;
+        lookups.push((name.clone(), time.elapsed().as_secs_f64()));
+    }
+    Ok(lookups)
 }
 
-impl QueryStatsRecorder {
-    fn new(k: usize, nprobe: usize) -> Self {
-        Self {
-            k,
-            nprobe,
-            seconds: Vec::with_capacity(10_000),
-            flat_seconds: Vec::with_capacity(10_000),
-            recalls: Vec::with_capacity(10_000),
+// Outcome of a single query.
+struct QueryOutcome {
+    // k-NN search time in seconds.
+    search_time: f64,
+    // squared distances of the results.
+    squared_distances: Vec<f32>,
+    // durations of query phases if query events are recorded.
+    phases: Option<Vec<(String, f64)>>,
+    // datum IDs and attribute resolution time in seconds if resolved.
+    resolved: Option<(Vec<usize>, f64)>,
+    // lookup time of each attribute in seconds if any attribute is looked
+    // up.
+    lookups: Option<Vec<(String, f64)>>,
+}
+
+// Recorder of a batch.
+//
+// Feeds measurements of queries into the statistics, and saves per-query
+// records and checkpoints if requested.
+struct BatchRecorder {
+    stats: QueryStatsRecorder,
+    progress: QueryProgress,
+    // number of completed queries including failed ones.
+    num_completed: usize,
+    records: Option<QueryRecordWriter>,
+    checkpoint: Option<CheckpointWriter>,
+    checkpoint_interval: usize,
+    // kind of each measured query if generated from a workload.
+    kinds: Option<Vec<WorkloadKind>>,
+    // index of the query vector of the first measured query.
+    query_offset: usize,
+}
+
+impl BatchRecorder {
+    // Starts recording a batch of `num_queries` queries.
+    //
+    // Replays the checkpoint if resumed.
+    // Returns the recorder and the index of the first query to run.
+    fn start(
+        options: &BatchOptions,
+        num_queries: usize,
+        r#async: bool,
+        kinds: Option<&[WorkloadKind]>,
+    ) -> Result<(Self, usize), Error> {
+        let mut stats = QueryStatsRecorder::new(options.k, options.nprobe);
+        if options.keep_samples {
+            stats.keep_samples();
         }
+        let header = CheckpointHeader {
+            k: options.k,
+            nprobe: options.nprobe,
+            r#async,
+            num_queries,
+            skip_attributes: options.skip_attributes,
+            lookup_attributes: options.lookup_attributes.clone(),
+            events: options.events,
+            io_stats: options.io_stats,
+            first_touch: options.first_touch,
+            workload: options.workload.description(),
+            cold: options.cold,
+            warmup: options.warmup,
+            remote: options.remote.description(),
+            in_memory: options.memory.in_memory
+                .then(|| format!("{:?}", options.memory.parameters())),
+            inputs: options.inputs.clone(),
+        };
+        let (checkpoint, first_query) = match options.checkpoint_path.as_ref() {
+            Some(path) if options.resume => {
+                info!(
+                    "loading_checkpoint",
+                    { path: path },
+                    "loading checkpoint: {}",
+                    path,
+                );
+                let checkpoint = Checkpoint::read(path)
+                    .context(format!("failed to read checkpoint: {}", path))?;
+                if checkpoint.header != header {
+                    bail!(
+                        "checkpoint does not match the batch: {:?} ≠ {:?}",
+                        checkpoint.header,
+                        header,
+                    );
+                }
+                for entry in checkpoint.entries.iter() {
+                    match entry.measurement.as_ref() {
+                        Some(measurement) => {
+                            add_measurement(&mut stats, measurement, options);
+                        },
+                        None => stats.add_failure(),
+                    }
+                }
+                // entries are indexed by query vectors following the warmup
+                let first_query = checkpoint.next_query_index() - options.warmup;
+                info!(
+                    "resuming",
+                    { first_query: first_query, num_queries: num_queries },
+                    "resuming from query: {}/{}",
+                    first_query,
+                    num_queries,
+                );
+                let writer = CheckpointWriter::resume(path, &checkpoint)
+                    .context(format!("failed to open checkpoint: {}", path))?;
+                (Some(writer), first_query)
+            },
+            Some(path) => {
+                let writer = CheckpointWriter::create(path, &header)
+                    .context(format!("failed to create checkpoint: {}", path))?;
+                (Some(writer), 0)
+            },
+            None => (None, 0),
+        };
+        let records = match options.records_path.as_ref() {
+            Some(path) if options.resume => Some(
+                QueryRecordWriter::resume(path, options.warmup + first_query)
+                    .context(format!("failed to open records file: {}", path))?,
+            ),
+            Some(path) => Some(
+                QueryRecordWriter::create(path)
+                    .context(format!("failed to create records file: {}", path))?,
+            ),
+            None => None,
+        };
+        Ok((
+            Self {
+                stats,
+                progress: QueryProgress::start(num_queries, first_query),
+                num_completed: first_query,
+                records,
+                checkpoint,
+                checkpoint_interval: options.checkpoint_interval,
+                kinds: kinds.map(|kinds| kinds[options.warmup..].to_vec()),
+                query_offset: options.warmup,
+            },
+            first_query,
+        ))
     }
 
-    fn add_record(&mut self, seconds: f64, flat_seconds: f64, recall: f32) {
-        self.seconds.push(seconds);
-        self.flat_seconds.push(flat_seconds);
-        self.recalls.push(recall);
+    // Counts a failed query.
+    //
+    // `query_index` is the index of the measured query, while logs and
+    // checkpoints refer to the index of the query vector.
+    fn add_failure(
+        &mut self,
+        query_index: usize,
+        error: &Error,
+    ) -> Result<(), Error> {
+        let vector_index = self.query_offset + query_index;
+        self.progress.suspend();
+        warn!(
+            "query_failed",
+            { query_index: vector_index, error: format!("{:#}", error) },
+            "query {} failed: {}",
+            vector_index,
+            error,
+        );
+        self.progress.add_failure();
+        self.stats.add_failure();
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.write(&CheckpointEntry {
+                query_index: vector_index,
+                measurement: None,
+            })?;
+        }
+        self.after_query(query_index)
     }
 
-    fn finish(self) -> QueryStats {
-        QueryStats {
-            k: self.k,
-            nprobe: self.nprobe,
-            num_queries: self.seconds.len(),
-            seconds: Stats::compute(self.seconds),
-            flat_seconds: Stats::compute(self.flat_seconds),
-            recalls: Stats::compute(self.recalls),
+    // Records the outcome of a single query.
+    //
+    // `query_index` is the index of the measured query, while records and
+    // checkpoints refer to the index of the query vector.
+    fn add_outcome(
+        &mut self,
+        query_index: usize,
+        outcome: QueryOutcome,
+        io: Option<IoSnapshot>,
+        vs: &BlockVectorSet<f32>,
+        qv: &[f32],
+        options: &BatchOptions,
+    ) -> Result<(), Error> {
+        let vector_index = self.query_offset + query_index;
+        let (mut measurement, record) =
+            measure_outcome(vector_index, outcome, io, vs, qv, options);
+        measurement.workload = self.kinds
+            .as_ref()
+            .map(|kinds| kinds[query_index].name().to_string());
+        add_measurement(&mut self.stats, &measurement, options);
+        self.progress.add_query(
+            measurement.search_seconds
+                + measurement.attribute_seconds.unwrap_or(0.0),
+            measurement.recall,
+        );
+        if let Some(records) = self.records.as_mut() {
+            records.write(&record)?;
+        }
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.write(&CheckpointEntry {
+                query_index: vector_index,
+                measurement: Some(measurement),
+            })?;
         }
+        self.after_query(query_index)
     }
-}
 
-// Statistics on queries.
-#[derive(Debug, Serialize)]
-struct QueryStats {
-    k: usize,
-    nprobe: usize,
-    num_queries: usize,
-    seconds: Stats<f64>,
-    flat_seconds: Stats<f64>,
-    recalls: Stats<f32>,
-}
-
-// Generic statistics.
-#[derive(Debug, Serialize)]
-struct Stats<T> {
-    mean: T,
-    std: T,
-    median: T,
-    min: T,
-    max: T,
-    q1: T,
-    q3: T,
-}
-
-impl<T> Stats<T> {
-    fn compute(mut records: Vec<T>) -> Stats<T>
-    where
-        T: FromAs<usize>
-            + Sqrt
-            + Zero
-            + AddAssign
-            + Div<Output = T>
-            + Mul<Output = T>
-            + Sub<Output = T>
-            + Copy
-            + PartialOrd,
-    {
-        records.sort_by(|l, r| l.partial_cmp(r).unwrap());
-        let sum = sum(&records);
-        let mean = sum / T::from_as(records.len());
-        let squared_sum = dot(&records, &records);
-        let var = (squared_sum - T::from_as(records.len()) * mean * mean) / T::from_as(records.len() - 1);
-        Stats {
-            mean: sum / T::from_as(records.len()),
-            std: var.sqrt(),
-            median: records[records.len() / 2],
-            min: records[0],
-            max: records[records.len() - 1],
-            q1: records[records.len() / 4],
-            q3: records[records.len() * 3 / 4],
-        }
+    // Clears the progress line so that a message can be logged.
+    fn suspend_progress(&mut self) {
+        self.progress.suspend();
     }
+
+    // Saves a checkpoint at every interval.
+    fn after_query(&mut self, query_index: usize) -> Result<(), Error> {
+        self.num_completed = query_index + 1;
+        if (query_index + 1).is_multiple_of(self.checkpoint_interval) {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    // Flushes the records and saves a checkpoint.
+    //
+    // Records are flushed first so that they never lag behind the checkpoint.
+    fn sync(&mut self) -> Result<(), Error> {
+        if let Some(records) = self.records.as_mut() {
+            records.flush()?;
+        }
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.sync()?;
+        }
+        Ok(())
+    }
+
+    // Finishes recording and computes the statistics.
+    //
+    // The statistics are marked partial if fewer than `num_planned` queries
+    // were completed.
+    // Returns `None` if interrupted before any query completed.
+    fn finish(mut self, num_planned: usize) -> Result<Option<QueryStats>, Error> {
+        self.progress.finish();
+        self.sync()?;
+        let num_completed = self.num_completed;
+        let mut stats = match self.stats.finish_if_any() {
+            Some(stats) => stats,
+            None if is_interrupted() => return Ok(None),
+            None => bail!("no query completed"),
+        };
+        if num_completed < num_planned {
+            stats.partial = Some(Partial { num_completed, num_planned });
+        }
+        stats.query_range = Some(QueryRange {
+            start: self.query_offset,
+            end: self.query_offset + num_planned,
+        });
+        Ok(Some(stats))
+    }
+}
+
+// Measures the outcome of a single query.
+//
+// `query_index` is the index of the query vector in the query vector set.
+// Evaluates the recall with the flat k-NN search if datum IDs are resolved.
+// Returns the measurement and the record of the query.
+fn measure_outcome(
+    query_index: usize,
+    outcome: QueryOutcome,
+    io: Option<IoSnapshot>,
+    vs: &BlockVectorSet<f32>,
+    qv: &[f32],
+    options: &BatchOptions,
+) -> (QueryMeasurement, QueryRecord) {
+    match outcome.resolved {
+        Some((results, attribute_time)) => {
+            // flat query
+            let time = std::time::Instant::now();
+            let flat_results = flat_query(vs, qv, options.k);
+            let flat_query_time = time.elapsed().as_secs_f64();
+            let recall = calculate_recall(&flat_results, &results);
+            (
+                QueryMeasurement {
+                    search_seconds: outcome.search_time,
+                    attribute_seconds: Some(attribute_time),
+                    flat_seconds: Some(flat_query_time),
+                    recall: Some(recall),
+                    phases: outcome.phases,
+                    io,
+                    workload: None,
+                    attribute_lookups: outcome.lookups,
+                },
+                QueryRecord {
+                    query_index,
+                    ids: Some(results),
+                    squared_distances: outcome.squared_distances,
+                    ground_truth_ids: Some(flat_results),
+                    recall: Some(recall),
+                    search_seconds: outcome.search_time,
+                    attribute_seconds: Some(attribute_time),
+                    flat_seconds: Some(flat_query_time),
+                },
+            )
+        },
+        None => (
+            QueryMeasurement {
+                search_seconds: outcome.search_time,
+                attribute_seconds: None,
+                flat_seconds: None,
+                recall: None,
+                phases: outcome.phases,
+                io,
+                workload: None,
+                attribute_lookups: outcome.lookups,
+            },
+            QueryRecord {
+                query_index,
+                ids: None,
+                squared_distances: outcome.squared_distances,
+                ground_truth_ids: None,
+                recall: None,
+                search_seconds: outcome.search_time,
+                attribute_seconds: None,
+                flat_seconds: None,
+            },
+        ),
+    }
+}
+
+// Adds the measurement of a single query to the statistics.
+fn add_measurement(
+    stats: &mut QueryStatsRecorder,
+    measurement: &QueryMeasurement,
+    options: &BatchOptions,
+) {
+    if let Some(phases) = measurement.phases.as_ref() {
+        stats.add_phases(phases.clone());
+    }
+    if let Some(lookups) = measurement.attribute_lookups.as_ref() {
+        stats.add_attribute_lookups(lookups.clone());
+    }
+    let attribute_seconds = measurement.attribute_seconds.unwrap_or(0.0);
+    if let Some(io) = measurement.io {
+        if options.io_stats {
+            stats.add_io(io);
+        }
+        if options.first_touch {
+            stats.add_touch(
+                io.num_opens > 0,
+                measurement.search_seconds + attribute_seconds,
+            );
+        }
+    }
+    if let Some(kind) = measurement.workload.as_ref() {
+        stats.add_workload(
+            kind,
+            measurement.search_seconds + attribute_seconds,
+            measurement.recall,
+        );
+    }
+    match (
+        measurement.attribute_seconds,
+        measurement.flat_seconds,
+        measurement.recall,
+    ) {
+        (Some(attribute_seconds), Some(flat_seconds), Some(recall)) => {
+            stats.add_record(
+                measurement.search_seconds,
+                attribute_seconds,
+                flat_seconds,
+                recall,
+            );
+        },
+        _ => stats.add_search_record(measurement.search_seconds),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn do_filter(
+    dataset_path: String,
+    database_path: String,
+    queries_path: String,
+    synthetic_attributes: Vec<SyntheticAttribute>,
+    seed: u64,
+    selectivities: Vec<f64>,
+    k: usize,
+    nprobe: usize,
+    over_fetch: usize,
+    limit: Option<usize>,
+    report_path: Option<String>,
+    markdown_path: Option<String>,
+) -> Result<(), Error> {
+    if over_fetch == 0 {
+        bail!("over-fetch must be positive");
+    }
+    if k == 0 {
+        bail!("k must be positive");
+    }
+    if limit == Some(0) {
+        bail!("limit must be positive");
+    }
+    let (category_position, num_categories) = synthetic_attributes
+        .iter()
+        .enumerate()
+        .find_map(|(i, attribute)| match attribute {
+            SyntheticAttribute::Category { num_categories } => {
+                Some((i, *num_categories))
+            },
+            _ => None,
+        })
+        .context("no category in synthetic attributes")?;
+    let filters = selectivities
+        .iter()
+        .map(|&s| CategoryFilter::from_selectivity(num_categories, s))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut provenance = Provenance::start();
+    provenance.add_file("dataset", &dataset_path)?;
+    provenance.add_file("database", &database_path)?;
+    provenance.add_file("queries", &queries_path)?;
+    provenance.seed = Some(seed);
+    let vs = load_dataset(&dataset_path)?;
+    let qvs = load_query_vectors(&queries_path)?;
+    info!(
+        "reconstructing_categories",
+        { attributes: format!("{:?}", synthetic_attributes), seed: seed },
+        "reconstructing categories: {:?} (seed={})",
+        synthetic_attributes,
+        seed,
+    );
+    let mut generator =
+        SyntheticAttributeGenerator::new(&synthetic_attributes, seed);
+    let categories = (0..vs.len())
+        .map(|_| parse_category(&generator.generate()[category_position].1))
+        .collect::<Result<Vec<_>, _>>()?;
+    let database_path = Path::new(&database_path);
+    info!(
+        "loading_database",
+        { path: database_path },
+        "loading database: {:?}",
+        database_path,
+    );
+    let db = load_database(
+        LocalFileSystem::new(database_path.parent().unwrap()),
+        database_path,
+    )?;
+    if qvs.len() == 0 {
+        bail!("no query vectors: {}", queries_path);
+    }
+    let num_queries = limit
+        .map(|n| std::cmp::min(n, qvs.len()))
+        .unwrap_or(qvs.len());
+    let num_candidates = k * over_fetch;
+    let mut levels: Vec<SelectivityResult> = Vec::with_capacity(filters.len());
+    for (filter, &selectivity) in filters.iter().zip(selectivities.iter()) {
+        let num_matched_vectors = categories
+            .iter()
+            .filter(|&&category| filter.matches(category))
+            .count();
+        let actual_selectivity = num_matched_vectors as f64 / vs.len() as f64;
+        info!(
+            "filtering",
+            {
+                selectivity: selectivity,
+                num_matched_categories: filter.num_matched,
+                num_matched_vectors: num_matched_vectors,
+            },
+            "selectivity {}: {}/{} categories, {} vectors ({:.1}%)",
+            selectivity,
+            filter.num_matched,
+            num_categories,
+            num_matched_vectors,
+            actual_selectivity * 100.0,
+        );
+        if num_matched_vectors == 0 {
+            warn!(
+                "selectivity_skipped",
+                { selectivity: selectivity },
+                "selectivity {}: skipping because no vector matches",
+                selectivity,
+            );
+            continue;
+        }
+        let mut stats = QueryStatsRecorder::new(k, nprobe);
+        let mut num_underfilled = 0usize;
+        let mut progress = QueryProgress::start(num_queries, 0);
+        for qi in 0..num_queries {
+            let qv = qvs.get(qi);
+            let time = std::time::Instant::now();
+            let results = db.query(
+                qv,
+                num_candidates.try_into()?,
+                nprobe.try_into()?,
+            )?;
+            let search_time = time.elapsed().as_secs_f64();
+            let time = std::time::Instant::now();
+            let results = filter_results(results, filter, &categories, k)?;
+            let attribute_time = time.elapsed().as_secs_f64();
+            if results.len() < k {
+                num_underfilled += 1;
+            }
+            let time = std::time::Instant::now();
+            let flat_results = flat_query_filtered(
+                &vs,
+                qv,
+                k,
+                |i| filter.matches(categories[i]),
+            );
+            let flat_time = time.elapsed().as_secs_f64();
+            let recall = calculate_filtered_recall(&flat_results, &results);
+            stats.add_record(search_time, attribute_time, flat_time, recall);
+            progress.add_query(search_time + attribute_time, Some(recall));
+        }
+        progress.finish();
+        let stats = stats.finish()?;
+        info!(
+            "selectivity_finished",
+            {
+                selectivity: selectivity,
+                mean_seconds: stats.seconds.mean,
+                mean_recall: stats.recalls.as_ref().map(|r| r.mean),
+                num_underfilled: num_underfilled,
+            },
+            "selectivity {}: time (ms)={:.3}, recall (%)={:.1}, underfilled={}",
+            selectivity,
+            stats.seconds.mean * 1_000.0,
+            stats.recalls.as_ref().map_or(f32::NAN, |r| r.mean) * 100.0,
+            num_underfilled,
+        );
+        levels.push(SelectivityResult {
+            selectivity,
+            num_matched_categories: filter.num_matched,
+            num_matched_vectors,
+            actual_selectivity,
+            num_underfilled,
+            stats,
+        });
+    }
+    if levels.is_empty() {
+        bail!("no vector matches any selectivity");
+    }
+    provenance.finish();
+    let report = FilterReport {
+        k,
+        nprobe,
+        over_fetch,
+        num_categories,
+        num_queries,
+        levels,
+        provenance: Some(provenance),
+    };
+    let markdown = report.to_markdown();
+    info!("filter_report", { report: report }, "{}", markdown);
+    if let Some(report_path) = report_path.as_ref() {
+        info!(
+            "saving_report",
+            { path: report_path },
+            "saving report: {}",
+            report_path,
+        );
+        let file = File::create(report_path)
+            .context(format!("failed to create report file: {}", report_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report)
+            .context(format!("failed to write report to file: {}", report_path))?;
+    }
+    if let Some(markdown_path) = markdown_path.as_ref() {
+        info!(
+            "saving_markdown_report",
+            { path: markdown_path },
+            "saving Markdown report: {}",
+            markdown_path,
+        );
+        std::fs::write(markdown_path, markdown)
+            .context(format!("failed to write Markdown report: {}", markdown_path))?;
+    }
+    Ok(())
+}
+
+// Keeps the k nearest results that match a filter.
+//
+// Returns the datum IDs of the kept results.
+// Fails if the category of a kept result in the database differs from the
+// reconstructed one.
+fn filter_results<FS>(
+    results: Vec<stored::QueryResult<'_, f32, FS>>,
+    filter: &CategoryFilter,
+    categories: &[usize],
+    k: usize,
+) -> Result<Vec<usize>, Error>
+where
+    FS: FileSystem,
+{
+    let mut datum_ids: Vec<usize> = Vec::with_capacity(k);
+    for result in results.into_iter() {
+        let category = result.get_attribute("category")?
+            .context("missing category")?;
+        let category = parse_category(&category)?;
+        if !filter.matches(category) {
+            continue;
+        }
+        let datum_id =
+            parse_datum_id(result.get_attribute("datum_id")?.as_deref())?;
+        if categories.get(datum_id) != Some(&category) {
+            bail!(
+                "category of vector {} in the database (c{}) differs from \
+                 the synthetic attributes. \
+                 give the same --synthetic and --seed as build",
+                datum_id,
+                category,
+            );
+        }
+        datum_ids.push(datum_id);
+        if datum_ids.len() == k {
+            break;
+        }
+    }
+    Ok(datum_ids)
+}
+
+// Options for the open-loop load generator.
+struct OpenLoopOptions {
+    rates: Vec<f64>,
+    arrival: ArrivalProcess,
+    duration: f64,
+    k: usize,
+    nprobe: usize,
+    warmup: usize,
+    max_in_flight: usize,
+    criteria: SaturationCriteria,
+    sweep_all: bool,
+    skip_attributes: bool,
+    seed: u64,
+    remote: RemoteOptions,
+}
+
+impl OpenLoopOptions {
+    // Makes options for a batch step of a scenario in the throughput mode.
+    fn from_scenario(
+        queries: &QuerySpec,
+        batch: &BatchStep,
+    ) -> Result<Self, Error> {
+        let throughput = &queries.throughput;
+        Ok(Self {
+            rates: throughput.rates.clone(),
+            arrival: throughput.arrival_process()?,
+            duration: throughput.duration,
+            k: batch.k,
+            nprobe: batch.nprobe,
+            warmup: queries.warmup,
+            max_in_flight: throughput.max_in_flight,
+            criteria: SaturationCriteria {
+                min_throughput_ratio: throughput.min_throughput_ratio,
+                max_p99_seconds: throughput.max_p99.map(|ms| ms / 1_000.0),
+            },
+            sweep_all: false,
+            skip_attributes: queries.skip_attributes,
+            seed: throughput.seed,
+            remote: RemoteOptions::local(),
+        })
+    }
+}
+
+fn do_open_loop(
+    database_path: String,
+    queries_path: String,
+    options: OpenLoopOptions,
+    report_path: Option<String>,
+    markdown_path: Option<String>,
+) -> Result<(), Error> {
+    if options.rates.iter().any(|&rate| !(rate > 0.0 && rate.is_finite())) {
+        bail!("rates must be positive");
+    }
+    if !(options.duration > 0.0 && options.duration.is_finite()) {
+        bail!("duration must be positive");
+    }
+    if options.max_in_flight == 0 {
+        bail!("maximum number of queries in flight must be positive");
+    }
+    install_handler()?;
+    let mut provenance = Provenance::start();
+    provenance.add_file("database", &database_path)?;
+    provenance.add_file("queries", &queries_path)?;
+    provenance.seed = Some(options.seed);
+    let qvs = load_query_vectors(&queries_path)?;
+    if qvs.len() == 0 {
+        bail!("no query vectors: {}", queries_path);
+    }
+    let database_path = Path::new(&database_path);
+    let base_path = database_path.parent().unwrap();
+    let rt = Runtime::new()?;
+    let rates = match options.remote.simulator() {
+        None => rt.block_on(_do_open_loop(
+            AsyncLocalFileSystem::new(base_path),
+            database_path,
+            &qvs,
+            &options,
+        ))?,
+        Some(simulator) => rt.block_on(_do_open_loop(
+            SimulatedRemoteFileSystem::new(
+                AsyncLocalFileSystem::new(base_path),
+                simulator,
+            ),
+            database_path,
+            &qvs,
+            &options,
+        ))?,
+    };
+    if rates.is_empty() {
+        warn_no_stats();
+        return Ok(());
+    }
+    provenance.finish();
+    let mut report = OpenLoopReport::new(
+        options.k,
+        options.nprobe,
+        options.arrival,
+        options.duration,
+        options.max_in_flight,
+        options.criteria,
+        rates,
+    );
+    report.provenance = Some(provenance);
+    let markdown = report.to_markdown();
+    info!("open_loop_report", { report: report }, "{}", markdown);
+    if let Some(report_path) = report_path.as_ref() {
+        info!(
+            "saving_report",
+            { path: report_path },
+            "saving report: {}",
+            report_path,
+        );
+        let file = File::create(report_path)
+            .context(format!("failed to create report file: {}", report_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report)
+            .context(format!("failed to write report to file: {}", report_path))?;
+    }
+    if let Some(markdown_path) = markdown_path.as_ref() {
+        info!(
+            "saving_markdown_report",
+            { path: markdown_path },
+            "saving Markdown report: {}",
+            markdown_path,
+        );
+        std::fs::write(markdown_path, markdown)
+            .context(format!("failed to write Markdown report: {}", markdown_path))?;
+    }
+    Ok(())
+}
+
+// Sweeps the arrival rates against a database loaded from a given file
+// system.
+//
+// Stops after the first saturated rate unless `options.sweep_all` is set.
+// Returns the results of the swept rates, which are empty if interrupted
+// during warmup queries.
+async fn _do_open_loop<FS>(
+    fs: FS,
+    database_path: &Path,
+    qvs: &BlockVectorSet<f32>,
+    options: &OpenLoopOptions,
+) -> Result<Vec<RateResult>, Error>
+where
+    FS: AsyncFileSystem + 'static,
+{
+    info!(
+        "loading_database",
+        { path: database_path },
+        "loading database: {:?}",
+        database_path,
+    );
+    let db = load_database_async(fs, database_path).await?;
+    info!(
+        "warming_up",
+        { num_planned: options.warmup },
+        "running warmup queries: {}",
+        options.warmup,
+    );
+    for wi in 0..options.warmup {
+        if is_interrupted() {
+            warn_interrupted_during_warmup(wi, options.warmup);
+            return Ok(Vec::new());
+        }
+        let now = tokio::time::Instant::now();
+        if let Err(e) = open_loop_query(&db, qvs.get(wi % qvs.len()), now, options).await {
+            if !options.remote.tolerates_errors() {
+                return Err(e);
+            }
+        }
+    }
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut next_query = options.warmup;
+    let mut results: Vec<RateResult> = Vec::with_capacity(options.rates.len());
+    for &rate in options.rates.iter() {
+        let arrivals = options.arrival.schedule(rate, options.duration, &mut rng);
+        info!(
+            "rate_started",
+            { target_rate: rate, num_arrivals: arrivals.len() },
+            "issuing {} queries at {} qps for {} s",
+            arrivals.len(),
+            rate,
+            options.duration,
+        );
+        let result = run_rate(&db, qvs, &arrivals, rate, &mut next_query, options).await?;
+        info!(
+            "rate_finished",
+            {
+                target_rate: rate,
+                achieved_qps: result.achieved_qps,
+                latency_percentiles: result.latency_percentiles,
+                num_dropped: result.num_dropped,
+                num_failures: result.num_failures,
+                saturated: result.saturated,
+            },
+            "{} qps: achieved {:.1} qps, p50={:.3} ms, p99={:.3} ms, dropped={}, failed={}{}",
+            rate,
+            result.achieved_qps,
+            result.latency_percentiles.map_or(f64::NAN, |p| p.p50) * 1_000.0,
+            result.latency_percentiles.map_or(f64::NAN, |p| p.p99) * 1_000.0,
+            result.num_dropped,
+            result.num_failures,
+            if result.saturated { " (saturated)" } else { "" },
+        );
+        let saturated = result.saturated;
+        results.push(result);
+        if is_interrupted() {
+            warn!(
+                "interrupted",
+                { num_completed: results.len(), num_planned: options.rates.len() },
+                "interrupted: stopping after {}/{} rates",
+                results.len(),
+                options.rates.len(),
+            );
+            break;
+        }
+        if saturated && !options.sweep_all {
+            info!(
+                "saturated",
+                { target_rate: rate },
+                "saturated at {} qps; skipping higher rates",
+                rate,
+            );
+            break;
+        }
+    }
+    Ok(results)
+}
+
+// Issues queries at given arrival times in seconds since the start, and waits
+// for all of them to complete.
+//
+// Queries in flight are interleaved on the current task; a query never waits
+// for another one to finish before it is sent.
+// CPU-bound query work is not parallelized though; with an in-memory or local
+// database, latency beyond the throughput of a single core is queueing on the
+// task rather than latency of the database.
+// Query vectors are taken in order from `next_query`, which is advanced.
+// Stops issuing queries when interrupted.
+async fn run_rate<FS>(
+    db: &AsyncDatabase<f32, FS>,
+    qvs: &BlockVectorSet<f32>,
+    arrivals: &[f64],
+    rate: f64,
+    next_query: &mut usize,
+    options: &OpenLoopOptions,
+) -> Result<RateResult, Error>
+where
+    FS: AsyncFileSystem,
+{
+    let mut recorder = RateRecorder::new(rate, options.duration);
+    let mut in_flight = FuturesUnordered::new();
+    let start = tokio::time::Instant::now();
+    let mut next_arrival = 0;
+    loop {
+        if is_interrupted() {
+            next_arrival = arrivals.len();
+        }
+        if next_arrival == arrivals.len() && in_flight.is_empty() {
+            break;
+        }
+        let due = arrivals
+            .get(next_arrival)
+            .map(|&t| start + Duration::from_secs_f64(t));
+        tokio::select! {
+            _ = tokio::time::sleep_until(due.unwrap_or(start)), if due.is_some() => {
+                // catches up with every arrival that is due
+                let now = tokio::time::Instant::now();
+                while next_arrival < arrivals.len()
+                    && start + Duration::from_secs_f64(arrivals[next_arrival]) <= now
+                {
+                    if in_flight.len() >= options.max_in_flight {
+                        recorder.add_dropped();
+                    } else {
+                        let intended =
+                            start + Duration::from_secs_f64(arrivals[next_arrival]);
+                        let qv = qvs.get(*next_query % qvs.len());
+                        in_flight.push(open_loop_query(db, qv, intended, options));
+                        recorder.add_issued(in_flight.len());
+                    }
+                    *next_query += 1;
+                    next_arrival += 1;
+                }
+            },
+            Some(result) = in_flight.next(), if !in_flight.is_empty() => {
+                match result {
+                    Ok((latency, send_lag)) => {
+                        recorder.add_completion(latency, send_lag);
+                    },
+                    Err(_) if options.remote.tolerates_errors() => {
+                        recorder.add_failure();
+                    },
+                    Err(e) => return Err(e),
+                }
+            },
+        }
+    }
+    Ok(recorder.finish(start.elapsed().as_secs_f64(), &options.criteria))
+}
+
+// Runs a single query that is intended to be sent at `intended`.
+//
+// Returns the latency from `intended` to the completion, and the lag from
+// `intended` to the actual send time, both in seconds.
+async fn open_loop_query<FS>(
+    db: &AsyncDatabase<f32, FS>,
+    qv: &[f32],
+    intended: tokio::time::Instant,
+    options: &OpenLoopOptions,
+) -> Result<(f64, f64), Error>
+where
+    FS: AsyncFileSystem,
+{
+    let send_lag = intended.elapsed().as_secs_f64();
+    let results = db.query(
+        qv,
+        options.k.try_into()?,
+        options.nprobe.try_into()?,
+    ).await?;
+    if !options.skip_attributes {
+        try_join_all(results
+            .iter()
+            .map(|result| result.get_attribute("datum_id")),
+        ).await?;
+    }
+    Ok((intended.elapsed().as_secs_f64(), send_lag))
+}
+
+fn do_load(
+    database_path: String,
+    queries_path: String,
+    num_loads: usize,
+    warmup: usize,
+    nprobe: usize,
+    stats_path: Option<String>,
+) -> Result<(), Error> {
+    if num_loads == 0 {
+        bail!("number of loads must be positive");
+    }
+    let qvs = load_query_vectors(&queries_path)?;
+    if qvs.len() == 0 {
+        bail!("no query vectors: {}", queries_path);
+    }
+    let mut provenance = Provenance::start();
+    provenance.add_file("queries", &queries_path)?;
+    provenance.add_file("database", &database_path)?;
+    let database_path = Path::new(&database_path);
+    let base_path = database_path.parent().unwrap();
+    let memory_before_load = MemoryUsage::read()?;
+    info!(
+        "loading_database",
+        { path: database_path, num_loads: num_loads },
+        "loading database {} times: {:?}",
+        num_loads,
+        database_path,
+    );
+    let mut seconds: Vec<f64> = Vec::with_capacity(num_loads);
+    let mut db = None;
+    for _ in 0..num_loads {
+        // drops the previous instance before loading
+        db.take();
+        let time = std::time::Instant::now();
+        db = Some(load_database(LocalFileSystem::new(base_path), database_path)?);
+        seconds.push(time.elapsed().as_secs_f64());
+    }
+    let db = db.unwrap();
+    let memory_after_load = MemoryUsage::read()?;
+    info!(
+        "warming_up",
+        { num_planned: warmup },
+        "running warmup queries: {}",
+        warmup,
+    );
+    for qi in 0..warmup {
+        let results = db.query(
+            qvs.get(qi % qvs.len()),
+            1.try_into()?,
+            nprobe.try_into()?,
+        )?;
+        resolve_datum_ids(&results)?;
+    }
+    let memory_after_warmup = MemoryUsage::read()?;
+    info!(
+        "touching_all_partitions",
+        { num_partitions: db.num_partitions() },
+        "touching all partitions: {}",
+        db.num_partitions(),
+    );
+    db.query(qvs.get(0), 1.try_into()?, db.num_partitions().try_into()?)?;
+    let memory_after_touch = MemoryUsage::read()?;
+    provenance.finish();
+    let stats = LoadStats {
+        r#async: false,
+        num_loads,
+        seconds: Stats::compute(seconds),
+        num_warmup_queries: warmup,
+        nprobe,
+        memory_before_load,
+        memory_after_load,
+        memory_after_warmup,
+        memory_after_touch,
+        provenance: Some(provenance),
+    };
+    print_load_stats(&stats);
+    if let Some(stats_path) = stats_path.as_ref() {
+        info!(
+            "saving_stats",
+            { path: stats_path },
+            "saving stats: {}",
+            stats_path,
+        );
+        let file = File::create(stats_path)
+            .context(format!("failed to create stats file: {}", stats_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &stats)
+            .context(format!("failed to write stats to file: {}", stats_path))?;
+    }
+    Ok(())
+}
+
+fn do_load_async(
+    database_path: String,
+    queries_path: String,
+    num_loads: usize,
+    warmup: usize,
+    nprobe: usize,
+    stats_path: Option<String>,
+) -> Result<(), Error> {
+    if num_loads == 0 {
+        bail!("number of loads must be positive");
+    }
+    let qvs = load_query_vectors(&queries_path)?;
+    if qvs.len() == 0 {
+        bail!("no query vectors: {}", queries_path);
+    }
+    let mut provenance = Provenance::start();
+    provenance.add_file("queries", &queries_path)?;
+    provenance.add_file("database", &database_path)?;
+    let rt = Runtime::new()?;
+    let mut stats = rt.block_on(_do_load_async(
+        database_path,
+        num_loads,
+        warmup,
+        nprobe,
+        qvs,
+    ))?;
+    provenance.finish();
+    stats.provenance = Some(provenance);
+    print_load_stats(&stats);
+    if let Some(stats_path) = stats_path.as_ref() {
+        info!(
+            "saving_stats",
+            { path: stats_path },
+            "saving stats: {}",
+            stats_path,
+        );
+        let file = File::create(stats_path)
+            .context(format!("failed to create stats file: {}", stats_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &stats)
+            .context(format!("failed to write stats to file: {}", stats_path))?;
+    }
+    Ok(())
+}
+
+async fn _do_load_async(
+    database_path: String,
+    num_loads: usize,
+    warmup: usize,
+    nprobe: usize,
+    qvs: BlockVectorSet<f32>,
+) -> Result<LoadStats, Error> {
+    let database_path = Path::new(&database_path);
+    let base_path = database_path.parent().unwrap();
+    let memory_before_load = MemoryUsage::read()?;
+    info!(
+        "loading_database",
+        { path: database_path, num_loads: num_loads },
+        "loading database {} times: {:?}",
+        num_loads,
+        database_path,
+    );
+    let mut seconds: Vec<f64> = Vec::with_capacity(num_loads);
+    let mut db = None;
+    for _ in 0..num_loads {
+        // drops the previous instance before loading
+        db.take();
+        let time = std::time::Instant::now();
+        db = Some(load_database_async(
+            AsyncLocalFileSystem::new(base_path),
+            database_path,
+        ).await?);
+        seconds.push(time.elapsed().as_secs_f64());
+    }
+    let db = db.unwrap();
+    let memory_after_load = MemoryUsage::read()?;
+    info!(
+        "warming_up",
+        { num_planned: warmup },
+        "running warmup queries: {}",
+        warmup,
+    );
+    for qi in 0..warmup {
+        let results = db.query(
+            qvs.get(qi % qvs.len()),
+            1.try_into()?,
+            nprobe.try_into()?,
+        ).await?;
+        try_join_all(results
+            .iter()
+            .map(|result| result.get_attribute("datum_id")),
+        ).await?;
+    }
+    let memory_after_warmup = MemoryUsage::read()?;
+    info!(
+        "touching_all_partitions",
+        { num_partitions: db.num_partitions() },
+        "touching all partitions: {}",
+        db.num_partitions(),
+    );
+    db.query(
+        qvs.get(0),
+        1.try_into()?,
+        db.num_partitions().try_into()?,
+    ).await?;
+    let memory_after_touch = MemoryUsage::read()?;
+    Ok(LoadStats {
+        r#async: true,
+        num_loads,
+        seconds: Stats::compute(seconds),
+        num_warmup_queries: warmup,
+        nprobe,
+        memory_before_load,
+        memory_after_load,
+        memory_after_warmup,
+        memory_after_touch,
+        provenance: None,
+    })
+}
+
+// Prints statistics on loading a database.
+//
+// Logs the statistics as a single event.
+fn print_load_stats(stats: &LoadStats) {
+    let mut out = String::new();
+    writeln!(out, "Statistics").unwrap();
+    write_time_stats(&mut out, "load time", &stats.seconds, 1_000.0);
+    let mut write_memory = |label: &str, memory: &MemoryUsage| writeln!(
+        out,
+        "RSS {} (MB): {:.1} (+{:.1}), peak={:.1}",
+        label,
+        memory.rss_bytes as f64 / 1_000_000.0,
+        memory.rss_bytes.saturating_sub(stats.memory_before_load.rss_bytes) as f64
+            / 1_000_000.0,
+        memory.peak_rss_bytes as f64 / 1_000_000.0,
+    ).unwrap();
+    write_memory("before load", &stats.memory_before_load);
+    write_memory("after load", &stats.memory_after_load);
+    write_memory("after warmup", &stats.memory_after_warmup);
+    write_memory("after touching all partitions", &stats.memory_after_touch);
+    info!("load_stats", { stats: stats }, "{}", out.trim_end());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn do_tune(
+    dataset_path: String,
+    queries_path: String,
+    output_path: String,
+    grid: Vec<BuildParameters>,
+    nprobes: Vec<usize>,
+    train_size: usize,
+    k: usize,
+    limit: usize,
+    seed: u64,
+    report_path: Option<String>,
+    markdown_path: Option<String>,
+) -> Result<(), Error> {
+    let mut provenance = Provenance::start();
+    provenance.add_file("dataset", &dataset_path)?;
+    provenance.add_file("queries", &queries_path)?;
+    provenance.seed = Some(seed);
+    let vs = load_dataset(&dataset_path)?;
+    let qvs = load_query_vectors(&queries_path)?;
+    info!(
+        "sampling_training_subset",
+        { train_size: train_size, seed: seed },
+        "sampling training subset: {} (seed={})",
+        train_size,
+        seed,
+    );
+    let train_indices = sample_indices(vs.len(), train_size, seed);
+    let train_vs = extract_vectors(&vs, &train_indices)?;
+    info!(
+        "training_subset_sampled",
+        { num_vectors: train_vs.len() },
+        "number of training vectors: {}",
+        train_vs.len(),
+    );
+    // ground truth is shared by all the configurations
+    let num_queries = std::cmp::min(limit, qvs.len());
+    info!(
+        "flat_querying",
+        { num_queries: num_queries },
+        "flat-querying ground truth: {} queries",
+        num_queries,
+    );
+    let mut flat_results: Vec<Vec<usize>> = Vec::with_capacity(num_queries);
+    let mut flat_seconds: Vec<f64> = Vec::with_capacity(num_queries);
+    for qi in 0..num_queries {
+        let time = std::time::Instant::now();
+        flat_results.push(flat_query(&train_vs, qvs.get(qi), k));
+        flat_seconds.push(time.elapsed().as_secs_f64());
+    }
+    let mut configurations: Vec<ConfigurationResult> =
+        Vec::with_capacity(grid.len());
+    for (ci, parameters) in grid.into_iter().enumerate() {
+        info!(
+            "configuration_started",
+            { index: ci, parameters: parameters },
+            "configuration {}: {:?}",
+            ci,
+            parameters,
+        );
+        let config_path = Path::new(&output_path)
+            .join(parameters.folder_name());
+        let time = std::time::Instant::now();
+        let mut db = DatabaseBuilder::new(
+            extract_vectors(&vs, &train_indices)?,
+        )
+            .with_partitions(parameters.num_partitions.try_into()?)
+            .with_divisions(parameters.num_divisions.try_into()?)
+            .with_clusters(parameters.num_codes.try_into()?)
+            .build()
+            .context(format!("failed to build database: {:?}", parameters))?;
+        for i in 0..db.num_vectors() {
+            db.set_attribute_at(i, ("datum_id", i as u64))?;
+        }
+        let build_seconds = time.elapsed().as_secs_f64();
+        info!(
+            "database_built",
+            { seconds: build_seconds },
+            "built database in {} s",
+            build_seconds,
+        );
+        let time = std::time::Instant::now();
+        serialize_database(&db, &mut LocalFileSystem::new(&config_path))
+            .context(format!("failed to save database: {:?}", config_path))?;
+        let save_seconds = time.elapsed().as_secs_f64();
+        info!(
+            "database_saved",
+            { seconds: save_seconds },
+            "saved database in {} s",
+            save_seconds,
+        );
+        let disk_bytes = disk_usage(&config_path)?;
+        info!(
+            "disk_usage",
+            { bytes: disk_bytes },
+            "disk usage: {} bytes",
+            disk_bytes,
+        );
+        let header_paths = find_header_files(&config_path)?;
+        if header_paths.len() != 1 {
+            bail!(
+                "expected one database header file in {:?} but found {}. \
+                 use an empty output folder",
+                config_path,
+                header_paths.len(),
+            );
+        }
+        let db = stored::Database::<f32, _>::load_database(
+            LocalFileSystem::new(&config_path),
+            header_paths[0].file_name().unwrap().to_str().unwrap(),
+        ).context(format!("failed to load database: {:?}", header_paths[0]))?;
+        let mut query_stats: Vec<QueryStats> = Vec::with_capacity(nprobes.len());
+        for &nprobe in nprobes.iter() {
+            if nprobe > parameters.num_partitions {
+                info!(
+                    "nprobe_skipped",
+                    { nprobe: nprobe },
+                    "skipping nprobe > partitions: {}",
+                    nprobe,
+                );
+                continue;
+            }
+            let mut stats = QueryStatsRecorder::new(k, nprobe);
+            for qi in 0..num_queries {
+                let qv = qvs.get(qi);
+                let time = std::time::Instant::now();
+                let results = db.query(qv, k.try_into()?, nprobe.try_into()?)?;
+                let search_time = time.elapsed().as_secs_f64();
+                let time = std::time::Instant::now();
+                let results = resolve_datum_ids(&results)?;
+                let attribute_time = time.elapsed().as_secs_f64();
+                let recall = calculate_recall(&flat_results[qi], &results);
+                stats.add_record(
+                    search_time,
+                    attribute_time,
+                    flat_seconds[qi],
+                    recall,
+                );
+            }
+            let stats = stats.finish()?;
+            info!(
+                "nprobe_finished",
+                {
+                    nprobe: nprobe,
+                    mean_seconds: stats.seconds.mean,
+                    mean_recall: stats.recalls.as_ref().map(|r| r.mean),
+                },
+                "nprobe={}: time (ms)={:.3}, recall (%)={:.1}",
+                nprobe,
+                stats.seconds.mean * 1_000.0,
+                stats.recalls.as_ref().map_or(f32::NAN, |r| r.mean) * 100.0,
+            );
+            query_stats.push(stats);
+        }
+        configurations.push(ConfigurationResult {
+            parameters,
+            build_seconds,
+            save_seconds,
+            disk_bytes,
+            query_stats,
+        });
+    }
+    let mut report = TuneReport::new(
+        k,
+        train_vs.len(),
+        num_queries,
+        seed,
+        configurations,
+    );
+    provenance.finish();
+    report.provenance = Some(provenance);
+    let markdown = report.to_markdown();
+    info!("tune_report", { report: report }, "{}", markdown);
+    if let Some(report_path) = report_path.as_ref() {
+        info!(
+            "saving_report",
+            { path: report_path },
+            "saving report: {}",
+            report_path,
+        );
+        let file = File::create(report_path)
+            .context(format!("failed to create report file: {}", report_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report)
+            .context(format!("failed to write report to file: {}", report_path))?;
+    }
+    if let Some(markdown_path) = markdown_path.as_ref() {
+        info!(
+            "saving_markdown_report",
+            { path: markdown_path },
+            "saving Markdown report: {}",
+            markdown_path,
+        );
+        std::fs::write(markdown_path, markdown)
+            .context(format!("failed to write Markdown report: {}", markdown_path))?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn do_incremental(
+    dataset_path: String,
+    queries_path: String,
+    parameters: BuildParameters,
+    initial_fraction: f64,
+    num_batches: usize,
+    k: usize,
+    nprobe: usize,
+    limit: usize,
+    report_path: Option<String>,
+    markdown_path: Option<String>,
+) -> Result<(), Error> {
+    if nprobe > parameters.num_partitions {
+        bail!(
+            "nprobe must not exceed the number of partitions: {} > {}",
+            nprobe,
+            parameters.num_partitions,
+        );
+    }
+    if limit == 0 {
+        bail!("limit must be positive");
+    }
+    install_handler()?;
+    let mut provenance = Provenance::start();
+    provenance.add_file("dataset", &dataset_path)?;
+    provenance.add_file("queries", &queries_path)?;
+    let vs = load_dataset(&dataset_path)?;
+    let qvs = load_query_vectors(&queries_path)?;
+    let plan = plan_steps(vs.len(), initial_fraction, num_batches)?;
+    if plan[0] < parameters.num_partitions {
+        bail!(
+            "initial database has fewer vectors than partitions: {} < {}",
+            plan[0],
+            parameters.num_partitions,
+        );
+    }
+    if qvs.len() == 0 {
+        bail!("no query vectors: {}", queries_path);
+    }
+    let num_queries = std::cmp::min(limit, qvs.len());
+    let mut steps: Vec<InsertionStep> = Vec::with_capacity(plan.len());
+    for (step, &num_vectors) in plan.iter().enumerate() {
+        if is_interrupted() {
+            warn!(
+                "interrupted",
+                { num_completed: step, num_planned: plan.len() },
+                "interrupted: stopping after {}/{} steps",
+                step,
+                plan.len(),
+            );
+            break;
+        }
+        let num_added = match step {
+            0 => num_vectors,
+            _ => num_vectors - plan[step - 1],
+        };
+        info!(
+            "insertion_step",
+            { step: step, num_vectors: num_vectors, num_added: num_added },
+            "step {}: {} vectors (+{})",
+            step,
+            num_vectors,
+            num_added,
+        );
+        let indices: Vec<usize> = (0..num_vectors).collect();
+        let step_vs = extract_vectors(&vs, &indices)?;
+        // copies the vectors to build before timing the rebuild
+        let build_vs = copy_vectors(&step_vs)?;
+        let time = std::time::Instant::now();
+        let (fs, header_path) = build_in_memory(build_vs, parameters)?;
+        let rebuild_seconds = time.elapsed().as_secs_f64();
+        let db = load_database(fs, Path::new(&header_path))?;
+        info!(
+            "flat_querying",
+            { num_queries: num_queries },
+            "flat-querying ground truth: {} queries",
+            num_queries,
+        );
+        let mut flat_results: Vec<Vec<usize>> = Vec::with_capacity(num_queries);
+        let mut flat_seconds: Vec<f64> = Vec::with_capacity(num_queries);
+        for qi in 0..num_queries {
+            let time = std::time::Instant::now();
+            flat_results.push(flat_query(&step_vs, qvs.get(qi), k));
+            flat_seconds.push(time.elapsed().as_secs_f64());
+        }
+        let rebuilt = evaluate_database(
+            &db,
+            &qvs,
+            &flat_results,
+            &flat_seconds,
+            k,
+            nprobe,
+        )?;
+        let step = InsertionStep {
+            step,
+            num_vectors,
+            num_added,
+            rebuild_seconds,
+            rebuild_seconds_per_vector: rebuild_seconds / num_vectors as f64,
+            rebuilt,
+        };
+        let recall = step.rebuilt.recalls.as_ref().map(|r| r.mean);
+        info!(
+            "step_finished",
+            {
+                step: step.step,
+                rebuild_seconds: rebuild_seconds,
+                rebuild_seconds_per_vector: step.rebuild_seconds_per_vector,
+                recall: recall,
+            },
+            "step {}: rebuild (s)={:.1}, rebuild (μs/vector)={:.1}, recall (%)={:.1}",
+            step.step,
+            rebuild_seconds,
+            step.rebuild_seconds_per_vector * 1_000_000.0,
+            recall.map_or(f32::NAN, |r| r * 100.0),
+        );
+        steps.push(step);
+    }
+    provenance.finish();
+    let report = IncrementalReport {
+        parameters,
+        k,
+        nprobe,
+        num_queries,
+        initial_fraction,
+        steps,
+        provenance: Some(provenance),
+    };
+    let markdown = report.to_markdown();
+    info!("incremental_report", { report: report }, "{}", markdown);
+    if let Some(report_path) = report_path.as_ref() {
+        info!(
+            "saving_report",
+            { path: report_path },
+            "saving report: {}",
+            report_path,
+        );
+        let file = File::create(report_path)
+            .context(format!("failed to create report file: {}", report_path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report)
+            .context(format!("failed to write report to file: {}", report_path))?;
+    }
+    if let Some(markdown_path) = markdown_path.as_ref() {
+        info!(
+            "saving_markdown_report",
+            { path: markdown_path },
+            "saving Markdown report: {}",
+            markdown_path,
+        );
+        std::fs::write(markdown_path, markdown)
+            .context(format!("failed to write Markdown report: {}", markdown_path))?;
+    }
+    Ok(())
+}
+
+// Queries a database with the query vectors, and evaluates recalls against
+// given flat k-NN results.
+//
+// Queries as many as the flat k-NN results.
+fn evaluate_database<FS>(
+    db: &stored::Database<f32, FS>,
+    qvs: &BlockVectorSet<f32>,
+    flat_results: &[Vec<usize>],
+    flat_seconds: &[f64],
+    k: usize,
+    nprobe: usize,
+) -> Result<QueryStats, Error>
+where
+    FS: FileSystem,
+{
+    let mut stats = QueryStatsRecorder::new(k, nprobe);
+    for (qi, flat_result) in flat_results.iter().enumerate() {
+        let time = std::time::Instant::now();
+        let results = db.query(qvs.get(qi), k.try_into()?, nprobe.try_into()?)?;
+        let search_time = time.elapsed().as_secs_f64();
+        let time = std::time::Instant::now();
+        let results = resolve_datum_ids(&results)?;
+        let attribute_time = time.elapsed().as_secs_f64();
+        let recall = calculate_recall(flat_result, &results);
+        stats.add_record(search_time, attribute_time, flat_seconds[qi], recall);
+    }
+    Ok(stats.finish()?)
+}
+
+// Parses a latency model argument.
+fn parse_latency_model(s: &str) -> Result<LatencyModel, String> {
+    s.parse().map_err(|e| format!("{}", e))
+}
+
+// Parses an arrival process argument.
+fn parse_arrival_process(s: &str) -> Result<ArrivalProcess, String> {
+    s.parse().map_err(|e| format!("{}", e))
+}
+
+// Parses a synthetic attribute argument.
+fn parse_synthetic_attribute(s: &str) -> Result<SyntheticAttribute, String> {
+    s.parse().map_err(|e| format!("{}", e))
+}
+
+// Parses a workload component argument.
+fn parse_workload_component(s: &str) -> Result<WorkloadComponent, String> {
+    s.parse().map_err(|e| format!("{}", e))
+}
+
+// Parses a log level argument.
+fn parse_log_level(s: &str) -> Result<Level, String> {
+    s.parse().map_err(|e| format!("{}", e))
+}
+
+// Parses a log format argument.
+fn parse_log_format(s: &str) -> Result<LogFormat, String> {
+    s.parse().map_err(|e| format!("{}", e))
+}
+
+fn do_report(
+    stats_paths: Vec<String>,
+    output_path: Option<String>,
+    time_csv_path: Option<String>,
+    recall_csv_path: Option<String>,
+) -> Result<(), Error> {
+    let stats = stats_paths
+        .iter()
+        .map(|path| read_stats_file(path)
+            .context(format!("failed to read stats: {}", path)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let report = Report::new(&stats)?;
+    let markdown = report.to_markdown();
+    match output_path.as_ref() {
+        Some(output_path) => {
+            info!(
+                "saving_tables",
+                { path: output_path },
+                "saving tables: {}",
+                output_path,
+            );
+            std::fs::write(output_path, markdown)
+                .context(format!("failed to write tables: {}", output_path))?;
+        },
+        None => info!("report_tables", "{}", markdown.trim_end()),
+    }
+    if let Some(time_csv_path) = time_csv_path.as_ref() {
+        info!(
+            "saving_time_table",
+            { path: time_csv_path },
+            "saving query time table: {}",
+            time_csv_path,
+        );
+        std::fs::write(time_csv_path, report.time_csv())
+            .context(format!("failed to write table: {}", time_csv_path))?;
+    }
+    if let Some(recall_csv_path) = recall_csv_path.as_ref() {
+        info!(
+            "saving_recall_table",
+            { path: recall_csv_path },
+            "saving recall table: {}",
+            recall_csv_path,
+        );
+        std::fs::write(recall_csv_path, report.recall_csv())
+            .context(format!("failed to write table: {}", recall_csv_path))?;
+    }
+    Ok(())
+}
+
+fn do_compare(
+    baseline_path: String,
+    candidate_path: String,
+    thresholds: Thresholds,
+) -> Result<(), Error> {
+    let baseline = read_stats_file(&baseline_path)
+        .context(format!("failed to read stats: {}", baseline_path))?;
+    let candidate = read_stats_file(&candidate_path)
+        .context(format!("failed to read stats: {}", candidate_path))?;
+    let comparison = Comparison::new(&baseline, &candidate, &thresholds)?;
+    info!(
+        "comparing",
+        {
+            k: comparison.k,
+            nprobe: comparison.nprobe,
+            mode: if comparison.r#async { "async" } else { "sync" },
+        },
+        "k: {}\nnprobe: {}\nasync: {}",
+        comparison.k,
+        comparison.nprobe,
+        comparison.r#async,
+    );
+    for (label, stats) in [("baseline", &baseline), ("candidate", &candidate)] {
+        if let Some(partial) = stats.partial.as_ref() {
+            warn!(
+                "partial_stats",
+                {
+                    stats: label,
+                    num_completed: partial.num_completed,
+                    num_planned: partial.num_planned,
+                },
+                "{} statistics are partial ({}/{} queries)",
+                label,
+                partial.num_completed,
+                partial.num_planned,
+            );
+        }
+    }
+    for metric in comparison.metrics.iter() {
+        info!(
+            "metric_compared",
+            {
+                name: metric.name,
+                baseline: metric.baseline,
+                candidate: metric.candidate,
+                regressed: metric.regressed,
+            },
+            "{}: {:.3} → {:.3} ({:+.3}, {:+.1}%){}",
+            metric.name,
+            metric.baseline,
+            metric.candidate,
+            metric.delta(),
+            metric.percent_change(),
+            if metric.regressed { " REGRESSED" } else { "" },
+        );
+    }
+    match comparison.time_test.as_ref() {
+        Some(test) => info!(
+            "time_test",
+            { u: test.u, z: test.z, p_value: test.p_value },
+            "Mann–Whitney U test on query time: U={:.1}, z={:.3}, p={:.4}",
+            test.u,
+            test.z,
+            test.p_value,
+        ),
+        None => info!(
+            "time_test_skipped",
+            "Mann–Whitney U test on query time skipped: \
+             no raw samples (run batch with --keep-samples)",
+        ),
+    }
+    if comparison.has_regression() {
+        bail!("regressions detected");
+    }
+    info!("no_regressions", "no regressions detected");
+    Ok(())
+}
+
+// Resolves datum IDs of given query results.
+// Interprets the value of the `datum_id` attribute as a vector index.
+fn parse_datum_id(
+    value: Option<&AttributeValue>,
+) -> Result<usize, flechasdb::error::Error> {
+    match value {
+        Some(AttributeValue::Uint64(v)) => Ok(*v as usize),
+        Some(v) => Err(flechasdb::error::Error::InvalidData(format!(
+            "datum_id is not a u64 but {:?}",
+            v,
+        ))),
+        None => Err(flechasdb::error::Error::InvalidData(
+            "missing datum_id".to_string(),
+        )),
+    }
+}
+
+// Resolves the datum IDs of given query results.
+fn resolve_datum_ids<FS>(
+    results: &[stored::QueryResult<'_, f32, FS>],
+) -> Result<Vec<usize>, Error>
+where
+    FS: FileSystem,
+{
+    let results = results
+        .iter()
+        .map(|result| result
+            .get_attribute("datum_id")
+            .and_then(|value| parse_datum_id(value.as_deref())))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(results)
+}
+
+// Looks up given attributes of every result.
+//
+// Returns the time to look up each attribute of all the results in seconds.
+fn look_up_attributes<FS>(
+    results: &[stored::QueryResult<'_, f32, FS>],
+    names: &[String],
+) -> Result<Vec<(String, f64)>, Error>
+where
+    FS: FileSystem,
+{
+    let mut lookups: Vec<(String, f64)> = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let time = std::time::Instant::now();
+        for result in results.iter() {
+            result.get_attribute(name)?;
+        }
+        lookups.push((name.clone(), time.elapsed().as_secs_f64()));
+    }
+    Ok(lookups)
+}
+
+// Prints statistics on queries.
+//
+// Logs the statistics as a single event.
+fn print_query_stats(stats: &QueryStats) {
+    let time_unit: f64 = 1_000.0; // s → ms
+    let mut out = String::new();
+    writeln!(out, "Statistics").unwrap();
+    writeln!(out, "k: {}", stats.k).unwrap();
+    writeln!(out, "nprobe: {}", stats.nprobe).unwrap();
+    if let Some(partial) = stats.partial.as_ref() {
+        writeln!(
+            out,
+            "PARTIAL: interrupted after {}/{} queries",
+            partial.num_completed,
+            partial.num_planned,
+        ).unwrap();
+    }
+    if stats.num_failures > 0 {
+        writeln!(
+            out,
+            "failed queries: {}/{}",
+            stats.num_failures,
+            stats.num_queries + stats.num_failures,
+        ).unwrap();
+    }
+    write_time_stats(&mut out, "indexed time", &stats.seconds, time_unit);
+    write_time_stats(&mut out, "search time", &stats.search_seconds, time_unit);
+    if let Some(attribute_seconds) = stats.attribute_seconds.as_ref() {
+        write_time_stats(&mut out, "attribute time", attribute_seconds, time_unit);
+    }
+    if let Some(flat_seconds) = stats.flat_seconds.as_ref() {
+        write_time_stats(&mut out, "flat time", flat_seconds, time_unit);
+    }
+    if let Some(phases) = stats.phases.as_ref() {
+        for phase in phases.iter() {
+            write_time_stats(
+                &mut out,
+                &format!("{} time", phase.name),
+                &phase.seconds,
+                time_unit,
+            );
+        }
+    }
+    if let Some(lookups) = stats.attribute_lookups.as_ref() {
+        for lookup in lookups.iter() {
+            write_time_stats(
+                &mut out,
+                &format!("{} lookup time", lookup.name),
+                &lookup.seconds,
+                time_unit,
+            );
+        }
+    }
+    if let Some(touch) = stats.touch.as_ref() {
+        writeln!(
+            out,
+            "first-touch queries: {}/{}",
+            touch.num_first_touch,
+            touch.num_first_touch + touch.num_steady,
+        ).unwrap();
+        if let Some(seconds) = touch.first_touch_seconds.as_ref() {
+            write_time_stats(&mut out, "first-touch time", seconds, time_unit);
+        }
+        if let Some(seconds) = touch.steady_seconds.as_ref() {
+            write_time_stats(&mut out, "steady-state time", seconds, time_unit);
+        }
+    }
+    if let Some(workloads) = stats.workloads.as_ref() {
+        for workload in workloads.iter() {
+            writeln!(
+                out,
+                "{} queries: {}",
+                workload.kind,
+                workload.num_queries,
+            ).unwrap();
+            write_time_stats(
+                &mut out,
+                &format!("{} time", workload.kind),
+                &workload.seconds,
+                time_unit,
+            );
+            if let Some(recalls) = workload.recalls.as_ref() {
+                write_recall_stats(
+                    &mut out,
+                    &format!("{} recall", workload.kind),
+                    recalls,
+                );
+            }
+        }
+    }
+    if let Some(io) = stats.io.as_ref() {
+        write_count_stats(&mut out, "opened files", &io.num_opens);
+        write_count_stats(&mut out, "read operations", &io.num_reads);
+        write_count_stats(&mut out, "read bytes", &io.bytes_read);
+        write_time_stats(&mut out, "open time", &io.open_seconds, time_unit);
+        write_time_stats(&mut out, "read time", &io.read_seconds, time_unit);
+    }
+    if let Some(recalls) = stats.recalls.as_ref() {
+        write_recall_stats(&mut out, "recall", recalls);
+    }
+    if let Some(resources) = stats.resources.as_ref() {
+        write_resource_usage(&mut out, resources);
+    }
+    info!("query_stats", { stats: stats }, "{}", out.trim_end());
+}
+
+// Prints resource usage.
+//
+// Logs the resource usage as a single event.
+fn print_resource_usage(resources: &ResourceUsage) {
+    let mut out = String::new();
+    write_resource_usage(&mut out, resources);
+    info!("resource_usage", { resources: resources }, "{}", out.trim_end());
+}
+
+// Writes resource usage.
+fn write_resource_usage(out: &mut String, resources: &ResourceUsage) {
+    writeln!(
+        out,
+        "peak RSS (MB): {:.1}",
+        resources.peak_rss_bytes as f64 / 1_000_000.0,
+    ).unwrap();
+    writeln!(
+        out,
+        "CPU time (s): user={:.3}, system={:.3}, wall={:.3}",
+        resources.cpu.user_seconds,
+        resources.cpu.system_seconds,
+        resources.seconds,
+    ).unwrap();
+}
+
+// Writes statistics on time in milliseconds.
+fn write_time_stats(
+    out: &mut String,
+    label: &str,
+    stats: &Stats<f64>,
+    time_unit: f64,
+) {
+    writeln!(
+        out,
+        "{} (ms): {:.3}±{:.3}, median={:.3}, q1={:.3}, q3={:.3}, min={:.3}, max={:.3}",
+        label,
+        stats.mean * time_unit,
+        stats.std * time_unit,
+        stats.median * time_unit,
+        stats.q1 * time_unit,
+        stats.q3 * time_unit,
+        stats.min * time_unit,
+        stats.max * time_unit,
+    ).unwrap();
+}
+
+// Writes statistics on recalls in percent.
+fn write_recall_stats(out: &mut String, label: &str, stats: &Stats<f32>) {
+    writeln!(
+        out,
+        "{} (%): {:.1}±{:.1}, median={:.1}, q1={:.1}, q3={:.1}, min={:.1}, max={:.1}",
+        label,
+        stats.mean * 100.0,
+        stats.std * 100.0,
+        stats.median * 100.0,
+        stats.q1 * 100.0,
+        stats.q3 * 100.0,
+        stats.min * 100.0,
+        stats.max * 100.0,
+    ).unwrap();
+}
+
+// Writes statistics on counts.
+fn write_count_stats(out: &mut String, label: &str, stats: &Stats<f64>) {
+    writeln!(
+        out,
+        "{}: {:.1}±{:.1}, median={:.0}, q1={:.0}, q3={:.0}, min={:.0}, max={:.0}",
+        label,
+        stats.mean,
+        stats.std,
+        stats.median,
+        stats.q1,
+        stats.q3,
+        stats.min,
+        stats.max,
+    ).unwrap();
+}
+
+fn do_plot(
+    stats_paths: Vec<String>,
+    output_path: String,
+    num_bins: usize,
+) -> Result<(), Error> {
+    let time_unit: f64 = 1_000.0; // s → ms
+    let sweeps = read_sweeps(&stats_paths)
+        .context("failed to read stats")?;
+    std::fs::create_dir_all(&output_path)
+        .context(format!("failed to create folder: {}", output_path))?;
+    let output_path = Path::new(&output_path);
+    let save = |name: &str, svg: String| -> Result<(), Error> {
+        let path = output_path.join(name);
+        info!(
+            "saving_chart",
+            { path: path },
+            "saving chart: {}",
+            path.display(),
+        );
+        std::fs::write(&path, svg)
+            .context(format!("failed to write chart: {}", path.display()))
+    };
+    // recall vs. mean query time
+    let series: Vec<Series> = sweeps
+        .iter()
+        .map(|sweep| Series {
+            label: sweep.label.clone(),
+            points: sweep.stats
+                .iter()
+                .filter_map(|stats| stats.recalls.as_ref().map(|recalls| (
+                    stats.seconds.mean * time_unit,
+                    recalls.mean as f64 * 100.0,
+                )))
+                .collect(),
+        })
+        .collect();
+    save("recall-latency.svg", line_chart(
+        "Recall vs. query time",
+        &Axis::new("mean query time (ms)", Scale::Log),
+        &Axis::new("mean recall (%)", Scale::Linear),
+        &series,
+    ))?;
+    // QPS vs. recall
+    let series: Vec<Series> = series
+        .into_iter()
+        .map(|s| Series {
+            label: s.label,
+            points: s.points
+                .into_iter()
+                .map(|(ms, recall)| (recall, time_unit / ms))
+                .collect(),
+        })
+        .collect();
+    save("recall-qps.svg", line_chart(
+        "Queries per second vs. recall",
+        &Axis::new("mean recall (%)", Scale::Linear),
+        &Axis::new("queries per second", Scale::Log),
+        &series,
+    ))?;
+    // query time distributions
+    let boxes: Vec<BoxItem> = sweeps
+        .iter()
+        .flat_map(|sweep| sweep.stats.iter().map(|stats| BoxItem {
+            label: format!("{} nprobe={}", sweep.label, stats.nprobe),
+            stats: Stats {
+                mean: stats.seconds.mean * time_unit,
+                std: stats.seconds.std * time_unit,
+                median: stats.seconds.median * time_unit,
+                min: stats.seconds.min * time_unit,
+                max: stats.seconds.max * time_unit,
+                q1: stats.seconds.q1 * time_unit,
+                q3: stats.seconds.q3 * time_unit,
+            },
+        }))
+        .collect();
+    save("latency-boxplot.svg", box_plot(
+        "Query time",
+        &Axis::new("query time (ms)", Scale::Log),
+        &boxes,
+    ))?;
+    // per-query time histograms if samples are kept
+    for sweep in sweeps.iter() {
+        for stats in sweep.stats.iter() {
+            let Some(samples) = stats.samples.as_ref() else {
+                continue;
+            };
+            let values: Vec<f64> = samples.seconds
+                .iter()
+                .map(|s| s * time_unit)
+                .collect();
+            let name = format!(
+                "latency-histogram-{}-nprobe{}.svg",
+                sweep.label
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    })
+                    .collect::<String>(),
+                stats.nprobe,
+            );
+            save(&name, histogram(
+                &format!("Query time ({}, nprobe={})", sweep.label, stats.nprobe),
+                &Axis::new("query time (ms)", Scale::Log),
+                &values,
+                num_bins,
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn do_run(scenario_path: String, force: bool) -> Result<(), Error> {
+    let mut provenance = Provenance::start();
+    provenance.add_file("scenario", &scenario_path)?;
+    info!(
+        "loading_scenario",
+        { path: scenario_path },
+        "loading scenario: {}",
+        scenario_path,
+    );
+    let scenario = Scenario::read(&scenario_path)
+        .context(format!("failed to load scenario: {}", scenario_path))?;
+    let base_path = Path::new(&scenario_path).parent().unwrap();
+    let plan = scenario.plan(base_path)
+        .context(format!("invalid scenario: {}", scenario_path))?;
+    let synthetic_attributes = scenario.build.synthetic_attributes()?;
+    if !synthetic_attributes.is_empty() {
+        provenance.seed = Some(scenario.build.seed);
+    }
+    info!(
+        "scenario_planned",
+        { num_builds: plan.builds.len(), num_batches: plan.batches.len() },
+        "planned {} builds and {} batches",
+        plan.builds.len(),
+        plan.batches.len(),
+    );
+    for folder in ["databases", "builds", "stats"] {
+        let path = plan.output_path.join(folder);
+        std::fs::create_dir_all(&path)
+            .context(format!("failed to create folder: {}", path.display()))?;
+    }
+    for (i, build) in plan.builds.iter().enumerate() {
+        info!(
+            "scenario_build",
+            {
+                step: i + 1,
+                num_steps: plan.builds.len(),
+                dataset: build.dataset,
+                parameters: build.parameters,
+            },
+            "[build {}/{}] {} {}",
+            i + 1,
+            plan.builds.len(),
+            build.dataset,
+            build.parameters.folder_name(),
+        );
+        if !force && build.is_up_to_date()? {
+            info!(
+                "up_to_date",
+                { path: build.report_path },
+                "up to date: {}",
+                build.report_path.display(),
+            );
+            continue;
+        }
+        build.clear_settings()?;
+        // removes stale database files that would make the header ambiguous
+        if build.database_path.exists() {
+            std::fs::remove_dir_all(&build.database_path).context(format!(
+                "failed to remove database: {}",
+                build.database_path.display(),
+            ))?;
+        }
+        std::fs::create_dir_all(&build.database_path).context(format!(
+            "failed to create folder: {}",
+            build.database_path.display(),
+        ))?;
+        do_build(
+            path_string(&build.dataset_path),
+            path_string(&build.database_path),
+            build.parameters.num_partitions,
+            build.parameters.num_divisions,
+            build.parameters.num_codes,
+            None,
+            synthetic_attributes.clone(),
+            scenario.build.seed,
+            Duration::from_millis(scenario.sampling_interval),
+            Some(path_string(&build.report_path)),
+        )?;
+        build.save_settings()?;
+    }
+    for (i, batch) in plan.batches.iter().enumerate() {
+        let build = &plan.builds[batch.build_index];
+        info!(
+            "scenario_batch",
+            {
+                step: i + 1,
+                num_steps: plan.batches.len(),
+                dataset: build.dataset,
+                parameters: build.parameters,
+                mode: batch.mode.name(),
+                k: batch.k,
+                nprobe: batch.nprobe,
+            },
+            "[batch {}/{}] {} {} {} k={} nprobe={}",
+            i + 1,
+            plan.batches.len(),
+            build.dataset,
+            build.parameters.folder_name(),
+            batch.mode.name(),
+            batch.k,
+            batch.nprobe,
+        );
+        if !force && batch.is_up_to_date(build)? {
+            info!(
+                "up_to_date",
+                { path: batch.stats_path },
+                "up to date: {}",
+                batch.stats_path.display(),
+            );
+            continue;
+        }
+        let header_paths = find_header_files(&build.database_path)?;
+        let database_path = match &header_paths[..] {
+            [header_path] => path_string(header_path),
+            _ => bail!(
+                "no single database header in: {}",
+                build.database_path.display(),
+            ),
+        };
+        batch.clear_settings()?;
+        let options = BatchOptions::from_scenario(
+            &scenario.queries,
+            batch,
+            scenario.sampling_interval,
+        );
+        let dataset_path = path_string(&build.dataset_path);
+        let queries_path = path_string(&batch.queries_path);
+        let stats_path = Some(path_string(&batch.stats_path));
+        match batch.mode {
+            QueryMode::Sync => do_batch(
+                dataset_path,
+                database_path,
+                queries_path,
+                options,
+                stats_path,
+            )?,
+            QueryMode::Async => do_batch_async(
+                dataset_path,
+                database_path,
+                queries_path,
+                options,
+                stats_path,
+            )?,
+            QueryMode::Throughput => do_open_loop(
+                database_path,
+                queries_path,
+                OpenLoopOptions::from_scenario(&scenario.queries, batch)?,
+                stats_path,
+                None,
+            )?,
+        }
+        if is_interrupted() {
+            warn!(
+                "interrupted",
+                "interrupted: skipping the remaining steps",
+            );
+            return Ok(());
+        }
+        batch.save_settings()?;
+    }
+    provenance.finish();
+    info!(
+        "saving_results",
+        { path: plan.results_path },
+        "saving results: {}",
+        plan.results_path.display(),
+    );
+    let results = ResultsBundle::collect(scenario, &plan, provenance)
+        .context("failed to collect results")?;
+    let file = File::create(&plan.results_path).context(format!(
+        "failed to create results file: {}",
+        plan.results_path.display(),
+    ))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &results).context(format!(
+        "failed to write results to file: {}",
+        plan.results_path.display(),
+    ))?;
+    Ok(())
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
 }
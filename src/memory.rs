@@ -0,0 +1,280 @@
+//! In-memory file system.
+//!
+//! [`MemoryFileSystem`] keeps files of a database in memory so that a database
+//! can be built, serialized, loaded, and queried in the same process without
+//! touching the disk.
+//! It implements both [`flechasdb::io::FileSystem`] and
+//! [`flechasdb::asyncdb::io::FileSystem`] so that it can serve both
+//! synchronous and asynchronous databases.
+//!
+//! Files are named after the URL-safe Base64 encoded SHA-256 hash of their
+//! contents, as `flechasdb::io::LocalFileSystem` names them.
+
+use async_trait::async_trait;
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use flechasdb::asyncdb::io::{
+    FileSystem as AsyncFileSystem,
+    HashedFileIn as AsyncHashedFileIn,
+};
+use flechasdb::error::Error;
+use flechasdb::io::{FileSystem, HashedFileIn, HashedFileOut};
+
+use crate::files::HEADER_EXTENSION;
+
+// Files keyed by path.
+type FileMap = HashMap<String, Arc<Vec<u8>>>;
+
+/// File system that keeps files in memory.
+///
+/// Clones share the same files.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFileSystem {
+    files: Arc<Mutex<FileMap>>,
+}
+
+impl MemoryFileSystem {
+    /// Creates an empty file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the paths of database header files at the root.
+    pub fn find_header_files(&self) -> Vec<String> {
+        let suffix = format!(".{}", HEADER_EXTENSION);
+        let mut paths: Vec<String> = self.files
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| !path.contains('/') && path.ends_with(&suffix))
+            .cloned()
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Returns the total size of the files in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.files
+            .lock()
+            .unwrap()
+            .values()
+            .map(|data| data.len() as u64)
+            .sum()
+    }
+
+    fn open(&self, path: &str) -> Result<MemoryFileIn, Error> {
+        let data = self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Error::from(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no such file in memory: {}", path),
+            )))?;
+        // the file stem is the expected hash
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        let expected_hash = file_name
+            .split('.')
+            .next()
+            .unwrap_or(file_name)
+            .to_string();
+        Ok(MemoryFileIn {
+            data,
+            pos: 0,
+            hasher: Sha256::new(),
+            expected_hash,
+        })
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    type HashedFileOut = MemoryFileOut;
+    type HashedFileIn = MemoryFileIn;
+
+    fn create_hashed_file(&self) -> Result<Self::HashedFileOut, Error> {
+        self.create_hashed_file_in("")
+    }
+
+    fn create_hashed_file_in(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<Self::HashedFileOut, Error> {
+        Ok(MemoryFileOut {
+            files: self.files.clone(),
+            folder: path.as_ref().trim_matches('/').to_string(),
+            data: Vec::new(),
+            hasher: Sha256::new(),
+        })
+    }
+
+    fn open_hashed_file(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<Self::HashedFileIn, Error> {
+        self.open(path.as_ref())
+    }
+}
+
+#[async_trait]
+impl AsyncFileSystem for MemoryFileSystem {
+    type HashedFileIn = MemoryFileIn;
+
+    async fn open_hashed_file(
+        &self,
+        path: impl Into<String> + Send,
+    ) -> Result<Self::HashedFileIn, Error> {
+        self.open(&path.into())
+    }
+}
+
+/// File being written to a [`MemoryFileSystem`].
+///
+/// Not visible in the file system until persisted.
+pub struct MemoryFileOut {
+    files: Arc<Mutex<FileMap>>,
+    folder: String,
+    data: Vec<u8>,
+    hasher: Sha256,
+}
+
+impl Write for MemoryFileOut {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        self.hasher.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl HashedFileOut for MemoryFileOut {
+    fn persist(self, extension: impl AsRef<str>) -> Result<String, Error> {
+        let hash = URL_SAFE_NO_PAD.encode(self.hasher.finalize());
+        let file_name = format!("{}.{}", hash, extension.as_ref());
+        let path = if self.folder.is_empty() {
+            file_name
+        } else {
+            format!("{}/{}", self.folder, file_name)
+        };
+        self.files.lock().unwrap().insert(path, Arc::new(self.data));
+        Ok(hash)
+    }
+}
+
+/// File being read from a [`MemoryFileSystem`].
+pub struct MemoryFileIn {
+    data: Arc<Vec<u8>>,
+    pos: usize,
+    hasher: Sha256,
+    expected_hash: String,
+}
+
+impl MemoryFileIn {
+    // Copies bytes to a given buffer and advances the position.
+    fn read_to(&mut self, buf: &mut [u8]) -> usize {
+        let n = std::cmp::min(buf.len(), self.data.len() - self.pos);
+        let bytes = &self.data[self.pos..self.pos + n];
+        buf[..n].copy_from_slice(bytes);
+        self.hasher.update(bytes);
+        self.pos += n;
+        n
+    }
+
+    // Verifies the hash of the bytes read so far.
+    fn verify_hash(self) -> Result<(), Error> {
+        let hash = URL_SAFE_NO_PAD.encode(self.hasher.finalize());
+        if hash == self.expected_hash {
+            Ok(())
+        } else {
+            Err(Error::InvalidData(format!(
+                "hash mismatch: expected {} but got {}",
+                self.expected_hash,
+                hash,
+            )))
+        }
+    }
+}
+
+impl Read for MemoryFileIn {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.read_to(buf))
+    }
+}
+
+impl HashedFileIn for MemoryFileIn {
+    fn verify(self) -> Result<(), Error> {
+        self.verify_hash()
+    }
+}
+
+impl AsyncRead for MemoryFileIn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let n = self.read_to(buf.initialize_unfilled());
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[async_trait]
+impl AsyncHashedFileIn for MemoryFileIn {
+    async fn verify(self) -> Result<(), Error> {
+        self.verify_hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a file and returns its path.
+    fn write_file(fs: &MemoryFileSystem, data: &[u8]) -> String {
+        let mut file = fs.create_hashed_file().unwrap();
+        file.write_all(data).unwrap();
+        let hash = file.persist("bin").unwrap();
+        format!("{}.bin", hash)
+    }
+
+    // Reads a whole file and verifies it.
+    fn read_file(fs: &MemoryFileSystem, path: &str) -> Result<Vec<u8>, Error> {
+        let mut file = FileSystem::open_hashed_file(fs, path)?;
+        let mut data: Vec<u8> = Vec::new();
+        file.read_to_end(&mut data)?;
+        HashedFileIn::verify(file)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn verify_intact_file() {
+        let fs = MemoryFileSystem::new();
+        let path = write_file(&fs, b"flechasdb");
+        assert_eq!(read_file(&fs, &path).unwrap(), b"flechasdb");
+    }
+
+    #[test]
+    fn verify_corrupted_file() {
+        let fs = MemoryFileSystem::new();
+        let path = write_file(&fs, b"flechasdb");
+        {
+            let mut files = fs.files.lock().unwrap();
+            let data = files.get_mut(&path).unwrap();
+            Arc::make_mut(data)[0] ^= 0x01;
+        }
+        assert!(matches!(read_file(&fs, &path), Err(Error::InvalidData(_))));
+    }
+}
@@ -0,0 +1,388 @@
+//! Open-loop load generation.
+//!
+//! A closed-loop batch issues the next query after the previous one finishes,
+//! so a slow query also delays the queries behind it, and their queueing delay
+//! never shows up in the measurements (coordinated omission).
+//! An open-loop load generator schedules queries at a target arrival rate
+//! regardless of completions, and measures the latency of each query from its
+//! intended send time.
+//!
+//! Sweeping the arrival rate finds the saturation point; the lowest rate at
+//! which the database cannot keep up with the offered load.
+
+use core::str::FromStr;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+use crate::error::Error;
+use crate::provenance::Provenance;
+use crate::stats::Stats;
+
+/// Arrival process of queries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArrivalProcess {
+    /// Exponentially distributed inter-arrival times.
+    Poisson,
+    /// Evenly spaced arrivals.
+    Constant,
+}
+
+impl ArrivalProcess {
+    /// Returns the name of the process.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArrivalProcess::Poisson => "poisson",
+            ArrivalProcess::Constant => "constant",
+        }
+    }
+
+    /// Schedules arrivals at a given rate in queries per second over a given
+    /// duration in seconds.
+    ///
+    /// Returns the arrival times in seconds since the start in ascending
+    /// order.
+    pub fn schedule(
+        &self,
+        rate: f64,
+        duration: f64,
+        rng: &mut impl Rng,
+    ) -> Vec<f64> {
+        let mut arrivals: Vec<f64> =
+            Vec::with_capacity((rate * duration).ceil() as usize);
+        match self {
+            ArrivalProcess::Poisson => {
+                let mut t = 0.0f64;
+                loop {
+                    let u: f64 = 1.0 - rng.gen::<f64>(); // (0, 1]
+                    t -= u.ln() / rate;
+                    if t >= duration {
+                        break;
+                    }
+                    arrivals.push(t);
+                }
+            },
+            ArrivalProcess::Constant => {
+                let num_arrivals = (rate * duration).ceil() as usize;
+                arrivals.extend((0..num_arrivals).map(|i| i as f64 / rate));
+            },
+        }
+        arrivals
+    }
+}
+
+impl FromStr for ArrivalProcess {
+    type Err = Error;
+
+    /// Parses `poisson` or `constant`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poisson" => Ok(ArrivalProcess::Poisson),
+            "constant" => Ok(ArrivalProcess::Constant),
+            _ => Err(Error::InvalidData(format!(
+                "unknown arrival process: {}",
+                s,
+            ))),
+        }
+    }
+}
+
+/// Criteria of saturation.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SaturationCriteria {
+    /// A rate is saturated if the achieved throughput falls below this
+    /// fraction of the offered rate.
+    pub min_throughput_ratio: f64,
+    /// A rate is saturated if the 99th percentile latency exceeds this in
+    /// seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_p99_seconds: Option<f64>,
+}
+
+/// Latency percentiles.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Percentiles {
+    /// 50th percentile.
+    pub p50: f64,
+    /// 90th percentile.
+    pub p90: f64,
+    /// 99th percentile.
+    pub p99: f64,
+    /// 99.9th percentile.
+    pub p999: f64,
+}
+
+impl Percentiles {
+    /// Computes the percentiles of given values.
+    ///
+    /// `None` if there are no values.
+    pub fn compute(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut values = values.to_vec();
+        values.sort_by(|l, r| l.partial_cmp(r).unwrap());
+        let percentile = |p: f64| {
+            let i = ((values.len() as f64 * p).ceil() as usize)
+                .clamp(1, values.len()) - 1;
+            values[i]
+        };
+        Some(Self {
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            p999: percentile(0.999),
+        })
+    }
+}
+
+/// Recorder of queries issued at a single arrival rate.
+pub struct RateRecorder {
+    target_rate: f64,
+    duration: f64,
+    num_scheduled: usize,
+    num_failures: usize,
+    num_dropped: usize,
+    max_in_flight: usize,
+    latencies: Vec<f64>,
+    service_seconds: Vec<f64>,
+    send_lags: Vec<f64>,
+}
+
+impl RateRecorder {
+    /// Creates a recorder for a given target rate in queries per second and
+    /// duration in seconds.
+    pub fn new(target_rate: f64, duration: f64) -> Self {
+        let capacity = (target_rate * duration).ceil() as usize;
+        Self {
+            target_rate,
+            duration,
+            num_scheduled: 0,
+            num_failures: 0,
+            num_dropped: 0,
+            max_in_flight: 0,
+            latencies: Vec::with_capacity(capacity),
+            service_seconds: Vec::with_capacity(capacity),
+            send_lags: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a query that is due.
+    ///
+    /// `in_flight` is the number of queries in flight including it.
+    pub fn add_issued(&mut self, in_flight: usize) {
+        self.num_scheduled += 1;
+        self.max_in_flight = self.max_in_flight.max(in_flight);
+    }
+
+    /// Adds a query that was dropped because too many queries were in
+    /// flight.
+    pub fn add_dropped(&mut self) {
+        self.num_scheduled += 1;
+        self.num_dropped += 1;
+    }
+
+    /// Adds a completed query.
+    ///
+    /// `latency` is the time from the intended send time to the completion,
+    /// and `send_lag` is the time from the intended send time to the actual
+    /// send time, both in seconds.
+    pub fn add_completion(&mut self, latency: f64, send_lag: f64) {
+        self.latencies.push(latency);
+        self.service_seconds.push(latency - send_lag);
+        self.send_lags.push(send_lag);
+    }
+
+    /// Adds a failed query.
+    pub fn add_failure(&mut self) {
+        self.num_failures += 1;
+    }
+
+    /// Finishes recording.
+    ///
+    /// `seconds` is the time from the start to the last completion.
+    pub fn finish(
+        self,
+        seconds: f64,
+        criteria: &SaturationCriteria,
+    ) -> RateResult {
+        let num_completed = self.latencies.len();
+        let offered_qps = self.num_scheduled as f64 / self.duration;
+        let achieved_qps = if seconds > 0.0 {
+            num_completed as f64 / seconds
+        } else {
+            0.0
+        };
+        let latency_percentiles = Percentiles::compute(&self.latencies);
+        let saturated = self.num_dropped > 0
+            || achieved_qps < criteria.min_throughput_ratio * offered_qps
+            || criteria.max_p99_seconds
+                .zip(latency_percentiles)
+                .is_some_and(|(max, p)| p.p99 > max);
+        RateResult {
+            target_rate: self.target_rate,
+            num_scheduled: self.num_scheduled,
+            num_completed,
+            num_failures: self.num_failures,
+            num_dropped: self.num_dropped,
+            seconds,
+            offered_qps,
+            achieved_qps,
+            max_in_flight: self.max_in_flight,
+            latency_percentiles,
+            latency_seconds: Stats::compute_if_any(self.latencies),
+            service_seconds: Stats::compute_if_any(self.service_seconds),
+            send_lag_seconds: Stats::compute_if_any(self.send_lags),
+            saturated,
+        }
+    }
+}
+
+/// Result of a single arrival rate.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RateResult {
+    /// Target arrival rate in queries per second.
+    pub target_rate: f64,
+    /// Number of queries that were due.
+    pub num_scheduled: usize,
+    /// Number of completed queries.
+    pub num_completed: usize,
+    /// Number of failed queries.
+    pub num_failures: usize,
+    /// Number of queries dropped because too many queries were in flight.
+    pub num_dropped: usize,
+    /// Time from the start to the last completion in seconds.
+    pub seconds: f64,
+    /// Actual arrival rate in queries per second.
+    pub offered_qps: f64,
+    /// Completed queries per second.
+    pub achieved_qps: f64,
+    /// Maximum number of queries in flight.
+    pub max_in_flight: usize,
+    /// Percentiles of latencies in seconds.
+    ///
+    /// `None` if no query completed.
+    pub latency_percentiles: Option<Percentiles>,
+    /// Latencies from the intended send time to the completion in seconds.
+    pub latency_seconds: Option<Stats<f64>>,
+    /// Time from the actual send time to the completion in seconds.
+    pub service_seconds: Option<Stats<f64>>,
+    /// Time from the intended send time to the actual send time in seconds.
+    pub send_lag_seconds: Option<Stats<f64>>,
+    /// Whether the database could not keep up with the rate.
+    pub saturated: bool,
+}
+
+/// Report of an open-loop rate sweep.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenLoopReport {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Arrival process.
+    pub arrival: String,
+    /// Duration in seconds to issue queries at each rate.
+    pub duration: f64,
+    /// Maximum number of queries in flight.
+    pub max_in_flight: usize,
+    /// Criteria of saturation.
+    pub criteria: SaturationCriteria,
+    /// Results of the rates in the order swept.
+    pub rates: Vec<RateResult>,
+    /// Lowest saturated rate in queries per second.
+    ///
+    /// `None` if no rate is saturated.
+    pub saturation_rate: Option<f64>,
+    /// Highest achieved throughput among the unsaturated rates in queries
+    /// per second.
+    ///
+    /// `None` if every rate is saturated.
+    pub max_sustained_qps: Option<f64>,
+    /// Provenance of the results.
+    ///
+    /// Set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+impl OpenLoopReport {
+    /// Creates a report and locates the saturation point.
+    pub fn new(
+        k: usize,
+        nprobe: usize,
+        arrival: ArrivalProcess,
+        duration: f64,
+        max_in_flight: usize,
+        criteria: SaturationCriteria,
+        rates: Vec<RateResult>,
+    ) -> Self {
+        let saturation_rate = rates
+            .iter()
+            .filter(|r| r.saturated)
+            .map(|r| r.target_rate)
+            .min_by(|l, r| l.partial_cmp(r).unwrap());
+        let max_sustained_qps = rates
+            .iter()
+            .filter(|r| !r.saturated)
+            .map(|r| r.achieved_qps)
+            .max_by(|l, r| l.partial_cmp(r).unwrap());
+        Self {
+            k,
+            nprobe,
+            arrival: arrival.name().to_string(),
+            duration,
+            max_in_flight,
+            criteria,
+            rates,
+            saturation_rate,
+            max_sustained_qps,
+            provenance: None,
+        }
+    }
+
+    /// Renders the report as a Markdown table.
+    pub fn to_markdown(&self) -> String {
+        let time_unit: f64 = 1_000.0; // s → ms
+        let mut md = String::new();
+        writeln!(
+            md,
+            "### Open-loop latency (k={}, nprobe={}, {} arrivals, {} s per rate)",
+            self.k,
+            self.nprobe,
+            self.arrival,
+            self.duration,
+        ).unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| target (qps) | offered (qps) | achieved (qps) | p50 (ms) | p90 (ms) | p99 (ms) | p99.9 (ms) | dropped | failed | saturated |").unwrap();
+        writeln!(md, "| ------------ | ------------- | -------------- | -------- | -------- | -------- | ---------- | ------- | ------ | --------- |").unwrap();
+        for rate in self.rates.iter() {
+            let p = |f: fn(&Percentiles) -> f64| rate.latency_percentiles
+                .as_ref()
+                .map_or(f64::NAN, f) * time_unit;
+            writeln!(
+                md,
+                "| {} | {:.1} | {:.1} | {:.3} | {:.3} | {:.3} | {:.3} | {} | {} | {} |",
+                rate.target_rate,
+                rate.offered_qps,
+                rate.achieved_qps,
+                p(|p| p.p50),
+                p(|p| p.p90),
+                p(|p| p.p99),
+                p(|p| p.p999),
+                rate.num_dropped,
+                rate.num_failures,
+                if rate.saturated { "yes" } else { "no" },
+            ).unwrap();
+        }
+        writeln!(md).unwrap();
+        match self.saturation_rate {
+            Some(rate) => writeln!(md, "Saturated at {} qps.", rate).unwrap(),
+            None => writeln!(md, "Not saturated.").unwrap(),
+        }
+        if let Some(qps) = self.max_sustained_qps {
+            writeln!(md, "Maximum sustained throughput: {:.1} qps.", qps).unwrap();
+        }
+        md
+    }
+}
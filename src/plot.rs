@@ -0,0 +1,531 @@
+//! SVG charts of query statistics.
+//!
+//! Charts are rendered as self-contained SVG documents without any external
+//! plotting tool.
+
+use core::fmt::Write as _;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::report::read_stats_file;
+use crate::stats::{QueryStats, Stats};
+use crate::tune::TuneReport;
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 480.0;
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 200.0;
+const MARGIN_TOP: f64 = 50.0;
+const MARGIN_BOTTOM: f64 = 100.0;
+const COLORS: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+    "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+/// Statistics over nprobes on a single database configuration.
+pub struct Sweep {
+    /// Label of the configuration.
+    pub label: String,
+    /// Statistics sorted by nprobe.
+    pub stats: Vec<QueryStats>,
+}
+
+/// Reads sweeps from statistics files of `batch` or reports of `tune`.
+///
+/// Each configuration in a report of `tune` makes a sweep.
+/// Statistics files of `batch` are grouped into sweeps by the database file
+/// in their provenance and the execution mode.
+pub fn read_sweeps(paths: &[impl AsRef<Path>]) -> Result<Vec<Sweep>, Error> {
+    let mut sweeps: Vec<Sweep> = Vec::new();
+    // label → index in `sweeps`
+    let mut batch_sweeps: BTreeMap<String, usize> = BTreeMap::new();
+    for path in paths.iter().map(|p| p.as_ref()) {
+        let contents = std::fs::read_to_string(path)?;
+        let is_report = serde_json::from_str::<serde_json::Value>(&contents)
+            .map_err(|e| Error::InvalidData(format!(
+                "failed to parse JSON file {:?}: {}",
+                path,
+                e,
+            )))?
+            .get("configurations")
+            .is_some();
+        if is_report {
+            let report: TuneReport = serde_json::from_str(&contents)
+                .map_err(|e| Error::InvalidData(format!(
+                    "failed to parse tune report {:?}: {}",
+                    path,
+                    e,
+                )))?;
+            for config in report.configurations.into_iter() {
+                sweeps.push(Sweep {
+                    label: config.parameters.folder_name(),
+                    stats: config.query_stats,
+                });
+            }
+        } else {
+            let stats = read_stats_file(path)?;
+            let label = batch_label(&stats);
+            let i = *batch_sweeps.entry(label.clone()).or_insert_with(|| {
+                sweeps.push(Sweep { label, stats: Vec::new() });
+                sweeps.len() - 1
+            });
+            sweeps[i].stats.push(stats);
+        }
+    }
+    for sweep in sweeps.iter_mut() {
+        sweep.stats.sort_by_key(|s| s.nprobe);
+    }
+    Ok(sweeps)
+}
+
+// Labels statistics of `batch` with the database folder and the execution
+// mode.
+fn batch_label(stats: &QueryStats) -> String {
+    let database = stats.provenance
+        .as_ref()
+        .and_then(|provenance| provenance.files
+            .iter()
+            .find(|file| file.role == "database"))
+        .map(|file| {
+            let path = Path::new(&file.path);
+            path.parent()
+                .and_then(|parent| parent.file_name())
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        })
+        .unwrap_or_else(|| "database".to_string());
+    if stats.r#async {
+        format!("{} (async)", database)
+    } else {
+        database
+    }
+}
+
+/// Scale of an axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    /// Linear scale.
+    Linear,
+    /// Logarithmic scale.
+    ///
+    /// Non-positive values are not plotted.
+    Log,
+}
+
+/// Axis of a chart.
+#[derive(Clone, Debug)]
+pub struct Axis {
+    /// Label.
+    pub label: String,
+    /// Scale.
+    pub scale: Scale,
+}
+
+impl Axis {
+    /// Creates an axis.
+    pub fn new(label: impl Into<String>, scale: Scale) -> Self {
+        Self { label: label.into(), scale }
+    }
+}
+
+/// Series of points connected by a line.
+#[derive(Clone, Debug)]
+pub struct Series {
+    /// Label shown in the legend.
+    pub label: String,
+    /// Points (x, y).
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Box of a box plot.
+#[derive(Debug)]
+pub struct BoxItem {
+    /// Label shown under the box.
+    pub label: String,
+    /// Statistics; the box spans from Q1 to Q3, and whiskers reach the
+    /// minimum and maximum.
+    pub stats: Stats<f64>,
+}
+
+/// Renders a line chart.
+pub fn line_chart(title: &str, x: &Axis, y: &Axis, series: &[Series]) -> String {
+    let points = || series
+        .iter()
+        .flat_map(|s| s.points.iter().copied())
+        .filter(|&(px, py)| x.is_plottable(px) && y.is_plottable(py));
+    let x_map = Mapping::fit(x.scale, points().map(|p| p.0), MARGIN_LEFT, WIDTH - MARGIN_RIGHT);
+    let y_map = Mapping::fit(y.scale, points().map(|p| p.1), HEIGHT - MARGIN_BOTTOM, MARGIN_TOP);
+    let mut svg = begin_svg(title);
+    draw_axes(&mut svg, x, &x_map, y, &y_map);
+    for (i, s) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let coords: Vec<(f64, f64)> = s.points
+            .iter()
+            .filter(|&&(px, py)| x.is_plottable(px) && y.is_plottable(py))
+            .map(|&(px, py)| (x_map.map(px), y_map.map(py)))
+            .collect();
+        let polyline = coords
+            .iter()
+            .map(|(cx, cy)| format!("{:.1},{:.1}", cx, cy))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+            polyline,
+            color,
+        ).unwrap();
+        for (cx, cy) in coords.iter() {
+            writeln!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#,
+                cx,
+                cy,
+                color,
+            ).unwrap();
+        }
+        draw_legend_entry(&mut svg, i, &s.label, color);
+    }
+    end_svg(svg)
+}
+
+/// Renders a box plot.
+pub fn box_plot(title: &str, y: &Axis, boxes: &[BoxItem]) -> String {
+    let y_map = Mapping::fit(
+        y.scale,
+        boxes
+            .iter()
+            .flat_map(|b| [b.stats.min, b.stats.max])
+            .filter(|&v| y.is_plottable(v)),
+        HEIGHT - MARGIN_BOTTOM,
+        MARGIN_TOP,
+    );
+    let mut svg = begin_svg(title);
+    draw_y_axis(&mut svg, y, &y_map);
+    draw_frame(&mut svg);
+    let slot = (WIDTH - MARGIN_LEFT - MARGIN_RIGHT) / boxes.len().max(1) as f64;
+    let half = (slot * 0.3).min(20.0);
+    for (i, b) in boxes.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let cx = MARGIN_LEFT + slot * (i as f64 + 0.5);
+        let v = |value: f64| y_map.map(if y.is_plottable(value) {
+            value
+        } else {
+            y_map.min
+        });
+        let (min, q1, median, q3, max) = (
+            v(b.stats.min),
+            v(b.stats.q1),
+            v(b.stats.median),
+            v(b.stats.q3),
+            v(b.stats.max),
+        );
+        writeln!(
+            svg,
+            r#"<line x1="{cx:.1}" y1="{max:.1}" x2="{cx:.1}" y2="{q3:.1}" stroke="{color}"/>"#,
+        ).unwrap();
+        writeln!(
+            svg,
+            r#"<line x1="{cx:.1}" y1="{q1:.1}" x2="{cx:.1}" y2="{min:.1}" stroke="{color}"/>"#,
+        ).unwrap();
+        for whisker in [min, max] {
+            writeln!(
+                svg,
+                r#"<line x1="{:.1}" y1="{whisker:.1}" x2="{:.1}" y2="{whisker:.1}" stroke="{color}"/>"#,
+                cx - half * 0.5,
+                cx + half * 0.5,
+            ).unwrap();
+        }
+        writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{q3:.1}" width="{:.1}" height="{:.1}" fill="{color}" fill-opacity="0.3" stroke="{color}"/>"#,
+            cx - half,
+            half * 2.0,
+            (q1 - q3).max(0.0),
+        ).unwrap();
+        writeln!(
+            svg,
+            r#"<line x1="{:.1}" y1="{median:.1}" x2="{:.1}" y2="{median:.1}" stroke="{color}" stroke-width="2"/>"#,
+            cx - half,
+            cx + half,
+        ).unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{cx:.1}" y="{:.1}" font-size="11" text-anchor="end" transform="rotate(-40 {cx:.1} {:.1})">{}</text>"#,
+            HEIGHT - MARGIN_BOTTOM + 14.0,
+            HEIGHT - MARGIN_BOTTOM + 14.0,
+            escape(&b.label),
+        ).unwrap();
+    }
+    end_svg(svg)
+}
+
+/// Renders a histogram.
+///
+/// Bins are evenly spaced on the scale of `x`.
+pub fn histogram(title: &str, x: &Axis, values: &[f64], num_bins: usize) -> String {
+    let values: Vec<f64> = values
+        .iter()
+        .copied()
+        .filter(|&v| x.is_plottable(v))
+        .collect();
+    let num_bins = num_bins.max(1);
+    let x_map = Mapping::fit_tight(x.scale, values.iter().copied(), MARGIN_LEFT, WIDTH - MARGIN_RIGHT);
+    let (lo, hi) = (x_map.forward(x_map.min), x_map.forward(x_map.max));
+    let mut counts = vec![0usize; num_bins];
+    for &v in values.iter() {
+        let i = ((x_map.forward(v) - lo) / (hi - lo) * num_bins as f64) as usize;
+        counts[i.min(num_bins - 1)] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let y = Axis::new("number of queries", Scale::Linear);
+    let y_map = Mapping::fit(y.scale, [0.0, max_count as f64].into_iter(), HEIGHT - MARGIN_BOTTOM, MARGIN_TOP);
+    let mut svg = begin_svg(title);
+    draw_axes(&mut svg, x, &x_map, &y, &y_map);
+    let bin_width = (WIDTH - MARGIN_LEFT - MARGIN_RIGHT) / num_bins as f64;
+    for (i, &count) in counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let top = y_map.map(count as f64);
+        writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" fill-opacity="0.6" stroke="{}"/>"#,
+            MARGIN_LEFT + bin_width * i as f64,
+            top,
+            bin_width,
+            y_map.map(0.0) - top,
+            COLORS[0],
+            COLORS[0],
+        ).unwrap();
+    }
+    end_svg(svg)
+}
+
+impl Axis {
+    fn is_plottable(&self, value: f64) -> bool {
+        value.is_finite() && (self.scale == Scale::Linear || value > 0.0)
+    }
+}
+
+// Mapping from values to coordinates.
+struct Mapping {
+    scale: Scale,
+    min: f64,
+    max: f64,
+    from: f64,
+    to: f64,
+}
+
+impl Mapping {
+    // Fits the domain to given values with a margin.
+    fn fit(
+        scale: Scale,
+        values: impl Iterator<Item = f64>,
+        from: f64,
+        to: f64,
+    ) -> Self {
+        let mut mapping = Self::fit_tight(scale, values, from, to);
+        let (lo, hi) = (mapping.forward(mapping.min), mapping.forward(mapping.max));
+        let pad = (hi - lo) * 0.05;
+        mapping.min = mapping.inverse(lo - pad);
+        mapping.max = mapping.inverse(hi + pad);
+        mapping
+    }
+
+    // Fits the domain to given values without a margin.
+    fn fit_tight(
+        scale: Scale,
+        values: impl Iterator<Item = f64>,
+        from: f64,
+        to: f64,
+    ) -> Self {
+        let (min, max) = values.fold(
+            (f64::INFINITY, f64::NEG_INFINITY),
+            |(min, max), v| (min.min(v), max.max(v)),
+        );
+        let (min, max) = if min > max {
+            match scale {
+                Scale::Linear => (0.0, 1.0),
+                Scale::Log => (1.0, 10.0),
+            }
+        } else if min == max {
+            match scale {
+                Scale::Linear => (min - 0.5, max + 0.5),
+                Scale::Log => (min * 0.5, max * 2.0),
+            }
+        } else {
+            (min, max)
+        };
+        Self { scale, min, max, from, to }
+    }
+
+    fn forward(&self, value: f64) -> f64 {
+        match self.scale {
+            Scale::Linear => value,
+            Scale::Log => value.log10(),
+        }
+    }
+
+    fn inverse(&self, value: f64) -> f64 {
+        match self.scale {
+            Scale::Linear => value,
+            Scale::Log => 10f64.powf(value),
+        }
+    }
+
+    fn map(&self, value: f64) -> f64 {
+        let (lo, hi) = (self.forward(self.min), self.forward(self.max));
+        self.from + (self.forward(value) - lo) / (hi - lo) * (self.to - self.from)
+    }
+
+    // Chooses tick values.
+    fn ticks(&self) -> Vec<f64> {
+        if self.scale == Scale::Log {
+            let ticks: Vec<f64> = (self.min.log10().ceil() as i32
+                ..=self.max.log10().floor() as i32)
+                .map(|e| 10f64.powi(e))
+                .collect();
+            if ticks.len() >= 2 {
+                return ticks;
+            }
+        }
+        let step = nice_step((self.max - self.min) / 6.0);
+        let first = (self.min / step).ceil() as i64;
+        let last = (self.max / step).floor() as i64;
+        (first..=last).map(|i| i as f64 * step).collect()
+    }
+}
+
+// Rounds a step to 1, 2, or 5 × 10^n.
+fn nice_step(rough: f64) -> f64 {
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let fraction = rough / magnitude;
+    let nice = if fraction <= 1.0 {
+        1.0
+    } else if fraction <= 2.0 {
+        2.0
+    } else if fraction <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+fn format_tick(value: f64) -> String {
+    if value == 0.0 {
+        "0".to_string()
+    } else if value.abs() >= 10_000.0 || value.abs() < 0.001 {
+        format!("{:.0e}", value)
+    } else {
+        let s = format!("{:.3}", value);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+fn begin_svg(title: &str) -> String {
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif">"#,
+    ).unwrap();
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{:.1}" y="28" font-size="16" text-anchor="middle">{}</text>"#,
+        (MARGIN_LEFT + WIDTH - MARGIN_RIGHT) * 0.5,
+        escape(title),
+    ).unwrap();
+    svg
+}
+
+fn end_svg(mut svg: String) -> String {
+    writeln!(svg, "</svg>").unwrap();
+    svg
+}
+
+fn draw_frame(svg: &mut String) {
+    writeln!(
+        svg,
+        r#"<rect x="{MARGIN_LEFT}" y="{MARGIN_TOP}" width="{}" height="{}" fill="none" stroke="black"/>"#,
+        WIDTH - MARGIN_LEFT - MARGIN_RIGHT,
+        HEIGHT - MARGIN_TOP - MARGIN_BOTTOM,
+    ).unwrap();
+}
+
+fn draw_axes(svg: &mut String, x: &Axis, x_map: &Mapping, y: &Axis, y_map: &Mapping) {
+    let bottom = HEIGHT - MARGIN_BOTTOM;
+    for tick in x_map.ticks() {
+        let cx = x_map.map(tick);
+        writeln!(
+            svg,
+            r##"<line x1="{cx:.1}" y1="{MARGIN_TOP}" x2="{cx:.1}" y2="{bottom}" stroke="#e0e0e0"/>"##,
+        ).unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{cx:.1}" y="{:.1}" font-size="11" text-anchor="middle">{}</text>"#,
+            bottom + 16.0,
+            format_tick(tick),
+        ).unwrap();
+    }
+    writeln!(
+        svg,
+        r#"<text x="{:.1}" y="{:.1}" font-size="13" text-anchor="middle">{}</text>"#,
+        (MARGIN_LEFT + WIDTH - MARGIN_RIGHT) * 0.5,
+        bottom + 40.0,
+        escape(&x.label),
+    ).unwrap();
+    draw_y_axis(svg, y, y_map);
+    draw_frame(svg);
+}
+
+fn draw_y_axis(svg: &mut String, y: &Axis, y_map: &Mapping) {
+    let right = WIDTH - MARGIN_RIGHT;
+    for tick in y_map.ticks() {
+        let cy = y_map.map(tick);
+        writeln!(
+            svg,
+            r##"<line x1="{MARGIN_LEFT}" y1="{cy:.1}" x2="{right}" y2="{cy:.1}" stroke="#e0e0e0"/>"##,
+        ).unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" font-size="11" text-anchor="end">{}</text>"#,
+            MARGIN_LEFT - 6.0,
+            cy + 4.0,
+            format_tick(tick),
+        ).unwrap();
+    }
+    let cy = (MARGIN_TOP + HEIGHT - MARGIN_BOTTOM) * 0.5;
+    writeln!(
+        svg,
+        r#"<text x="20" y="{cy:.1}" font-size="13" text-anchor="middle" transform="rotate(-90 20 {cy:.1})">{}</text>"#,
+        escape(&y.label),
+    ).unwrap();
+}
+
+fn draw_legend_entry(svg: &mut String, i: usize, label: &str, color: &str) {
+    let x = WIDTH - MARGIN_RIGHT + 16.0;
+    let y = MARGIN_TOP + 10.0 + 18.0 * i as f64;
+    writeln!(
+        svg,
+        r#"<line x1="{x:.1}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="{color}" stroke-width="2"/>"#,
+        x + 20.0,
+    ).unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{:.1}" y="{:.1}" font-size="11">{}</text>"#,
+        x + 26.0,
+        y + 4.0,
+        escape(label),
+    ).unwrap();
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,372 @@
+//! Live progress reporting.
+//!
+//! If the standard output is a terminal, the progress line is redrawn in
+//! place a few times a second.
+//! Otherwise, e.g., when the output is redirected to a file, a progress line
+//! is logged at a fixed interval.
+//! Progress is logged as events with running metrics in the JSON log format.
+
+use core::fmt::Write as _;
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use flechasdb::db::build::BuildEvent;
+
+use crate::info;
+use crate::logging::{self, Level, LogFormat};
+
+// Interval to redraw the progress line on a terminal.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+// Interval to log a progress line if the output is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Progress of queries with running metrics.
+pub struct QueryProgress {
+    display: Display,
+    num_planned: usize,
+    num_completed: usize,
+    num_failures: usize,
+    start_time: Instant,
+    // number of queries completed before starting; e.g., resumed ones.
+    num_skipped: usize,
+    seconds: Vec<f64>,
+    recall_sum: f64,
+    num_recalls: usize,
+}
+
+impl QueryProgress {
+    /// Starts reporting progress of `num_planned` queries.
+    ///
+    /// `num_skipped` is the number of queries already completed; e.g.,
+    /// those resumed from a checkpoint.
+    /// They count toward the progress, but not toward the throughput or the
+    /// running metrics.
+    pub fn start(num_planned: usize, num_skipped: usize) -> Self {
+        Self {
+            display: Display::new(),
+            num_planned,
+            num_completed: num_skipped,
+            num_failures: 0,
+            start_time: Instant::now(),
+            num_skipped,
+            seconds: Vec::with_capacity(num_planned - num_skipped),
+            recall_sum: 0.0,
+            num_recalls: 0,
+        }
+    }
+
+    /// Adds a completed query.
+    ///
+    /// `recall` is `None` if not evaluated.
+    pub fn add_query(&mut self, seconds: f64, recall: Option<f32>) {
+        self.num_completed += 1;
+        self.seconds.push(seconds);
+        if let Some(recall) = recall {
+            self.recall_sum += recall as f64;
+            self.num_recalls += 1;
+        }
+        self.update(false);
+    }
+
+    /// Adds a failed query.
+    pub fn add_failure(&mut self) {
+        self.num_completed += 1;
+        self.num_failures += 1;
+        self.update(false);
+    }
+
+    /// Clears the progress line so that a message can be logged without
+    /// breaking it.
+    ///
+    /// The progress line is redrawn at the next update.
+    pub fn suspend(&mut self) {
+        self.display.suspend();
+    }
+
+    /// Shows the final progress.
+    pub fn finish(&mut self) {
+        self.update(true);
+        self.display.finish();
+    }
+
+    fn update(&mut self, force: bool) {
+        if self.display.is_due() || force {
+            let snapshot = self.snapshot();
+            let line = snapshot.format_line();
+            match logging::format() {
+                LogFormat::Text => self.display.show(&line),
+                LogFormat::Json => {
+                    info!("query_progress", { progress: snapshot }, "{}", line);
+                    self.display.mark_shown();
+                },
+            }
+        }
+    }
+
+    fn snapshot(&self) -> QueryProgressSnapshot {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let num_run = self.num_completed - self.num_skipped;
+        let qps = if num_run > 0 && elapsed > 0.0 {
+            Some(num_run as f64 / elapsed)
+        } else {
+            None
+        };
+        QueryProgressSnapshot {
+            num_completed: self.num_completed,
+            num_planned: self.num_planned,
+            num_failures: self.num_failures,
+            elapsed_seconds: elapsed,
+            qps,
+            eta_seconds: qps.map(|qps| {
+                (self.num_planned - self.num_completed) as f64 / qps
+            }),
+            mean_seconds: if !self.seconds.is_empty() {
+                Some(self.seconds.iter().sum::<f64>() / self.seconds.len() as f64)
+            } else {
+                None
+            },
+            p99_seconds: if !self.seconds.is_empty() {
+                Some(percentile(&self.seconds, 0.99))
+            } else {
+                None
+            },
+            mean_recall: if self.num_recalls > 0 {
+                Some(self.recall_sum / self.num_recalls as f64)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+// Running metrics of queries at a moment.
+#[derive(Serialize)]
+struct QueryProgressSnapshot {
+    num_completed: usize,
+    num_planned: usize,
+    num_failures: usize,
+    elapsed_seconds: f64,
+    // throughput in queries per second if any query has run.
+    qps: Option<f64>,
+    eta_seconds: Option<f64>,
+    // latency if any query has succeeded.
+    mean_seconds: Option<f64>,
+    p99_seconds: Option<f64>,
+    // if any recall has been evaluated.
+    mean_recall: Option<f64>,
+}
+
+impl QueryProgressSnapshot {
+    fn format_line(&self) -> String {
+        let mut line = String::new();
+        write!(
+            line,
+            "queries {}/{} ({:.1}%)",
+            self.num_completed,
+            self.num_planned,
+            self.num_completed as f64 / self.num_planned.max(1) as f64 * 100.0,
+        ).unwrap();
+        if let (Some(qps), Some(eta)) = (self.qps, self.eta_seconds) {
+            write!(
+                line,
+                " | {:.1} q/s | elapsed {} | ETA {}",
+                qps,
+                format_duration(self.elapsed_seconds),
+                format_duration(eta),
+            ).unwrap();
+        }
+        if let (Some(mean), Some(p99)) = (self.mean_seconds, self.p99_seconds) {
+            write!(
+                line,
+                " | latency mean {:.3} ms p99 {:.3} ms",
+                mean * 1_000.0,
+                p99 * 1_000.0,
+            ).unwrap();
+        }
+        if let Some(recall) = self.mean_recall {
+            write!(line, " | recall {:.1}%", recall * 100.0).unwrap();
+        }
+        if self.num_failures > 0 {
+            write!(line, " | failures {}", self.num_failures).unwrap();
+        }
+        line
+    }
+}
+
+/// Progress of building a database.
+///
+/// Counts the build steps notified by [`BuildEvent`]s; ID assignment,
+/// partitioning, subvector division, and quantization of every division.
+/// The remaining time is estimated from the mean time of quantizations
+/// once any quantization has finished.
+pub struct BuildProgress {
+    num_divisions: usize,
+    num_finished_steps: usize,
+    start_time: Instant,
+    quantization_start: Option<Instant>,
+    quantization_seconds: Vec<f64>,
+}
+
+impl BuildProgress {
+    /// Starts reporting progress of building a database with
+    /// `num_divisions` subvector divisions.
+    pub fn start(num_divisions: usize) -> Self {
+        Self {
+            num_divisions,
+            num_finished_steps: 0,
+            start_time: Instant::now(),
+            quantization_start: None,
+            quantization_seconds: Vec::with_capacity(num_divisions),
+        }
+    }
+
+    /// Reports a build event.
+    pub fn on_event(&mut self, event: BuildEvent) {
+        match event {
+            BuildEvent::StartingQuantization(_) => {
+                self.quantization_start = Some(Instant::now());
+            },
+            BuildEvent::FinishedQuantization(_) => {
+                if let Some(start) = self.quantization_start.take() {
+                    self.quantization_seconds.push(start.elapsed().as_secs_f64());
+                }
+                self.num_finished_steps += 1;
+            },
+            BuildEvent::FinishedIdAssignment
+            | BuildEvent::FinishedPartitioning
+            | BuildEvent::FinishedSubvectorDivision => {
+                self.num_finished_steps += 1;
+            },
+            _ => {},
+        }
+        let seconds = self.start_time.elapsed().as_secs_f64();
+        let mut line = format!(
+            "[{}/{}] {:?} at {:.3} s",
+            self.num_finished_steps,
+            self.num_steps(),
+            event,
+            seconds,
+        );
+        let eta_seconds = if !self.quantization_seconds.is_empty() {
+            let mean = self.quantization_seconds.iter().sum::<f64>()
+                / self.quantization_seconds.len() as f64;
+            let remaining = self.num_divisions
+                .saturating_sub(self.quantization_seconds.len());
+            Some(mean * remaining as f64)
+        } else {
+            None
+        };
+        if let Some(eta_seconds) = eta_seconds {
+            write!(line, " | ETA {}", format_duration(eta_seconds)).unwrap();
+        }
+        info!(
+            "build_event",
+            {
+                event: format!("{:?}", event),
+                num_finished_steps: self.num_finished_steps,
+                num_steps: self.num_steps(),
+                seconds: seconds,
+                eta_seconds: eta_seconds,
+            },
+            "{}",
+            line,
+        );
+    }
+
+    fn num_steps(&self) -> usize {
+        3 + self.num_divisions
+    }
+}
+
+// Output of progress lines.
+struct Display {
+    is_terminal: bool,
+    last_time: Option<Instant>,
+    // whether a progress line is drawn and not terminated on the terminal.
+    is_drawn: bool,
+}
+
+impl Display {
+    fn new() -> Self {
+        // redrawing would garble JSON lines or interleaved debug messages
+        let is_terminal = std::io::stdout().is_terminal()
+            && logging::format() == LogFormat::Text
+            && !logging::is_enabled(Level::Debug);
+        Self {
+            is_terminal,
+            last_time: None,
+            is_drawn: false,
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        let interval = if self.is_terminal {
+            REDRAW_INTERVAL
+        } else {
+            LOG_INTERVAL
+        };
+        self.last_time.is_none_or(|t| t.elapsed() >= interval)
+    }
+
+    fn show(&mut self, line: &str) {
+        if self.is_terminal {
+            // clears the line and redraws it
+            print!("\r\x1b[2K{}", line);
+            std::io::stdout().flush().ok();
+            self.is_drawn = true;
+        } else {
+            println!("{}", line);
+        }
+        self.last_time = Some(Instant::now());
+    }
+
+    // Records that a progress line has been shown without the display.
+    fn mark_shown(&mut self) {
+        self.last_time = Some(Instant::now());
+    }
+
+    fn suspend(&mut self) {
+        if self.is_drawn {
+            print!("\r\x1b[2K");
+            std::io::stdout().flush().ok();
+            self.is_drawn = false;
+            // redraws the progress line at the next update
+            self.last_time = None;
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.is_drawn {
+            println!();
+            self.is_drawn = false;
+        }
+    }
+}
+
+// Returns the `p`-th percentile of given values.
+fn percentile(values: &[f64], p: f64) -> f64 {
+    let mut values = values.to_vec();
+    let i = ((values.len() as f64 * p).ceil() as usize)
+        .clamp(1, values.len()) - 1;
+    let (_, value, _) = values
+        .select_nth_unstable_by(i, |l, r| l.partial_cmp(r).unwrap());
+    *value
+}
+
+// Formats seconds as `1h02m03s`, `2m03s`, or `3s`.
+fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "?".to_string();
+    }
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3_600, seconds % 3_600 / 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,225 @@
+//! Provenance of benchmark results.
+//!
+//! Tells which versions of the benchmark and `flechasdb` produced results on
+//! which machine, from which files, and when, so that results from different
+//! machines and dates can be compared.
+//!
+//! The machine is described from `/proc` on Linux, and is partly unknown on
+//! the other platforms.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+
+/// Provenance of benchmark results.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Provenance {
+    /// Version of the benchmark.
+    pub benchmark_version: String,
+    /// Git commit of the benchmark.
+    ///
+    /// Suffixed with `-dirty` if there were uncommitted changes.
+    pub git_commit: String,
+    /// Version and source of `flechasdb`.
+    pub flechasdb_version: String,
+    /// Build profile of the benchmark; e.g., `release`.
+    pub build_profile: String,
+    /// Machine that ran the benchmark.
+    pub machine: Machine,
+    /// Command-line arguments.
+    pub args: Vec<String>,
+    /// Input files.
+    pub files: Vec<FileDigest>,
+    /// Seed of the random number generator if any.
+    pub seed: Option<u64>,
+    /// When the command started (RFC 3339 in UTC).
+    pub started_at: String,
+    /// When the command finished (RFC 3339 in UTC).
+    pub finished_at: Option<String>,
+}
+
+impl Provenance {
+    /// Starts recording provenance of the running command.
+    pub fn start() -> Self {
+        Self {
+            benchmark_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("FLECHASDB_BENCHMARK_GIT_COMMIT").to_string(),
+            flechasdb_version: env!("FLECHASDB_VERSION").to_string(),
+            build_profile: env!("FLECHASDB_BENCHMARK_PROFILE").to_string(),
+            machine: Machine::describe(),
+            args: std::env::args().collect(),
+            files: Vec::new(),
+            seed: None,
+            started_at: now_rfc3339(),
+            finished_at: None,
+        }
+    }
+
+    /// Adds an input file with its SHA-256 hash.
+    ///
+    /// `role` describes the file; e.g., `dataset`.
+    ///
+    /// As a database header file refers to the other files of the database
+    /// by their hashes, the hash of the header file identifies the entire
+    /// database.
+    pub fn add_file(
+        &mut self,
+        role: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Error> {
+        self.files.push(FileDigest::compute(role, path)?);
+        Ok(())
+    }
+
+    /// Returns the SHA-256 hashes of the input files keyed by role.
+    pub fn file_hashes(&self) -> Vec<(String, String)> {
+        self.files
+            .iter()
+            .map(|file| (file.role.clone(), file.sha256.clone()))
+            .collect()
+    }
+
+    /// Records when the command finished.
+    pub fn finish(&mut self) {
+        self.finished_at = Some(now_rfc3339());
+    }
+}
+
+/// Description of a machine.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Machine {
+    /// CPU model name.
+    pub cpu_model: Option<String>,
+    /// Number of available CPU cores.
+    pub num_cpus: usize,
+    /// Total memory in bytes.
+    pub total_memory_bytes: Option<u64>,
+    /// Operating system.
+    pub os: String,
+    /// Kernel release.
+    pub kernel: Option<String>,
+    /// CPU architecture.
+    pub arch: String,
+}
+
+impl Machine {
+    /// Describes the running machine.
+    pub fn describe() -> Self {
+        let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok();
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok();
+        Self {
+            cpu_model: cpuinfo.as_deref().and_then(|info| info
+                .lines()
+                .find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    if key.trim() == "model name" {
+                        Some(value.trim().to_string())
+                    } else {
+                        None
+                    }
+                })),
+            num_cpus: std::thread::available_parallelism()
+                .map_or(1, |n| n.get()),
+            total_memory_bytes: meminfo.as_deref().and_then(|info| info
+                .lines()
+                .find_map(|line| line.strip_prefix("MemTotal:"))
+                .and_then(|value| value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok())
+                .map(|kb| kb * 1024)),
+            os: std::env::consts::OS.to_string(),
+            kernel: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .map(|release| release.trim().to_string()),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+/// Digest of a file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FileDigest {
+    /// Role of the file; e.g., `dataset`.
+    pub role: String,
+    /// Path to the file.
+    pub path: String,
+    /// Size in bytes.
+    pub bytes: u64,
+    /// SHA-256 hash in hex.
+    pub sha256: String,
+}
+
+impl FileDigest {
+    /// Computes the digest of a given file.
+    pub fn compute(
+        role: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1024 * 1024];
+        let mut bytes = 0u64;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            bytes += n as u64;
+        }
+        Ok(Self {
+            role: role.into(),
+            path: path.to_string_lossy().into_owned(),
+            bytes,
+            sha256: hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        })
+    }
+}
+
+// Formats the current time in RFC 3339 in UTC.
+pub(crate) fn now_rfc3339() -> String {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = elapsed.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+        elapsed.subsec_millis(),
+    )
+}
+
+// Converts days since the UNIX epoch into a date in the Gregorian calendar.
+//
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
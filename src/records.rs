@@ -0,0 +1,156 @@
+//! Per-query records.
+//!
+//! Records of individual queries are streamed to a JSON Lines file for
+//! offline analysis; e.g., to find which queries are hard.
+
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Record of a single query.
+#[derive(Debug, Serialize)]
+pub struct QueryRecord {
+    /// Index of the query vector in the query vector set.
+    pub query_index: usize,
+    /// Datum IDs (vector indices in the dataset) of the results.
+    ///
+    /// `None` if attributes are not resolved.
+    pub ids: Option<Vec<usize>>,
+    /// Squared distances of the results.
+    pub squared_distances: Vec<f32>,
+    /// Vector indices of the true k-nearest neighbors by the flat search.
+    ///
+    /// `None` if attributes are not resolved.
+    pub ground_truth_ids: Option<Vec<usize>>,
+    /// Recall.
+    ///
+    /// `None` if attributes are not resolved.
+    pub recall: Option<f32>,
+    /// k-NN search time in seconds.
+    pub search_seconds: f64,
+    /// Attribute (`datum_id`) resolution time in seconds.
+    ///
+    /// `None` if attributes are not resolved.
+    pub attribute_seconds: Option<f64>,
+    /// Flat query time in seconds.
+    ///
+    /// `None` if attributes are not resolved.
+    pub flat_seconds: Option<f64>,
+}
+
+/// Writer of [`QueryRecord`]s to a JSON Lines file.
+pub struct QueryRecordWriter {
+    writer: BufWriter<File>,
+}
+
+impl QueryRecordWriter {
+    /// Creates a JSON Lines file.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Opens a JSON Lines file to append records of queries resumed from a
+    /// given query index.
+    ///
+    /// The query index is of the query vector; i.e., it includes the warmup
+    /// queries.
+    /// Records of the query index and later, and an incomplete last line are
+    /// truncated.
+    pub fn resume(
+        path: impl AsRef<Path>,
+        next_query_index: usize,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)?;
+        let mut valid_len = 0usize;
+        for line in contents.split_inclusive(|&b| b == b'\n') {
+            if !line.ends_with(b"\n") {
+                break;
+            }
+            let query_index = serde_json::from_slice::<serde_json::Value>(line)
+                .ok()
+                .and_then(|record| record.get("query_index")?.as_u64());
+            match query_index {
+                Some(i) if (i as usize) < next_query_index => {
+                    valid_len += line.len();
+                },
+                _ => break,
+            }
+        }
+        let file = OpenOptions::new().append(true).open(path)?;
+        file.set_len(valid_len as u64)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Writes a record as a line.
+    pub fn write(&mut self, record: &QueryRecord) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|e| Error::InvalidData(format!(
+                "failed to serialize query record: {}",
+                e,
+            )))?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Flushes the records written so far.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(query_index: usize) -> QueryRecord {
+        QueryRecord {
+            query_index,
+            ids: None,
+            squared_distances: vec![1.0],
+            ground_truth_ids: None,
+            recall: None,
+            search_seconds: 0.001,
+            attribute_seconds: None,
+            flat_seconds: None,
+        }
+    }
+
+    #[test]
+    fn resume_after_warmup() {
+        let path = std::env::temp_dir().join(format!(
+            "flechasdb-benchmark-records-warmup-{}.jsonl",
+            std::process::id(),
+        ));
+        // queries 0..3 are warmup and 3..6 are measured
+        let mut writer = QueryRecordWriter::create(&path).unwrap();
+        for query_index in 3..6 {
+            writer.write(&record(query_index)).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        let mut writer = QueryRecordWriter::resume(&path, 5).unwrap();
+        writer.write(&record(5)).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let query_indices: Vec<u64> = contents
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value =
+                    serde_json::from_str(line).unwrap();
+                record["query_index"].as_u64().unwrap()
+            })
+            .collect();
+        assert_eq!(query_indices, vec![3, 4, 5]);
+    }
+}
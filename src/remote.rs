@@ -0,0 +1,310 @@
+//! Simulated remote storage.
+//!
+//! [`SimulatedRemoteFileSystem`] wraps a local file system of `flechasdb` and
+//! behaves like an object storage such as Amazon S3:
+//! - opening a file is a request that takes a random latency and may fail
+//! - reading a file is limited by a bandwidth
+//!
+//! It implements both [`flechasdb::io::FileSystem`] and
+//! [`flechasdb::asyncdb::io::FileSystem`].
+//! The synchronous one blocks the thread, and the asynchronous one sleeps
+//! with [`tokio::time::sleep`].
+
+use async_trait::async_trait;
+use core::future::Future;
+use core::pin::Pin;
+use core::str::FromStr;
+use core::task::{Context, Poll};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::Sleep;
+
+use flechasdb::asyncdb::io::{
+    FileSystem as AsyncFileSystem,
+    HashedFileIn as AsyncHashedFileIn,
+};
+use flechasdb::error::Error as FlechasDBError;
+use flechasdb::io::{FileSystem, HashedFileIn};
+
+use crate::error::Error;
+
+/// Distribution of request latencies.
+///
+/// Parameters are in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LatencyModel {
+    /// Fixed latency.
+    Fixed {
+        /// Latency.
+        latency_ms: f64,
+    },
+    /// Normal distribution truncated at zero.
+    Normal {
+        /// Mean.
+        mean_ms: f64,
+        /// Standard deviation.
+        std_ms: f64,
+    },
+    /// Pareto distribution, which is heavy-tailed.
+    Pareto {
+        /// Minimum latency (scale).
+        min_ms: f64,
+        /// Shape. The smaller, the heavier the tail.
+        shape: f64,
+    },
+}
+
+impl LatencyModel {
+    /// Samples a latency.
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let ms = match *self {
+            LatencyModel::Fixed { latency_ms } => latency_ms,
+            LatencyModel::Normal { mean_ms, std_ms } => {
+                // Box-Muller transform
+                let u1: f64 = 1.0 - rng.gen::<f64>(); // (0, 1]
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * u2).cos();
+                mean_ms + std_ms * z
+            },
+            LatencyModel::Pareto { min_ms, shape } => {
+                let u: f64 = 1.0 - rng.gen::<f64>(); // (0, 1]
+                min_ms / u.powf(1.0 / shape)
+            },
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1_000.0)
+    }
+}
+
+impl FromStr for LatencyModel {
+    type Err = Error;
+
+    /// Parses `fixed:<MS>`, `normal:<MEAN_MS>,<STD_MS>`, or
+    /// `pareto:<MIN_MS>,<SHAPE>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidData(format!("invalid latency: {}", s));
+        let (name, params) = s.split_once(':').ok_or_else(invalid)?;
+        let params = params
+            .split(',')
+            .map(|p| p.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        if params.iter().any(|p| !p.is_finite() || *p < 0.0) {
+            return Err(invalid());
+        }
+        match (name, params.as_slice()) {
+            ("fixed", &[latency_ms]) => Ok(LatencyModel::Fixed { latency_ms }),
+            ("normal", &[mean_ms, std_ms]) => {
+                Ok(LatencyModel::Normal { mean_ms, std_ms })
+            },
+            ("pareto", &[min_ms, shape]) if shape > 0.0 => {
+                Ok(LatencyModel::Pareto { min_ms, shape })
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Behavior of a simulated remote storage.
+#[derive(Clone, Debug)]
+pub struct RemoteProfile {
+    /// Latency of each request (file open).
+    pub latency: Option<LatencyModel>,
+    /// Bandwidth in bytes per second.
+    pub bandwidth: Option<f64>,
+    /// Probability that a request fails.
+    pub error_rate: f64,
+    /// Seed for latencies and errors.
+    pub seed: u64,
+}
+
+/// Simulator of a remote storage.
+///
+/// Shared among [`SimulatedRemoteFileSystem`]s so that reloaded databases see
+/// a single sequence of latencies and errors.
+pub struct RemoteSimulator {
+    profile: RemoteProfile,
+    rng: Mutex<StdRng>,
+}
+
+impl RemoteSimulator {
+    /// Creates a simulator with a given profile.
+    pub fn new(profile: RemoteProfile) -> Self {
+        let rng = Mutex::new(StdRng::seed_from_u64(profile.seed));
+        Self { profile, rng }
+    }
+
+    // Decides the latency of a request and whether it fails.
+    fn request(&self) -> (Duration, bool) {
+        let mut rng = self.rng.lock().unwrap();
+        let latency = self.profile.latency
+            .map_or(Duration::ZERO, |latency| latency.sample(&mut *rng));
+        let fails = self.profile.error_rate > 0.0
+            && rng.gen::<f64>() < self.profile.error_rate;
+        (latency, fails)
+    }
+}
+
+/// File system that simulates a remote storage over another file system.
+pub struct SimulatedRemoteFileSystem<FS> {
+    fs: FS,
+    simulator: Arc<RemoteSimulator>,
+}
+
+impl<FS> SimulatedRemoteFileSystem<FS> {
+    /// Wraps a given file system.
+    pub fn new(fs: FS, simulator: Arc<RemoteSimulator>) -> Self {
+        Self { fs, simulator }
+    }
+
+    fn wrap<F>(&self, file: F) -> ThrottledFileIn<F> {
+        ThrottledFileIn {
+            file,
+            bandwidth: self.simulator.profile.bandwidth,
+            delay: None,
+        }
+    }
+}
+
+impl<FS> FileSystem for SimulatedRemoteFileSystem<FS>
+where
+    FS: FileSystem,
+{
+    type HashedFileOut = FS::HashedFileOut;
+    type HashedFileIn = ThrottledFileIn<FS::HashedFileIn>;
+
+    fn create_hashed_file(&self) -> Result<Self::HashedFileOut, FlechasDBError> {
+        self.fs.create_hashed_file()
+    }
+
+    fn create_hashed_file_in(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<Self::HashedFileOut, FlechasDBError> {
+        self.fs.create_hashed_file_in(path)
+    }
+
+    fn open_hashed_file(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<Self::HashedFileIn, FlechasDBError> {
+        let (latency, fails) = self.simulator.request();
+        std::thread::sleep(latency);
+        if fails {
+            return Err(injected_error(path.as_ref()));
+        }
+        Ok(self.wrap(self.fs.open_hashed_file(path)?))
+    }
+}
+
+#[async_trait]
+impl<FS> AsyncFileSystem for SimulatedRemoteFileSystem<FS>
+where
+    FS: AsyncFileSystem,
+{
+    type HashedFileIn = ThrottledFileIn<FS::HashedFileIn>;
+
+    async fn open_hashed_file(
+        &self,
+        path: impl Into<String> + Send,
+    ) -> Result<Self::HashedFileIn, FlechasDBError> {
+        let path = path.into();
+        let (latency, fails) = self.simulator.request();
+        tokio::time::sleep(latency).await;
+        if fails {
+            return Err(injected_error(&path));
+        }
+        Ok(self.wrap(self.fs.open_hashed_file(path).await?))
+    }
+}
+
+/// File whose reads are limited by a bandwidth.
+///
+/// A synchronous read blocks until the bytes read are transferred at the
+/// bandwidth.
+/// An asynchronous read delays the next read instead, so the transfer of the
+/// last bytes is paid when the end of the file is read.
+pub struct ThrottledFileIn<F> {
+    file: F,
+    bandwidth: Option<f64>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<F> ThrottledFileIn<F> {
+    fn transfer_time(&self, bytes: usize) -> Option<Duration> {
+        self.bandwidth
+            .filter(|_| bytes > 0)
+            .map(|bandwidth| Duration::from_secs_f64(bytes as f64 / bandwidth))
+    }
+}
+
+impl<F> Read for ThrottledFileIn<F>
+where
+    F: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read(buf)?;
+        if let Some(time) = self.transfer_time(n) {
+            std::thread::sleep(time);
+        }
+        Ok(n)
+    }
+}
+
+impl<F> HashedFileIn for ThrottledFileIn<F>
+where
+    F: HashedFileIn,
+{
+    fn verify(self) -> Result<(), FlechasDBError> {
+        self.file.verify()
+    }
+}
+
+impl<F> AsyncRead for ThrottledFileIn<F>
+where
+    F: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if let Some(delay) = self.delay.as_mut() {
+            match delay.as_mut().poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(()) => self.delay = None,
+            }
+        }
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.file).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                let n = buf.filled().len() - filled;
+                if let Some(time) = self.transfer_time(n) {
+                    self.delay = Some(Box::pin(tokio::time::sleep(time)));
+                }
+                Poll::Ready(Ok(()))
+            },
+            poll => poll,
+        }
+    }
+}
+
+#[async_trait]
+impl<F> AsyncHashedFileIn for ThrottledFileIn<F>
+where
+    F: AsyncHashedFileIn,
+{
+    async fn verify(self) -> Result<(), FlechasDBError> {
+        self.file.verify().await
+    }
+}
+
+fn injected_error(path: &str) -> FlechasDBError {
+    FlechasDBError::from(
+        std::io::Error::other(format!("injected error: {}", path)),
+    )
+}
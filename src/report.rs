@@ -0,0 +1,241 @@
+//! Tables of query statistics.
+//!
+//! Renders statistics files saved by `batch` into the tables in the README.
+
+use core::fmt::Write as _;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::stats::{QueryStats, Stats};
+
+/// Reads a statistics file (JSON).
+pub fn read_stats_file(path: impl AsRef<Path>) -> Result<QueryStats, Error> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path)?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|e| Error::InvalidData(format!(
+            "failed to parse stats file {:?}: {}",
+            path,
+            e,
+        )))
+}
+
+/// Row of the query time table.
+#[derive(Debug)]
+pub struct TimeRow {
+    /// `sync`, `async`, or `flat`.
+    pub mode: &'static str,
+    /// Number of partitions searched in.
+    ///
+    /// `None` for the flat query.
+    pub nprobe: Option<usize>,
+    /// Query time in milliseconds.
+    pub milliseconds: Stats<f64>,
+}
+
+/// Row of the recall table.
+#[derive(Debug)]
+pub struct RecallRow {
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Recalls in percent.
+    pub percents: Stats<f64>,
+}
+
+/// Tables of query time and recalls.
+pub struct Report {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Rows of the query time table.
+    ///
+    /// Sorted by `nprobe` where synchronous queries come before asynchronous
+    /// ones, and the flat query comes last.
+    pub time_rows: Vec<TimeRow>,
+    /// Rows of the recall table sorted by `nprobe`.
+    pub recall_rows: Vec<RecallRow>,
+}
+
+impl Report {
+    /// Makes tables from given statistics.
+    ///
+    /// The flat query time is taken from the first statistics that have it,
+    /// because it does not depend on `nprobe`.
+    /// Recalls are taken from synchronous queries if there are both
+    /// synchronous and asynchronous queries with the same `nprobe`, because
+    /// they do not depend on the execution mode.
+    ///
+    /// Fails if `stats` is empty or `k` differs among `stats`.
+    pub fn new(stats: &[QueryStats]) -> Result<Self, Error> {
+        let k = stats
+            .first()
+            .ok_or_else(|| Error::InvalidData(
+                "no statistics to report".to_string(),
+            ))?
+            .k;
+        if let Some(other) = stats.iter().find(|s| s.k != k) {
+            return Err(Error::InvalidData(format!(
+                "k must be the same in all statistics: {} ≠ {}",
+                k,
+                other.k,
+            )));
+        }
+        let mut sorted: Vec<&QueryStats> = stats.iter().collect();
+        sorted.sort_by_key(|s| (s.nprobe, s.r#async));
+        let mut time_rows: Vec<TimeRow> = sorted
+            .iter()
+            .map(|s| TimeRow {
+                mode: if s.r#async { "async" } else { "sync" },
+                nprobe: Some(s.nprobe),
+                milliseconds: scale_stats(&s.seconds, 1_000.0),
+            })
+            .collect();
+        if let Some(flat_seconds) = stats
+            .iter()
+            .find_map(|s| s.flat_seconds.as_ref())
+        {
+            time_rows.push(TimeRow {
+                mode: "flat",
+                nprobe: None,
+                milliseconds: scale_stats(flat_seconds, 1_000.0),
+            });
+        }
+        let mut recall_rows: Vec<RecallRow> = Vec::new();
+        for s in sorted.iter() {
+            if recall_rows.last().is_some_and(|row| row.nprobe == s.nprobe) {
+                continue;
+            }
+            if let Some(recalls) = s.recalls.as_ref() {
+                recall_rows.push(RecallRow {
+                    nprobe: s.nprobe,
+                    percents: scale_stats(
+                        &Stats {
+                            mean: recalls.mean as f64,
+                            std: recalls.std as f64,
+                            median: recalls.median as f64,
+                            min: recalls.min as f64,
+                            max: recalls.max as f64,
+                            q1: recalls.q1 as f64,
+                            q3: recalls.q3 as f64,
+                        },
+                        100.0,
+                    ),
+                });
+            }
+        }
+        Ok(Report { k, time_rows, recall_rows })
+    }
+
+    /// Renders the tables in Markdown.
+    ///
+    /// Query time is rounded to 3 decimal places in milliseconds, and recalls
+    /// are rounded to 1 decimal place in percent.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        writeln!(md, "### Results: query time (ms)").unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| sync/async/flat | nprobe | μ ± σ | med | Q1 | Q3 | min | max |").unwrap();
+        writeln!(md, "| --------------- | ------ | ----- | --- | --- | --- | -- | -- |").unwrap();
+        for row in self.time_rows.iter() {
+            let s = &row.milliseconds;
+            writeln!(
+                md,
+                "| {} | {} | {:.3} ± {:.3} | {:.3} | {:.3} | {:.3} | {:.3} | {:.3} |",
+                row.mode,
+                format_nprobe(row.nprobe),
+                s.mean,
+                s.std,
+                s.median,
+                s.q1,
+                s.q3,
+                s.min,
+                s.max,
+            ).unwrap();
+        }
+        writeln!(md).unwrap();
+        writeln!(md, "### Results: recall (%)").unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| nprobe | μ ± σ | med | Q1 | Q3 | min | max |").unwrap();
+        writeln!(md, "| ------ | ----- | --- | --- | --- | -- | -- |").unwrap();
+        for row in self.recall_rows.iter() {
+            let s = &row.percents;
+            writeln!(
+                md,
+                "| {} | {:.1} ± {:.1} | {:.1} | {:.1} | {:.1} | {:.1} | {:.1} |",
+                row.nprobe,
+                s.mean,
+                s.std,
+                s.median,
+                s.q1,
+                s.q3,
+                s.min,
+                s.max,
+            ).unwrap();
+        }
+        md
+    }
+
+    /// Renders the query time table in CSV.
+    ///
+    /// Rounded in the same way as [`Report::to_markdown`].
+    pub fn time_csv(&self) -> String {
+        let mut csv = String::new();
+        writeln!(csv, "mode,nprobe,mean,std,median,q1,q3,min,max").unwrap();
+        for row in self.time_rows.iter() {
+            let s = &row.milliseconds;
+            writeln!(
+                csv,
+                "{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}",
+                row.mode,
+                row.nprobe.map_or(String::new(), |n| n.to_string()),
+                s.mean,
+                s.std,
+                s.median,
+                s.q1,
+                s.q3,
+                s.min,
+                s.max,
+            ).unwrap();
+        }
+        csv
+    }
+
+    /// Renders the recall table in CSV.
+    ///
+    /// Rounded in the same way as [`Report::to_markdown`].
+    pub fn recall_csv(&self) -> String {
+        let mut csv = String::new();
+        writeln!(csv, "nprobe,mean,std,median,q1,q3,min,max").unwrap();
+        for row in self.recall_rows.iter() {
+            let s = &row.percents;
+            writeln!(
+                csv,
+                "{},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1}",
+                row.nprobe,
+                s.mean,
+                s.std,
+                s.median,
+                s.q1,
+                s.q3,
+                s.min,
+                s.max,
+            ).unwrap();
+        }
+        csv
+    }
+}
+
+fn scale_stats(stats: &Stats<f64>, unit: f64) -> Stats<f64> {
+    Stats {
+        mean: stats.mean * unit,
+        std: stats.std * unit,
+        median: stats.median * unit,
+        min: stats.min * unit,
+        max: stats.max * unit,
+        q1: stats.q1 * unit,
+        q3: stats.q3 * unit,
+    }
+}
+
+fn format_nprobe(nprobe: Option<usize>) -> String {
+    nprobe.map_or("n/a".to_string(), |n| n.to_string())
+}
@@ -0,0 +1,158 @@
+//! Resource usage of the process.
+//!
+//! Linux only; memory usage is read from `/proc/self/status`, and CPU time is
+//! obtained with `getrusage`.
+
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// Memory usage of the process.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct MemoryUsage {
+    /// Resident set size in bytes (`VmRSS`).
+    pub rss_bytes: u64,
+    /// Peak resident set size in bytes (`VmHWM`).
+    pub peak_rss_bytes: u64,
+}
+
+impl MemoryUsage {
+    /// Reads the current memory usage from `/proc/self/status`.
+    pub fn read() -> Result<Self, Error> {
+        let status = std::fs::read_to_string("/proc/self/status")?;
+        Ok(MemoryUsage {
+            rss_bytes: read_status_kb(&status, "VmRSS")? * 1024,
+            peak_rss_bytes: read_status_kb(&status, "VmHWM")? * 1024,
+        })
+    }
+}
+
+// Reads a field in kB from the contents of `/proc/self/status`.
+fn read_status_kb(status: &str, field: &str) -> Result<u64, Error> {
+    status
+        .lines()
+        .find_map(|line| line
+            .strip_prefix(field)
+            .and_then(|rest| rest.strip_prefix(':')))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .ok_or_else(|| Error::InvalidData(format!(
+            "no {} in /proc/self/status",
+            field,
+        )))
+}
+
+/// CPU time consumed by the process.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct CpuTime {
+    /// User CPU time in seconds.
+    pub user_seconds: f64,
+    /// System CPU time in seconds.
+    pub system_seconds: f64,
+}
+
+impl CpuTime {
+    /// Reads the CPU time consumed so far with `getrusage`.
+    pub fn read() -> Result<Self, Error> {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        let ret = unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let usage = unsafe { usage.assume_init() };
+        Ok(CpuTime {
+            user_seconds: timeval_seconds(&usage.ru_utime),
+            system_seconds: timeval_seconds(&usage.ru_stime),
+        })
+    }
+
+    /// Returns the CPU time consumed since an earlier reading.
+    pub fn since(&self, earlier: &CpuTime) -> CpuTime {
+        CpuTime {
+            user_seconds: self.user_seconds - earlier.user_seconds,
+            system_seconds: self.system_seconds - earlier.system_seconds,
+        }
+    }
+}
+
+/// Sample of resource usage.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ResourceSample {
+    /// Elapsed time since the sampler started in seconds.
+    pub seconds: f64,
+    /// Resident set size in bytes.
+    pub rss_bytes: u64,
+    /// CPU time consumed since the sampler started.
+    #[serde(flatten)]
+    pub cpu: CpuTime,
+}
+
+/// Resource usage over a period.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResourceUsage {
+    /// Length of the period in seconds.
+    pub seconds: f64,
+    /// Peak resident set size in bytes over the lifetime of the process
+    /// (`VmHWM`).
+    pub peak_rss_bytes: u64,
+    /// CPU time consumed in the period.
+    pub cpu: CpuTime,
+    /// Samples taken at regular intervals.
+    pub samples: Vec<ResourceSample>,
+}
+
+/// Background sampler of resource usage.
+///
+/// Samples resident set size and CPU time in a separate thread at regular
+/// intervals until stopped.
+pub struct ResourceSampler {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<Result<ResourceUsage, Error>>,
+}
+
+impl ResourceSampler {
+    /// Starts sampling at a given interval.
+    pub fn start(interval: Duration) -> Result<Self, Error> {
+        let start_time = Instant::now();
+        let start_cpu = CpuTime::read()?;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            let mut samples: Vec<ResourceSample> = Vec::new();
+            let sample = |samples: &mut Vec<ResourceSample>| -> Result<(), Error> {
+                samples.push(ResourceSample {
+                    seconds: start_time.elapsed().as_secs_f64(),
+                    rss_bytes: MemoryUsage::read()?.rss_bytes,
+                    cpu: CpuTime::read()?.since(&start_cpu),
+                });
+                Ok(())
+            };
+            sample(&mut samples)?;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                sample(&mut samples)?;
+            }
+            sample(&mut samples)?;
+            Ok(ResourceUsage {
+                seconds: start_time.elapsed().as_secs_f64(),
+                peak_rss_bytes: MemoryUsage::read()?.peak_rss_bytes,
+                cpu: CpuTime::read()?.since(&start_cpu),
+                samples,
+            })
+        });
+        Ok(Self { stop, handle })
+    }
+
+    /// Stops sampling and returns the resource usage.
+    pub fn stop(self) -> Result<ResourceUsage, Error> {
+        // the sampler thread has ended if sending fails
+        let _ = self.stop.send(());
+        self.handle.join().map_err(|_| Error::InvalidData(
+            "resource sampler panicked".to_string(),
+        ))?
+    }
+}
+
+fn timeval_seconds(tv: &libc::timeval) -> f64 {
+    tv.tv_sec as f64 + tv.tv_usec as f64 * 1.0e-6
+}
@@ -0,0 +1,48 @@
+//! Random sampling of vectors.
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use flechasdb::vector::BlockVectorSet;
+
+use crate::error::Error;
+
+/// Randomly samples indices of vectors.
+///
+/// The same `seed` always gives the same indices.
+/// Returned indices are sorted in ascending order.
+/// All the indices are returned if `sample_size` ≥ `num_vectors`.
+pub fn sample_indices(
+    num_vectors: usize,
+    sample_size: usize,
+    seed: u64,
+) -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut indices = rand::seq::index::sample(
+        &mut rng,
+        num_vectors,
+        std::cmp::min(sample_size, num_vectors),
+    ).into_vec();
+    indices.sort_unstable();
+    indices
+}
+
+/// Extracts vectors at given indices into a new vector set.
+pub fn extract_vectors(
+    vs: &BlockVectorSet<f32>,
+    indices: &[usize],
+) -> Result<BlockVectorSet<f32>, Error> {
+    let mut block: Vec<f32> = Vec::with_capacity(
+        vs.vector_size() * indices.len(),
+    );
+    for &i in indices {
+        block.extend_from_slice(vs.get(i));
+    }
+    Ok(BlockVectorSet::chunk(block, vs.vector_size().try_into().unwrap())?)
+}
+
+/// Copies all the vectors into a new vector set.
+pub fn copy_vectors(vs: &BlockVectorSet<f32>) -> Result<BlockVectorSet<f32>, Error> {
+    let indices: Vec<usize> = (0..vs.len()).collect();
+    extract_vectors(vs, &indices)
+}
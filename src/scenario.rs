@@ -0,0 +1,648 @@
+//! Declarative benchmark scenarios.
+//!
+//! A scenario file (TOML) describes datasets, build parameters, and query
+//! settings, and is expanded into a plan of build and batch steps.
+//!
+//! Outputs are laid out in the output folder as follows:
+//! - `databases/{dataset}-{parameters}/`: databases
+//! - `builds/{dataset}-{parameters}.json`: build reports
+//! - `stats/{dataset}-{parameters}-{mode}-k{k}-nprobe{nprobe}.json`:
+//!   statistics of batches, or open-loop reports in the throughput mode
+//! - `results.json`: consolidated results bundle
+//!
+//! Each build report and statistics file has a `.settings` file next to it,
+//! which holds the hash of the settings of the step that produced it.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::attributes::SyntheticAttribute;
+use crate::build::BuildReport;
+use crate::error::Error;
+use crate::openloop::{ArrivalProcess, OpenLoopReport};
+use crate::provenance::Provenance;
+use crate::report::read_stats_file;
+use crate::stats::QueryStats;
+use crate::tune::BuildParameters;
+
+/// Name of the consolidated results bundle in the output folder.
+pub const RESULTS_FILE_NAME: &str = "results.json";
+
+/// Benchmark scenario.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Path to the output folder.
+    ///
+    /// Relative to the scenario file.
+    pub output_path: String,
+    /// Datasets.
+    pub datasets: Vec<DatasetSpec>,
+    /// Build parameter grids.
+    #[serde(default)]
+    pub build: BuildSpec,
+    /// Query settings.
+    #[serde(default)]
+    pub queries: QuerySpec,
+    /// Interval in ms to sample resource usage.
+    #[serde(default = "default_sampling_interval")]
+    pub sampling_interval: u64,
+}
+
+// Default interval in ms to sample resource usage.
+fn default_sampling_interval() -> u64 {
+    1000
+}
+
+/// Dataset in a scenario.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetSpec {
+    /// Name of the dataset used in output file names.
+    pub name: String,
+    /// Path to the dataset (*.fvecs file).
+    ///
+    /// Relative to the scenario file.
+    pub dataset_path: String,
+    /// Path to the query vectorset (*.fvecs file).
+    ///
+    /// Relative to the scenario file.
+    pub queries_path: String,
+}
+
+/// Build parameter grids in a scenario.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildSpec {
+    /// Numbers of partitions.
+    pub num_partitions: Vec<usize>,
+    /// Numbers of subvector divisions.
+    pub num_divisions: Vec<usize>,
+    /// Numbers of clusters (codes).
+    pub num_codes: Vec<usize>,
+    /// Synthetic attributes to assign; `category:<NUM_CATEGORIES>` or
+    /// `timestamp`.
+    pub synthetic: Vec<String>,
+    /// Seed for synthetic attributes.
+    pub seed: u64,
+}
+
+impl Default for BuildSpec {
+    fn default() -> Self {
+        Self {
+            num_partitions: vec![2048],
+            num_divisions: vec![8],
+            num_codes: vec![256],
+            synthetic: Vec::new(),
+            seed: 0,
+        }
+    }
+}
+
+impl BuildSpec {
+    /// Parses the synthetic attributes.
+    pub fn synthetic_attributes(&self) -> Result<Vec<SyntheticAttribute>, Error> {
+        self.synthetic.iter().map(|s| s.parse()).collect()
+    }
+}
+
+/// Query settings in a scenario.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuerySpec {
+    /// Execution modes.
+    pub modes: Vec<QueryMode>,
+    /// Numbers of best matches (k-nearest neighbors) to return.
+    pub k: Vec<usize>,
+    /// Numbers of partitions to search in.
+    pub nprobe: Vec<usize>,
+    /// Limits the number of queries.
+    pub limit: Option<usize>,
+    /// Number of warmup queries excluded from the statistics.
+    pub warmup: usize,
+    /// Skips resolving datum IDs of results.
+    pub skip_attributes: bool,
+    /// Records the duration of each query phase.
+    pub events: bool,
+    /// Counts files opened and bytes read by each query.
+    pub io_stats: bool,
+    /// Keeps the query time and recall of every query.
+    pub keep_samples: bool,
+    /// Open-loop load in the throughput mode.
+    pub throughput: ThroughputSpec,
+}
+
+impl Default for QuerySpec {
+    fn default() -> Self {
+        Self {
+            modes: vec![QueryMode::Sync],
+            k: vec![100],
+            nprobe: vec![10],
+            limit: None,
+            warmup: 0,
+            skip_attributes: false,
+            events: false,
+            io_stats: false,
+            keep_samples: false,
+            throughput: ThroughputSpec::default(),
+        }
+    }
+}
+
+/// Open-loop load in the throughput mode of a scenario.
+///
+/// `limit`, `events`, `io_stats`, and `keep_samples` of the query settings do
+/// not apply to the throughput mode.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThroughputSpec {
+    /// Target arrival rates in queries per second swept in the given order.
+    pub rates: Vec<f64>,
+    /// Arrival process: `poisson` or `constant`.
+    pub arrival: String,
+    /// Duration in seconds to issue queries at each rate.
+    pub duration: f64,
+    /// Maximum number of queries in flight.
+    pub max_in_flight: usize,
+    /// A rate is saturated if the achieved throughput falls below this
+    /// fraction of the offered rate.
+    pub min_throughput_ratio: f64,
+    /// A rate is saturated if the 99th percentile latency exceeds this in ms.
+    pub max_p99: Option<f64>,
+    /// Seed for the arrivals.
+    pub seed: u64,
+}
+
+impl Default for ThroughputSpec {
+    fn default() -> Self {
+        Self {
+            rates: vec![10.0, 20.0, 50.0, 100.0, 200.0, 500.0],
+            arrival: "poisson".to_string(),
+            duration: 10.0,
+            max_in_flight: 1_000,
+            min_throughput_ratio: 0.95,
+            max_p99: None,
+            seed: 0,
+        }
+    }
+}
+
+impl ThroughputSpec {
+    /// Parses the arrival process.
+    pub fn arrival_process(&self) -> Result<ArrivalProcess, Error> {
+        self.arrival.parse()
+    }
+}
+
+/// Execution mode of queries.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryMode {
+    /// Synchronous database.
+    Sync,
+    /// Asynchronous database.
+    Async,
+    /// Open-loop load against the asynchronous database.
+    Throughput,
+}
+
+impl QueryMode {
+    /// Returns the name used in output file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueryMode::Sync => "sync",
+            QueryMode::Async => "async",
+            QueryMode::Throughput => "throughput",
+        }
+    }
+}
+
+impl Scenario {
+    /// Reads a scenario file.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|e| Error::InvalidData(format!(
+                "failed to parse scenario {:?}: {}",
+                path,
+                e,
+            )))
+    }
+
+    /// Expands the scenario into a plan.
+    ///
+    /// `base_path` is the folder that relative paths in the scenario are
+    /// resolved against; i.e., the folder containing the scenario file.
+    pub fn plan(&self, base_path: impl AsRef<Path>) -> Result<Plan, Error> {
+        let base_path = base_path.as_ref();
+        let output_path = base_path.join(&self.output_path);
+        self.validate()?;
+        let grid = BuildParameters::grid(
+            &self.build.num_partitions,
+            &self.build.num_divisions,
+            &self.build.num_codes,
+        );
+        let mut builds: Vec<BuildStep> = Vec::new();
+        let mut batches: Vec<BatchStep> = Vec::new();
+        for dataset in self.datasets.iter() {
+            let dataset_path = base_path.join(&dataset.dataset_path);
+            let queries_path = base_path.join(&dataset.queries_path);
+            for parameters in grid.iter() {
+                let name = format!("{}-{}", dataset.name, parameters.folder_name());
+                let build_settings = hash_settings(&BuildSettings {
+                    dataset_path: &dataset_path,
+                    parameters,
+                    synthetic: &self.build.synthetic,
+                    seed: self.build.seed,
+                })?;
+                for mode in self.queries.modes.iter() {
+                    for &k in self.queries.k.iter() {
+                        for &nprobe in self.queries.nprobe.iter() {
+                            let settings = hash_settings(&BatchSettings {
+                                build: &build_settings,
+                                queries_path: &queries_path,
+                                mode: *mode,
+                                k,
+                                nprobe,
+                                limit: self.queries.limit,
+                                warmup: self.queries.warmup,
+                                skip_attributes: self.queries.skip_attributes,
+                                events: self.queries.events,
+                                io_stats: self.queries.io_stats,
+                                keep_samples: self.queries.keep_samples,
+                                throughput: (*mode == QueryMode::Throughput)
+                                    .then_some(&self.queries.throughput),
+                            })?;
+                            batches.push(BatchStep {
+                                build_index: builds.len(),
+                                mode: *mode,
+                                k,
+                                nprobe,
+                                queries_path: queries_path.clone(),
+                                settings,
+                                stats_path: output_path
+                                    .join("stats")
+                                    .join(format!(
+                                        "{}-{}-k{}-nprobe{}.json",
+                                        name,
+                                        mode.name(),
+                                        k,
+                                        nprobe,
+                                    )),
+                            });
+                        }
+                    }
+                }
+                builds.push(BuildStep {
+                    dataset: dataset.name.clone(),
+                    parameters: *parameters,
+                    dataset_path: dataset_path.clone(),
+                    database_path: output_path.join("databases").join(&name),
+                    report_path: output_path
+                        .join("builds")
+                        .join(format!("{}.json", name)),
+                    settings: build_settings,
+                });
+            }
+        }
+        Ok(Plan {
+            results_path: output_path.join(RESULTS_FILE_NAME),
+            output_path,
+            builds,
+            batches,
+        })
+    }
+
+    // Fails if the scenario is empty or has duplicate dataset names.
+    fn validate(&self) -> Result<(), Error> {
+        if self.datasets.is_empty() {
+            return Err(Error::InvalidData("no datasets in scenario".to_string()));
+        }
+        for (i, dataset) in self.datasets.iter().enumerate() {
+            if self.datasets[..i].iter().any(|d| d.name == dataset.name) {
+                return Err(Error::InvalidData(format!(
+                    "duplicate dataset name: {}",
+                    dataset.name,
+                )));
+            }
+        }
+        let empty = |name: &str, len: usize| if len == 0 {
+            Err(Error::InvalidData(format!("empty {} in scenario", name)))
+        } else {
+            Ok(())
+        };
+        empty("build.num_partitions", self.build.num_partitions.len())?;
+        empty("build.num_divisions", self.build.num_divisions.len())?;
+        empty("build.num_codes", self.build.num_codes.len())?;
+        empty("queries.modes", self.queries.modes.len())?;
+        empty("queries.k", self.queries.k.len())?;
+        empty("queries.nprobe", self.queries.nprobe.len())?;
+        empty("queries.throughput.rates", self.queries.throughput.rates.len())?;
+        self.build.synthetic_attributes()?;
+        self.queries.throughput.arrival_process()?;
+        Ok(())
+    }
+}
+
+/// Plan of a scenario.
+#[derive(Debug)]
+pub struct Plan {
+    /// Path to the output folder.
+    pub output_path: PathBuf,
+    /// Path to the consolidated results bundle.
+    pub results_path: PathBuf,
+    /// Steps to build databases.
+    pub builds: Vec<BuildStep>,
+    /// Steps to query databases.
+    pub batches: Vec<BatchStep>,
+}
+
+/// Step to build a database.
+#[derive(Debug)]
+pub struct BuildStep {
+    /// Name of the dataset.
+    pub dataset: String,
+    /// Build parameters.
+    pub parameters: BuildParameters,
+    /// Path to the dataset.
+    pub dataset_path: PathBuf,
+    /// Path to the folder to save the database.
+    pub database_path: PathBuf,
+    /// Path to the build report.
+    pub report_path: PathBuf,
+    /// Hash of the settings of the step.
+    pub settings: String,
+}
+
+impl BuildStep {
+    /// Whether the build report is newer than the dataset and was produced
+    /// with the same settings.
+    pub fn is_up_to_date(&self) -> Result<bool, Error> {
+        Ok(is_up_to_date(&self.report_path, &[&self.dataset_path])?
+            && has_settings(&self.report_path, &self.settings)?)
+    }
+
+    /// Removes the settings hash of the build report.
+    ///
+    /// Call before the step so that an unfinished step is never up to date.
+    pub fn clear_settings(&self) -> Result<(), Error> {
+        clear_settings(&self.report_path)
+    }
+
+    /// Saves the settings hash next to the build report.
+    ///
+    /// Call after the step is finished.
+    pub fn save_settings(&self) -> Result<(), Error> {
+        save_settings(&self.report_path, &self.settings)
+    }
+}
+
+/// Step to query a database with every query vector.
+#[derive(Debug)]
+pub struct BatchStep {
+    /// Index of the build step of the database.
+    pub build_index: usize,
+    /// Execution mode.
+    pub mode: QueryMode,
+    /// Number of best matches (k-nearest neighbors) to return.
+    pub k: usize,
+    /// Number of partitions to search in.
+    pub nprobe: usize,
+    /// Path to the query vectorset.
+    pub queries_path: PathBuf,
+    /// Hash of the settings of the step.
+    ///
+    /// Includes the settings of the build step.
+    pub settings: String,
+    /// Path to the statistics.
+    ///
+    /// Open-loop report in the throughput mode.
+    pub stats_path: PathBuf,
+}
+
+impl BatchStep {
+    /// Whether the statistics are newer than the query vectorset and the
+    /// database built by a given step, were produced with the same settings,
+    /// and are not partial.
+    pub fn is_up_to_date(&self, build: &BuildStep) -> Result<bool, Error> {
+        let is_newer = is_up_to_date(
+            &self.stats_path,
+            &[&self.queries_path, &build.dataset_path, &build.report_path],
+        )?;
+        if !is_newer || !has_settings(&self.stats_path, &self.settings)? {
+            return Ok(false);
+        }
+        match self.mode {
+            // an interrupted sweep never saves the settings
+            QueryMode::Throughput => Ok(true),
+            _ => Ok(read_stats_file(&self.stats_path)?.partial.is_none()),
+        }
+    }
+
+    /// Removes the settings hash of the statistics.
+    ///
+    /// Call before the step so that an unfinished step is never up to date.
+    pub fn clear_settings(&self) -> Result<(), Error> {
+        clear_settings(&self.stats_path)
+    }
+
+    /// Saves the settings hash next to the statistics.
+    ///
+    /// Call after the step is finished.
+    pub fn save_settings(&self) -> Result<(), Error> {
+        save_settings(&self.stats_path, &self.settings)
+    }
+}
+
+// Settings that a build report depends on.
+#[derive(Serialize)]
+struct BuildSettings<'a> {
+    dataset_path: &'a Path,
+    parameters: &'a BuildParameters,
+    synthetic: &'a [String],
+    seed: u64,
+}
+
+// Settings that statistics depend on.
+#[derive(Serialize)]
+struct BatchSettings<'a> {
+    build: &'a str,
+    queries_path: &'a Path,
+    mode: QueryMode,
+    k: usize,
+    nprobe: usize,
+    limit: Option<usize>,
+    warmup: usize,
+    skip_attributes: bool,
+    events: bool,
+    io_stats: bool,
+    keep_samples: bool,
+    throughput: Option<&'a ThroughputSpec>,
+}
+
+// Hashes settings into a SHA-256 hash in hex.
+fn hash_settings(settings: &impl Serialize) -> Result<String, Error> {
+    let json = serde_json::to_vec(settings)
+        .map_err(|e| Error::InvalidData(format!(
+            "failed to serialize settings: {}",
+            e,
+        )))?;
+    Ok(Sha256::digest(&json)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+// Path to the settings hash of a given output.
+fn settings_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("settings")
+}
+
+// Whether the settings hash of a given output matches.
+fn has_settings(output_path: &Path, settings: &str) -> Result<bool, Error> {
+    match std::fs::read_to_string(settings_path(output_path)) {
+        Ok(saved) => Ok(saved.trim() == settings),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Removes the settings hash of a given output if any.
+fn clear_settings(output_path: &Path) -> Result<(), Error> {
+    match std::fs::remove_file(settings_path(output_path)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Saves the settings hash of a given output.
+fn save_settings(output_path: &Path, settings: &str) -> Result<(), Error> {
+    std::fs::write(settings_path(output_path), settings)?;
+    Ok(())
+}
+
+/// Whether an output file exists and is newer than all the inputs.
+///
+/// Fails if any input does not exist.
+pub fn is_up_to_date(
+    output_path: impl AsRef<Path>,
+    input_paths: &[impl AsRef<Path>],
+) -> Result<bool, Error> {
+    let output_time = match std::fs::metadata(output_path) {
+        Ok(metadata) => metadata.modified()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(false);
+        },
+        Err(e) => return Err(e.into()),
+    };
+    let mut latest_input_time = SystemTime::UNIX_EPOCH;
+    for input_path in input_paths {
+        latest_input_time = latest_input_time
+            .max(std::fs::metadata(input_path)?.modified()?);
+    }
+    Ok(output_time >= latest_input_time)
+}
+
+/// Consolidated results of a scenario.
+#[derive(Debug, Serialize)]
+pub struct ResultsBundle {
+    /// Scenario.
+    pub scenario: Scenario,
+    /// Builds.
+    pub builds: Vec<BuildResult>,
+    /// Batches.
+    pub batches: Vec<BatchResult>,
+    /// Provenance of the run.
+    pub provenance: Provenance,
+}
+
+/// Result of a build step.
+#[derive(Debug, Serialize)]
+pub struct BuildResult {
+    /// Name of the dataset.
+    pub dataset: String,
+    /// Build report.
+    pub report: BuildReport,
+}
+
+/// Result of a batch step.
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    /// Name of the dataset.
+    pub dataset: String,
+    /// Build parameters of the database.
+    #[serde(flatten)]
+    pub parameters: BuildParameters,
+    /// Execution mode.
+    pub mode: QueryMode,
+    /// Statistics.
+    ///
+    /// `None` in the throughput mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<QueryStats>,
+    /// Open-loop report.
+    ///
+    /// `None` unless in the throughput mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_loop: Option<OpenLoopReport>,
+}
+
+impl ResultsBundle {
+    /// Collects the outputs of all the steps in a plan.
+    pub fn collect(
+        scenario: Scenario,
+        plan: &Plan,
+        provenance: Provenance,
+    ) -> Result<Self, Error> {
+        let builds = plan.builds
+            .iter()
+            .map(|build| Ok(BuildResult {
+                dataset: build.dataset.clone(),
+                report: read_json(&build.report_path)?,
+            }))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let batches = plan.batches
+            .iter()
+            .map(|batch| {
+                let build = &plan.builds[batch.build_index];
+                let (stats, open_loop) = match batch.mode {
+                    QueryMode::Throughput => {
+                        (None, Some(read_json(&batch.stats_path)?))
+                    },
+                    _ => (Some(read_json(&batch.stats_path)?), None),
+                };
+                Ok(BatchResult {
+                    dataset: build.dataset.clone(),
+                    parameters: build.parameters,
+                    mode: batch.mode,
+                    stats,
+                    open_loop,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            scenario,
+            builds,
+            batches,
+            provenance,
+        })
+    }
+}
+
+// Reads a JSON file.
+fn read_json<T>(path: &Path) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let file = std::fs::File::open(path)?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|e| Error::InvalidData(format!(
+            "failed to parse JSON file {:?}: {}",
+            path,
+            e,
+        )))
+}
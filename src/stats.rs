@@ -0,0 +1,476 @@
+//! Statistics on queries.
+
+use core::ops::{AddAssign, Div, Mul, Sub};
+use serde::{Deserialize, Serialize};
+
+use flechasdb::linalg::{dot, sum};
+use flechasdb::numbers::{FromAs, Sqrt, Zero};
+
+use crate::error::Error;
+use crate::events::{PhaseStats, PhaseStatsRecorder};
+use crate::io::{IoSnapshot, IoStats, IoStatsRecorder};
+use crate::provenance::Provenance;
+use crate::resources::ResourceUsage;
+
+/// Recorder of statistics on queries.
+pub struct QueryStatsRecorder {
+    k: usize,
+    nprobe: usize,
+    seconds: Vec<f64>,
+    search_seconds: Vec<f64>,
+    attribute_seconds: Vec<f64>,
+    flat_seconds: Vec<f64>,
+    recalls: Vec<f32>,
+    num_failures: usize,
+    phases: Option<PhaseStatsRecorder>,
+    attribute_lookups: Option<PhaseStatsRecorder>,
+    io: Option<IoStatsRecorder>,
+    // query time of first-touch and steady-state queries.
+    touch: Option<(Vec<f64>, Vec<f64>)>,
+    // each kind of workload queries in order of appearance.
+    workloads: Option<Vec<WorkloadRecorder>>,
+    keeps_samples: bool,
+}
+
+impl QueryStatsRecorder {
+    /// Creates a recorder for given `k` and `nprobe`.
+    pub fn new(k: usize, nprobe: usize) -> Self {
+        Self {
+            k,
+            nprobe,
+            seconds: Vec::with_capacity(10_000),
+            search_seconds: Vec::with_capacity(10_000),
+            attribute_seconds: Vec::with_capacity(10_000),
+            flat_seconds: Vec::with_capacity(10_000),
+            recalls: Vec::with_capacity(10_000),
+            num_failures: 0,
+            phases: None,
+            attribute_lookups: None,
+            io: None,
+            touch: None,
+            workloads: None,
+            keeps_samples: false,
+        }
+    }
+
+    /// Keeps raw per-query samples in the statistics.
+    ///
+    /// See [`QueryStats::samples`].
+    pub fn keep_samples(&mut self) {
+        self.keeps_samples = true;
+    }
+
+    /// Adds a record of a single query.
+    ///
+    /// The query time is the sum of `search_seconds` and
+    /// `attribute_seconds`.
+    pub fn add_record(
+        &mut self,
+        search_seconds: f64,
+        attribute_seconds: f64,
+        flat_seconds: f64,
+        recall: f32,
+    ) {
+        self.seconds.push(search_seconds + attribute_seconds);
+        self.search_seconds.push(search_seconds);
+        self.attribute_seconds.push(attribute_seconds);
+        self.flat_seconds.push(flat_seconds);
+        self.recalls.push(recall);
+    }
+
+    /// Adds a record of a single query whose attributes are not resolved.
+    ///
+    /// Neither attribute time, flat query time, nor recall is recorded.
+    pub fn add_search_record(&mut self, search_seconds: f64) {
+        self.seconds.push(search_seconds);
+        self.search_seconds.push(search_seconds);
+    }
+
+    /// Counts a failed query.
+    pub fn add_failure(&mut self) {
+        self.num_failures += 1;
+    }
+
+    /// Adds durations of query phases of a single query.
+    ///
+    /// See [`crate::events`].
+    pub fn add_phases(&mut self, durations: Vec<(String, f64)>) {
+        self.phases
+            .get_or_insert_with(PhaseStatsRecorder::new)
+            .add_durations(durations);
+    }
+
+    /// Adds the lookup time of each attribute of a single query.
+    ///
+    /// Pairs of attribute name and time to look up the attribute of all the
+    /// results in seconds.
+    pub fn add_attribute_lookups(&mut self, lookups: Vec<(String, f64)>) {
+        self.attribute_lookups
+            .get_or_insert_with(PhaseStatsRecorder::new)
+            .add_durations(lookups);
+    }
+
+    /// Adds I/O operations of a single query.
+    pub fn add_io(&mut self, io: IoSnapshot) {
+        self.io
+            .get_or_insert_with(IoStatsRecorder::new)
+            .add_record(io);
+    }
+
+    /// Adds the query time of a single query classified as first-touch or
+    /// steady-state.
+    ///
+    /// A first-touch query is one that opened files.
+    pub fn add_touch(&mut self, first_touch: bool, seconds: f64) {
+        let (first_touch_seconds, steady_seconds) = self.touch
+            .get_or_insert_with(|| (Vec::new(), Vec::new()));
+        if first_touch {
+            first_touch_seconds.push(seconds);
+        } else {
+            steady_seconds.push(seconds);
+        }
+    }
+
+    /// Adds the query time and recall of a single query of a given kind in a
+    /// workload.
+    ///
+    /// `recall` is `None` if not evaluated.
+    pub fn add_workload(
+        &mut self,
+        kind: &str,
+        seconds: f64,
+        recall: Option<f32>,
+    ) {
+        let workloads = self.workloads.get_or_insert_with(Vec::new);
+        let i = match workloads.iter().position(|w| w.kind == kind) {
+            Some(i) => i,
+            None => {
+                workloads.push(WorkloadRecorder {
+                    kind: kind.to_string(),
+                    seconds: Vec::new(),
+                    recalls: Vec::new(),
+                });
+                workloads.len() - 1
+            },
+        };
+        let workload = &mut workloads[i];
+        workload.seconds.push(seconds);
+        if let Some(recall) = recall {
+            workload.recalls.push(recall);
+        }
+    }
+
+    /// Finishes recording and computes the statistics if any query
+    /// succeeded.
+    pub fn finish_if_any(self) -> Option<QueryStats> {
+        self.finish().ok()
+    }
+
+    /// Finishes recording and computes the statistics.
+    ///
+    /// Fails if no query succeeded.
+    pub fn finish(self) -> Result<QueryStats, Error> {
+        if self.seconds.is_empty() {
+            return Err(Error::InvalidData("no query succeeded".to_string()));
+        }
+        let samples = if self.keeps_samples {
+            Some(QuerySamples {
+                seconds: self.seconds.clone(),
+                search_seconds: self.search_seconds.clone(),
+                recalls: self.recalls.clone(),
+            })
+        } else {
+            None
+        };
+        Ok(QueryStats {
+            k: self.k,
+            nprobe: self.nprobe,
+            r#async: false,
+            num_queries: self.seconds.len(),
+            num_failures: self.num_failures,
+            partial: None,
+            query_range: None,
+            seconds: Stats::compute(self.seconds),
+            search_seconds: Stats::compute(self.search_seconds),
+            attribute_seconds: Stats::compute_if_any(self.attribute_seconds),
+            flat_seconds: Stats::compute_if_any(self.flat_seconds),
+            recalls: Stats::compute_if_any(self.recalls),
+            phases: self.phases.map(|phases| phases.finish()).transpose()?,
+            attribute_lookups: self.attribute_lookups
+                .map(|lookups| lookups.finish())
+                .transpose()?,
+            io: self.io.map(|io| io.finish()).transpose()?,
+            touch: self.touch.map(|(first_touch_seconds, steady_seconds)| {
+                TouchStats {
+                    num_first_touch: first_touch_seconds.len(),
+                    first_touch_seconds: Stats::compute_if_any(first_touch_seconds),
+                    num_steady: steady_seconds.len(),
+                    steady_seconds: Stats::compute_if_any(steady_seconds),
+                }
+            }),
+            workloads: self.workloads.map(|workloads| {
+                workloads
+                    .into_iter()
+                    .map(|workload| WorkloadStats {
+                        kind: workload.kind,
+                        num_queries: workload.seconds.len(),
+                        seconds: Stats::compute(workload.seconds),
+                        recalls: Stats::compute_if_any(workload.recalls),
+                    })
+                    .collect()
+            }),
+            resources: None,
+            samples,
+            provenance: None,
+        })
+    }
+}
+
+// Recorder of a single kind of workload queries.
+struct WorkloadRecorder {
+    kind: String,
+    seconds: Vec<f64>,
+    recalls: Vec<f32>,
+}
+
+/// Statistics on queries.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryStats {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Whether queries are asynchronously executed.
+    ///
+    /// Set by the caller.
+    #[serde(default)]
+    pub r#async: bool,
+    /// Number of queries.
+    ///
+    /// Failed queries are not included.
+    pub num_queries: usize,
+    /// Number of failed queries.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub num_failures: usize,
+    /// Progress of the batch if interrupted before running all the queries.
+    ///
+    /// Set by the caller; `None` if the batch completed.
+    /// The statistics cover only the completed queries if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial: Option<Partial>,
+    /// Range of the query vectors planned to be measured.
+    ///
+    /// Set by the caller; `None` if not recorded.
+    /// Warmup queries are taken from the query vectors before the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_range: Option<QueryRange>,
+    /// Query time in seconds.
+    ///
+    /// Sum of the k-NN search time and the attribute resolution time.
+    pub seconds: Stats<f64>,
+    /// k-NN search time in seconds.
+    pub search_seconds: Stats<f64>,
+    /// Attribute (`datum_id`) resolution time in seconds.
+    ///
+    /// `None` if attributes are not resolved.
+    pub attribute_seconds: Option<Stats<f64>>,
+    /// Flat query time in seconds.
+    ///
+    /// `None` if attributes are not resolved.
+    pub flat_seconds: Option<Stats<f64>>,
+    /// Recalls.
+    ///
+    /// `None` if attributes are not resolved.
+    pub recalls: Option<Stats<f32>>,
+    /// Statistics on query phases.
+    ///
+    /// `None` if query events are not recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phases: Option<Vec<PhaseStats>>,
+    /// Statistics on the time to look up each attribute of all the results
+    /// per query.
+    ///
+    /// `name` of each item is the attribute name.
+    /// `None` if no attribute other than `datum_id` is looked up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribute_lookups: Option<Vec<PhaseStats>>,
+    /// Statistics on I/O operations per query.
+    ///
+    /// `None` if I/O operations are not counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io: Option<IoStats>,
+    /// Statistics on first-touch and steady-state queries.
+    ///
+    /// `None` if queries are not classified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub touch: Option<TouchStats>,
+    /// Statistics on each kind of queries in a workload.
+    ///
+    /// `None` unless queries are generated from a workload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workloads: Option<Vec<WorkloadStats>>,
+    /// Resource usage during the batch.
+    ///
+    /// Set by the caller; `None` if not sampled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceUsage>,
+    /// Raw per-query samples.
+    ///
+    /// `None` unless kept by [`QueryStatsRecorder::keep_samples`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub samples: Option<QuerySamples>,
+    /// Provenance of the results.
+    ///
+    /// Set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// Progress of an interrupted batch.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Partial {
+    /// Number of completed queries including failed ones.
+    pub num_completed: usize,
+    /// Number of planned queries.
+    pub num_planned: usize,
+}
+
+/// Range of query vectors.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryRange {
+    /// Index of the first query vector.
+    pub start: usize,
+    /// Index next to the last query vector.
+    pub end: usize,
+}
+
+/// Raw per-query samples.
+///
+/// Samples are in the order of queries.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QuerySamples {
+    /// Query time in seconds.
+    pub seconds: Vec<f64>,
+    /// k-NN search time in seconds.
+    pub search_seconds: Vec<f64>,
+    /// Recalls.
+    ///
+    /// Empty if attributes are not resolved.
+    pub recalls: Vec<f32>,
+}
+
+/// Statistics on first-touch and steady-state queries.
+///
+/// A first-touch query is one that opened files; i.e., loaded partitions,
+/// codebooks, or attributes that no previous query had loaded.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TouchStats {
+    /// Number of first-touch queries.
+    pub num_first_touch: usize,
+    /// Query time of first-touch queries in seconds.
+    pub first_touch_seconds: Option<Stats<f64>>,
+    /// Number of steady-state queries.
+    pub num_steady: usize,
+    /// Query time of steady-state queries in seconds.
+    pub steady_seconds: Option<Stats<f64>>,
+}
+
+/// Statistics on a kind of queries in a workload.
+///
+/// See [`crate::workload`].
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WorkloadStats {
+    /// Kind of queries.
+    pub kind: String,
+    /// Number of queries.
+    pub num_queries: usize,
+    /// Query time in seconds.
+    pub seconds: Stats<f64>,
+    /// Recalls.
+    ///
+    /// `None` if attributes are not resolved.
+    pub recalls: Option<Stats<f32>>,
+}
+
+/// Generic statistics.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Stats<T> {
+    /// Mean.
+    pub mean: T,
+    /// Standard deviation.
+    pub std: T,
+    /// Median.
+    pub median: T,
+    /// Minimum.
+    pub min: T,
+    /// Maximum.
+    pub max: T,
+    /// 1st quartile.
+    pub q1: T,
+    /// 3rd quartile.
+    pub q3: T,
+}
+
+impl<T> Stats<T> {
+    /// Computes the statistics over given records.
+    ///
+    /// The standard deviation is zero for a single record.
+    /// Panics if `records` is empty.
+    pub fn compute(mut records: Vec<T>) -> Stats<T>
+    where
+        T: FromAs<usize>
+            + Sqrt
+            + Zero
+            + AddAssign
+            + Div<Output = T>
+            + Mul<Output = T>
+            + Sub<Output = T>
+            + Copy
+            + PartialOrd,
+    {
+        records.sort_by(|l, r| l.partial_cmp(r).unwrap());
+        let sum = sum(&records);
+        let mean = sum / T::from_as(records.len());
+        let squared_sum = dot(&records, &records);
+        let var = if records.len() > 1 {
+            (squared_sum - T::from_as(records.len()) * mean * mean) / T::from_as(records.len() - 1)
+        } else {
+            T::zero()
+        };
+        // rounding errors may make the variance of equal records negative
+        let var = if var < T::zero() { T::zero() } else { var };
+        Stats {
+            mean: sum / T::from_as(records.len()),
+            std: var.sqrt(),
+            median: records[records.len() / 2],
+            min: records[0],
+            max: records[records.len() - 1],
+            q1: records[records.len() / 4],
+            q3: records[records.len() * 3 / 4],
+        }
+    }
+
+    /// Computes the statistics over given records if there is any.
+    pub fn compute_if_any(records: Vec<T>) -> Option<Stats<T>>
+    where
+        T: FromAs<usize>
+            + Sqrt
+            + Zero
+            + AddAssign
+            + Div<Output = T>
+            + Mul<Output = T>
+            + Sub<Output = T>
+            + Copy
+            + PartialOrd,
+    {
+        if records.is_empty() {
+            None
+        } else {
+            Some(Stats::compute(records))
+        }
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
@@ -0,0 +1,209 @@
+//! Parameter grid search.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+use crate::provenance::Provenance;
+use crate::stats::QueryStats;
+
+/// Parameters to build a database.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct BuildParameters {
+    /// Number of partitions.
+    pub num_partitions: usize,
+    /// Number of subvector divisions.
+    pub num_divisions: usize,
+    /// Number of clusters (codes).
+    pub num_codes: usize,
+}
+
+impl BuildParameters {
+    /// Enumerates all the combinations of given parameter grids.
+    pub fn grid(
+        num_partitions: &[usize],
+        num_divisions: &[usize],
+        num_codes: &[usize],
+    ) -> Vec<Self> {
+        let mut grid = Vec::with_capacity(
+            num_partitions.len() * num_divisions.len() * num_codes.len(),
+        );
+        for &p in num_partitions {
+            for &d in num_divisions {
+                for &c in num_codes {
+                    grid.push(BuildParameters {
+                        num_partitions: p,
+                        num_divisions: d,
+                        num_codes: c,
+                    });
+                }
+            }
+        }
+        grid
+    }
+
+    /// Returns the name of the folder to save the database.
+    pub fn folder_name(&self) -> String {
+        format!(
+            "p{}-d{}-c{}",
+            self.num_partitions,
+            self.num_divisions,
+            self.num_codes,
+        )
+    }
+}
+
+/// Result of a single database configuration.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConfigurationResult {
+    /// Parameters.
+    #[serde(flatten)]
+    pub parameters: BuildParameters,
+    /// Time to build the database in seconds.
+    pub build_seconds: f64,
+    /// Time to save the database in seconds.
+    pub save_seconds: f64,
+    /// Disk usage of the database in bytes.
+    pub disk_bytes: u64,
+    /// Statistics for each nprobe.
+    pub query_stats: Vec<QueryStats>,
+}
+
+/// Point on the recall-vs-latency Pareto front.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ParetoPoint {
+    /// Parameters.
+    #[serde(flatten)]
+    pub parameters: BuildParameters,
+    /// Number of partitions searched in.
+    pub nprobe: usize,
+    /// Mean query time in seconds.
+    pub mean_seconds: f64,
+    /// Mean recall.
+    pub mean_recall: f32,
+}
+
+/// Report of a parameter grid search.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TuneReport {
+    /// Number of best matches (k-nearest neighbors).
+    pub k: usize,
+    /// Number of vectors used to build each database.
+    pub train_size: usize,
+    /// Number of queries per nprobe.
+    pub num_queries: usize,
+    /// Seed of the training subset sampling.
+    pub seed: u64,
+    /// Results of the configurations.
+    pub configurations: Vec<ConfigurationResult>,
+    /// Pareto front sorted by mean query time.
+    pub pareto_front: Vec<ParetoPoint>,
+    /// Provenance of the results.
+    ///
+    /// Set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+impl TuneReport {
+    /// Creates a report and computes the Pareto front.
+    pub fn new(
+        k: usize,
+        train_size: usize,
+        num_queries: usize,
+        seed: u64,
+        configurations: Vec<ConfigurationResult>,
+    ) -> Self {
+        let pareto_front = pareto_front(&configurations);
+        Self {
+            k,
+            train_size,
+            num_queries,
+            seed,
+            configurations,
+            pareto_front,
+            provenance: None,
+        }
+    }
+
+    /// Renders the report as Markdown tables.
+    pub fn to_markdown(&self) -> String {
+        let time_unit: f64 = 1_000.0; // s → ms
+        let mut md = String::new();
+        writeln!(md, "### Configurations").unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| partitions | divisions | codes | build (s) | save (s) | disk (MB) |").unwrap();
+        writeln!(md, "| ---------- | --------- | ----- | --------- | -------- | --------- |").unwrap();
+        for config in self.configurations.iter() {
+            writeln!(
+                md,
+                "| {} | {} | {} | {:.1} | {:.1} | {:.1} |",
+                config.parameters.num_partitions,
+                config.parameters.num_divisions,
+                config.parameters.num_codes,
+                config.build_seconds,
+                config.save_seconds,
+                config.disk_bytes as f64 / 1_000_000.0,
+            ).unwrap();
+        }
+        writeln!(md).unwrap();
+        writeln!(md, "### Queries (k={}, {} queries)", self.k, self.num_queries).unwrap();
+        writeln!(md).unwrap();
+        writeln!(md, "| partitions | divisions | codes | nprobe | time (ms) μ ± σ | recall (%) μ ± σ | Pareto |").unwrap();
+        writeln!(md, "| ---------- | --------- | ----- | ------ | --------------- | ---------------- | ------ |").unwrap();
+        for config in self.configurations.iter() {
+            for stats in config.query_stats.iter() {
+                let is_pareto = self.pareto_front.iter().any(|point| {
+                    point.parameters.folder_name() == config.parameters.folder_name()
+                        && point.nprobe == stats.nprobe
+                });
+                writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {:.3} ± {:.3} | {:.1} ± {:.1} | {} |",
+                    config.parameters.num_partitions,
+                    config.parameters.num_divisions,
+                    config.parameters.num_codes,
+                    stats.nprobe,
+                    stats.seconds.mean * time_unit,
+                    stats.seconds.std * time_unit,
+                    stats.recalls.as_ref().map_or(f32::NAN, |r| r.mean) * 100.0,
+                    stats.recalls.as_ref().map_or(f32::NAN, |r| r.std) * 100.0,
+                    if is_pareto { "✓" } else { "" },
+                ).unwrap();
+            }
+        }
+        md
+    }
+}
+
+/// Computes the recall-vs-latency Pareto front.
+///
+/// Statistics without recalls are ignored.
+///
+/// A point is on the front if no other point has a higher or equal mean
+/// recall with a shorter or equal mean query time, and is strictly better in
+/// either.
+pub fn pareto_front(configurations: &[ConfigurationResult]) -> Vec<ParetoPoint> {
+    let points: Vec<ParetoPoint> = configurations
+        .iter()
+        .flat_map(|config| config.query_stats.iter().filter_map(|stats| {
+            stats.recalls.as_ref().map(|recalls| ParetoPoint {
+                parameters: config.parameters,
+                nprobe: stats.nprobe,
+                mean_seconds: stats.seconds.mean,
+                mean_recall: recalls.mean,
+            })
+        }))
+        .collect();
+    let is_dominated = |p: &ParetoPoint| points.iter().any(|q| {
+        q.mean_recall >= p.mean_recall
+            && q.mean_seconds <= p.mean_seconds
+            && (q.mean_recall > p.mean_recall || q.mean_seconds < p.mean_seconds)
+    });
+    let mut front: Vec<ParetoPoint> = points
+        .iter()
+        .filter(|p| !is_dominated(p))
+        .copied()
+        .collect();
+    front.sort_by(|l, r| l.mean_seconds.partial_cmp(&r.mean_seconds).unwrap());
+    front
+}
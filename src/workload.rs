@@ -0,0 +1,292 @@
+//! Query workloads derived from the query vectorset and the dataset.
+//!
+//! A workload is a stream of queries that mixes the following kinds at given
+//! weights:
+//! - `sequential`: query vectors in order, wrapping around
+//! - `zipf`: query vectors sampled from a Zipfian distribution, so that a few
+//!   hot queries are repeated many times
+//! - `noise`: randomly chosen query vectors perturbed with Gaussian noise;
+//!   near-duplicates
+//! - `base`: randomly chosen vectors in the dataset; their exact nearest
+//!   neighbor is themselves (self-recall)
+//! - `ood`: random vectors uniformly distributed in the bounding box of the
+//!   dataset; out of the distribution of the dataset
+//!
+//! The same components, parameters, and seed always give the same workload.
+
+use core::str::FromStr;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use flechasdb::vector::BlockVectorSet;
+
+use crate::error::Error;
+
+/// Kind of queries in a workload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkloadKind {
+    /// Query vectors in order.
+    Sequential,
+    /// Query vectors sampled from a Zipfian distribution.
+    Zipf,
+    /// Query vectors perturbed with Gaussian noise.
+    Noise,
+    /// Vectors in the dataset.
+    Base,
+    /// Random vectors out of the distribution of the dataset.
+    OutOfDistribution,
+}
+
+impl WorkloadKind {
+    /// Returns the name of the kind.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkloadKind::Sequential => "sequential",
+            WorkloadKind::Zipf => "zipf",
+            WorkloadKind::Noise => "noise",
+            WorkloadKind::Base => "base",
+            WorkloadKind::OutOfDistribution => "ood",
+        }
+    }
+}
+
+impl FromStr for WorkloadKind {
+    type Err = Error;
+
+    /// Parses `sequential`, `zipf`, `noise`, `base`, or `ood`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequential" => Ok(WorkloadKind::Sequential),
+            "zipf" => Ok(WorkloadKind::Zipf),
+            "noise" => Ok(WorkloadKind::Noise),
+            "base" => Ok(WorkloadKind::Base),
+            "ood" => Ok(WorkloadKind::OutOfDistribution),
+            _ => Err(Error::InvalidData(format!("unknown workload: {}", s))),
+        }
+    }
+}
+
+/// Kind of queries with its weight in a workload.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorkloadComponent {
+    /// Kind of queries.
+    pub kind: WorkloadKind,
+    /// Relative weight.
+    pub weight: f64,
+}
+
+impl FromStr for WorkloadComponent {
+    type Err = Error;
+
+    /// Parses `<KIND>` or `<KIND>:<WEIGHT>`.
+    ///
+    /// The weight is 1 if omitted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, weight) = match s.split_once(':') {
+            Some((kind, weight)) => {
+                let weight: f64 = weight.parse().map_err(|_| {
+                    Error::InvalidData(format!("invalid weight: {}", weight))
+                })?;
+                (kind, weight)
+            },
+            None => (s, 1.0),
+        };
+        if !(weight > 0.0 && weight.is_finite()) {
+            return Err(Error::InvalidData(format!(
+                "weight must be positive: {}",
+                weight,
+            )));
+        }
+        Ok(Self {
+            kind: kind.parse()?,
+            weight,
+        })
+    }
+}
+
+/// Parameters of a workload.
+#[derive(Clone, Copy, Debug)]
+pub struct WorkloadParameters {
+    /// Exponent of the Zipfian distribution.
+    pub zipf_exponent: f64,
+    /// Standard deviation of the noise relative to that of the components of
+    /// the dataset.
+    pub noise_scale: f64,
+    /// Seed of the random number generator.
+    pub seed: u64,
+}
+
+/// Workload.
+pub struct Workload {
+    /// Query vectors in order.
+    pub queries: BlockVectorSet<f32>,
+    /// Kind of each query.
+    pub kinds: Vec<WorkloadKind>,
+}
+
+impl Workload {
+    /// Generates a workload of `num_queries` queries.
+    ///
+    /// `qvs` is the query vectorset, and `vs` is the dataset.
+    pub fn generate(
+        components: &[WorkloadComponent],
+        parameters: &WorkloadParameters,
+        qvs: &BlockVectorSet<f32>,
+        vs: &BlockVectorSet<f32>,
+        num_queries: usize,
+    ) -> Result<Self, Error> {
+        if components.is_empty() {
+            return Err(Error::InvalidData("empty workload".to_string()));
+        }
+        if qvs.vector_size() != vs.vector_size() {
+            return Err(Error::InvalidData(format!(
+                "vector sizes of queries and dataset differ: {} ≠ {}",
+                qvs.vector_size(),
+                vs.vector_size(),
+            )));
+        }
+        let mut rng = StdRng::seed_from_u64(parameters.seed);
+        let has = |kind| components.iter().any(|c| c.kind == kind);
+        let zipf = if has(WorkloadKind::Zipf) {
+            Some(Zipf::new(qvs.len(), parameters.zipf_exponent, &mut rng))
+        } else {
+            None
+        };
+        let noise_std = if has(WorkloadKind::Noise) {
+            parameters.noise_scale * component_std(vs)
+        } else {
+            0.0
+        };
+        let bounds = if has(WorkloadKind::OutOfDistribution) {
+            Some(bounding_box(vs))
+        } else {
+            None
+        };
+        let total_weight: f64 = components.iter().map(|c| c.weight).sum();
+        let vector_size = vs.vector_size();
+        let mut block: Vec<f32> = Vec::with_capacity(vector_size * num_queries);
+        let mut kinds: Vec<WorkloadKind> = Vec::with_capacity(num_queries);
+        let mut next_sequential = 0usize;
+        for _ in 0..num_queries {
+            let kind = choose_kind(components, total_weight, &mut rng);
+            match kind {
+                WorkloadKind::Sequential => {
+                    block.extend_from_slice(qvs.get(next_sequential % qvs.len()));
+                    next_sequential += 1;
+                },
+                WorkloadKind::Zipf => {
+                    let i = zipf.as_ref().unwrap().sample(&mut rng);
+                    block.extend_from_slice(qvs.get(i));
+                },
+                WorkloadKind::Noise => {
+                    let qv = qvs.get(rng.gen_range(0..qvs.len()));
+                    for &x in qv {
+                        block.push(x + (noise_std * standard_normal(&mut rng)) as f32);
+                    }
+                },
+                WorkloadKind::Base => {
+                    block.extend_from_slice(vs.get(rng.gen_range(0..vs.len())));
+                },
+                WorkloadKind::OutOfDistribution => {
+                    let (min, max) = bounds.as_ref().unwrap();
+                    for (&lo, &hi) in min.iter().zip(max.iter()) {
+                        block.push(if lo < hi { rng.gen_range(lo..hi) } else { lo });
+                    }
+                },
+            }
+            kinds.push(kind);
+        }
+        Ok(Self {
+            queries: BlockVectorSet::chunk(block, vector_size.try_into().unwrap())?,
+            kinds,
+        })
+    }
+}
+
+// Zipfian distribution over indices.
+//
+// Ranks are assigned to indices in a random order, so that hot queries are
+// not biased to the beginning of the query vectorset.
+struct Zipf {
+    // cumulative probabilities of ranks.
+    cdf: Vec<f64>,
+    // index at each rank.
+    indices: Vec<usize>,
+}
+
+impl Zipf {
+    fn new(n: usize, exponent: f64, rng: &mut impl Rng) -> Self {
+        let mut cdf: Vec<f64> = Vec::with_capacity(n);
+        let mut sum = 0.0f64;
+        for rank in 1..=n {
+            sum += 1.0 / (rank as f64).powf(exponent);
+            cdf.push(sum);
+        }
+        for p in cdf.iter_mut() {
+            *p /= sum;
+        }
+        let mut indices: Vec<usize> = (0..n).collect();
+        indices.shuffle(rng);
+        Self { cdf, indices }
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> usize {
+        let u: f64 = rng.gen();
+        let rank = self.cdf
+            .partition_point(|&p| p < u)
+            .min(self.indices.len() - 1);
+        self.indices[rank]
+    }
+}
+
+// Chooses a kind of queries at the weights of components.
+fn choose_kind(
+    components: &[WorkloadComponent],
+    total_weight: f64,
+    rng: &mut impl Rng,
+) -> WorkloadKind {
+    let mut u = rng.gen::<f64>() * total_weight;
+    for component in components.iter() {
+        if u < component.weight {
+            return component.kind;
+        }
+        u -= component.weight;
+    }
+    components.last().unwrap().kind
+}
+
+// Samples the standard normal distribution with the Box–Muller transform.
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>(); // (0, 1]
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+// Computes the standard deviation of all the components of vectors.
+fn component_std(vs: &BlockVectorSet<f32>) -> f64 {
+    let mut sum = 0.0f64;
+    let mut sum_squares = 0.0f64;
+    for i in 0..vs.len() {
+        for &x in vs.get(i) {
+            sum += x as f64;
+            sum_squares += (x as f64) * (x as f64);
+        }
+    }
+    let n = (vs.len() * vs.vector_size()) as f64;
+    let mean = sum / n;
+    (sum_squares / n - mean * mean).max(0.0).sqrt()
+}
+
+// Computes the minimum and maximum of each dimension.
+fn bounding_box(vs: &BlockVectorSet<f32>) -> (Vec<f32>, Vec<f32>) {
+    let mut min = vec![f32::INFINITY; vs.vector_size()];
+    let mut max = vec![f32::NEG_INFINITY; vs.vector_size()];
+    for i in 0..vs.len() {
+        for (d, &x) in vs.get(i).iter().enumerate() {
+            min[d] = min[d].min(x);
+            max[d] = max[d].max(x);
+        }
+    }
+    (min, max)
+}
@@ -0,0 +1,120 @@
+//! Builds, loads, and queries a tiny database in a `MemoryFileSystem`.
+
+use std::collections::HashSet;
+
+use flechasdb::asyncdb::stored::{
+    Database as AsyncDatabase,
+    LoadDatabase as _,
+};
+use flechasdb::db::AttributeValue;
+use flechasdb::db::build::{DatabaseBuilder, proto::serialize_database};
+use flechasdb::db::stored::{self, LoadDatabase as _};
+use flechasdb::vector::BlockVectorSet;
+
+use flechasdb_benchmark::memory::MemoryFileSystem;
+
+const NUM_CLUSTERS: usize = 4;
+const CLUSTER_SIZE: usize = 16;
+const NUM_VECTORS: usize = NUM_CLUSTERS * CLUSTER_SIZE;
+const VECTOR_SIZE: usize = 4;
+const NUM_PARTITIONS: usize = 2;
+const K: usize = 5;
+
+// Makes vectors scattered around well separated cluster centers.
+fn make_vectors() -> BlockVectorSet<f32> {
+    let mut block: Vec<f32> = Vec::with_capacity(NUM_VECTORS * VECTOR_SIZE);
+    for i in 0..NUM_VECTORS {
+        let center = (i / CLUSTER_SIZE) as f32 * 100.0;
+        for j in 0..VECTOR_SIZE {
+            block.push(center + ((i * 7 + j * 3) % 11) as f32 * 0.1);
+        }
+    }
+    BlockVectorSet::chunk(block, VECTOR_SIZE.try_into().unwrap()).unwrap()
+}
+
+// Builds a database over given vectors into a new `MemoryFileSystem`.
+//
+// Returns the file system and the path of the database header file.
+fn build_in_memory(vs: BlockVectorSet<f32>) -> (MemoryFileSystem, String) {
+    let mut db = DatabaseBuilder::new(vs)
+        .with_partitions(NUM_PARTITIONS.try_into().unwrap())
+        .with_divisions(2.try_into().unwrap())
+        .with_clusters(4.try_into().unwrap())
+        .build()
+        .unwrap();
+    for i in 0..db.num_vectors() {
+        db.set_attribute_at(i, ("datum_id", i as u64)).unwrap();
+    }
+    let mut fs = MemoryFileSystem::new();
+    serialize_database(&db, &mut fs).unwrap();
+    let mut header_paths = fs.find_header_files();
+    assert_eq!(header_paths.len(), 1);
+    (fs, header_paths.pop().unwrap())
+}
+
+// Checks the datum IDs of results.
+fn check_datum_ids(datum_ids: &[u64]) {
+    assert_eq!(datum_ids.len(), K);
+    let unique: HashSet<u64> = datum_ids.iter().copied().collect();
+    assert_eq!(unique.len(), K);
+    assert!(datum_ids.iter().all(|&id| (id as usize) < NUM_VECTORS));
+}
+
+// Queries a synchronous database and returns the datum IDs of the results.
+fn query_datum_ids<FS>(db: &stored::Database<f32, FS>, qv: &[f32]) -> Vec<u64>
+where
+    FS: flechasdb::io::FileSystem,
+{
+    db
+        .query(qv, K.try_into().unwrap(), NUM_PARTITIONS.try_into().unwrap())
+        .unwrap()
+        .iter()
+        .map(|result| match result.get_attribute("datum_id").unwrap() {
+            Some(value) => match value.as_ref() {
+                AttributeValue::Uint64(id) => *id,
+                value => panic!("datum_id is not a u64 but {:?}", value),
+            },
+            None => panic!("missing datum_id"),
+        })
+        .collect()
+}
+
+#[test]
+fn query_database_in_memory() {
+    let vs = make_vectors();
+    let qv = vs.get(0).to_vec();
+    let (fs, header_path) = build_in_memory(vs);
+    let db = stored::Database::<f32, _>::load_database(fs, &header_path)
+        .unwrap();
+    let datum_ids = query_datum_ids(&db, &qv);
+    check_datum_ids(&datum_ids);
+    // results are in the cluster of the query vector
+    assert!(datum_ids.iter().all(|&id| (id as usize) < CLUSTER_SIZE));
+}
+
+#[tokio::test]
+async fn query_database_in_memory_async() {
+    let vs = make_vectors();
+    let qv = vs.get(0).to_vec();
+    let (fs, header_path) = build_in_memory(vs);
+    let db = AsyncDatabase::<f32, _>::load_database(
+        fs.clone(),
+        header_path.clone(),
+    ).await.unwrap();
+    let results = db
+        .query(&qv, K.try_into().unwrap(), NUM_PARTITIONS.try_into().unwrap())
+        .await
+        .unwrap();
+    let mut datum_ids: Vec<u64> = Vec::with_capacity(results.len());
+    for result in results.iter() {
+        match result.get_attribute("datum_id").await.unwrap() {
+            Some(AttributeValue::Uint64(id)) => datum_ids.push(id),
+            value => panic!("datum_id is not a u64 but {:?}", value),
+        }
+    }
+    check_datum_ids(&datum_ids);
+    // the synchronous database returns the same results
+    let sync_db = stored::Database::<f32, _>::load_database(fs, &header_path)
+        .unwrap();
+    assert_eq!(datum_ids, query_datum_ids(&sync_db, &qv));
+}